@@ -0,0 +1,15 @@
+#![feature(i128)]
+#![feature(i128_type)]
+#![feature(use_nested_groups)]
+
+// the features above are for the nightlies this was first written against, the lints below go against how it's written:
+// errors are reported by `response!` as they're found, leaving `Result<_, ()>` to say whether any were
+#![allow(stable_features, bare_trait_objects)]
+#![allow(clippy::unit_arg, clippy::result_unit_err, clippy::new_without_default, clippy::should_implement_trait)]
+#![allow(clippy::needless_borrowed_reference, clippy::needless_arbitrary_self_type, clippy::match_like_matches_macro, clippy::legacy_numeric_constants)]
+#![allow(clippy::explicit_auto_deref, clippy::borrow_deref_ref, clippy::module_inception)]
+
+extern crate colored;
+
+#[macro_use]
+pub mod snek;
@@ -1,62 +1,243 @@
-#![feature(i128)]
-#![feature(i128_type)]
-#![feature(use_nested_groups)]
+#[macro_use]
+extern crate snek;
 
-extern crate colored;
+use snek::snek::lexer::*;
+use snek::snek::syntax::*;
+use snek::snek::parser::*;
+use snek::snek::interpreter::*;
+use snek::snek::module::*;
+use snek::snek::doc::*;
+use snek::snek::lsp::Server;
+use snek::snek::error::Response::Wrong;
 
-mod snek;
-use snek::lexer::*;
-use snek::parser::*;
-use snek::visitor::*;
-use snek::interpreter::*;
+use std::env;
+use std::process;
+use std::path::{ Path, PathBuf };
+use std::fs::File;
+use std::io::{ self, Read, Write };
+use std::time::{ Duration, Instant };
 
-fn main() {
-  let content = r#"
-foo := fun(a: int) -> int {
-  a + 100
+fn search_path() -> Vec<PathBuf> {
+  env::var_os("SNEK_PATH").map(|paths| env::split_paths(&paths).collect()).unwrap_or_default()
 }
 
-bar := foo(10)
+// formats files in place, or stdin to stdout, `--check` only reports what would change
+fn fmt(args: Vec<String>) -> i32 {
+  let check = args.iter().any(|arg| arg == "--check");
+  let paths = args.into_iter().filter(|arg| arg != "--check").collect::<Vec<String>>();
+
+  let mut code = 0;
 
-bar
-  "#;
+  if paths.is_empty() || paths == ["-"] {
+    let mut content = String::new();
 
-  let source = Source::from("<static.wu>", content.lines().map(|x| x.into()).collect::<Vec<String>>());
-  let lexer  = Lexer::default(content.chars().collect(), &source);
+    if io::stdin().read_to_string(&mut content).is_err() {
+      return 1
+    }
 
-  let mut tokens = Vec::new();
+    let source = Source::from_text("<stdin>", content);
 
-  for token_result in lexer {
-    if let Ok(token) = token_result {
-      tokens.push(token)
-    } else {
-      return
+    return match format_source(&source) {
+      Ok(ref formatted) if check => if *formatted != source.content {
+        println!("would reformat <stdin>");
+        1
+      } else {
+        0
+      },
+
+      Ok(formatted) => {
+        print!("{}", formatted);
+        0
+      },
+
+      Err(_) => 1,
+    }
+  }
+
+  for path in paths {
+    if !Path::new(&path).is_file() {
+      response!(
+        Wrong(format!("can't find file `{}`", path))
+      );
+
+      code = 1;
+      continue
+    }
+
+    let source = Source::new(path.clone());
+
+    let formatted = match format_source(&source) {
+      Ok(formatted) => formatted,
+      Err(_)        => {
+        code = 1;
+        continue
+      },
+    };
+
+    if formatted == source.content {
+      continue
+    }
+
+    if check {
+      println!("would reformat {}", path);
+      code = 1
+    } else if File::create(&path).and_then(|mut file| file.write_all(formatted.as_bytes())).is_err() {
+      response!(
+        Wrong(format!("can't write to file `{}`", path))
+      );
+
+      code = 1
+    }
+  }
+
+  code
+}
+
+// documents the top-level declarations of a file as Markdown, or as HTML with `--html`
+fn doc(args: Vec<String>) -> i32 {
+  let html  = args.iter().any(|arg| arg == "--html");
+  let paths = args.into_iter().filter(|arg| arg != "--html").collect::<Vec<String>>();
+
+  let path = match paths.first() {
+    Some(path) if paths.len() == 1 && Path::new(path).is_file() => path.clone(),
+
+    Some(path) if paths.len() == 1 => {
+      response!(
+        Wrong(format!("can't find file `{}`", path))
+      );
+
+      return 1
+    },
+
+    _ => {
+      println!("usage: snek doc [--html] <file>");
+
+      return 1
+    },
+  };
+
+  match Documentation::new(&Source::new(path), search_path()) {
+    Ok(documentation) => {
+      print!("{}", if html { documentation.html() } else { documentation.markdown() });
+
+      0
+    },
+
+    Err(_) => 1,
+  }
+}
+
+// a language server for editors, over stdin and stdout
+fn lsp() -> i32 {
+  let stdin  = io::stdin();
+  let stdout = io::stdout();
+
+  Server::new(search_path()).run(stdin.lock(), stdout.lock())
+}
+
+// times edits to a generated file of `lines` lines going through the incremental parser,
+// checking that what it ends up with is what parsing the whole file gives
+fn bench_edits(args: Vec<String>) -> i32 {
+  let lines = args.first().and_then(|lines| lines.parse::<usize>().ok()).unwrap_or(10_000);
+
+  let mut text = String::new();
+
+  for i in 0 .. lines.div_ceil(10) {
+    text.push_str(&format!(
+      "-- scales by {0}\nvalue_{0} := {0}\nscale_{0} := fun(x: int) -> int {{\n  if x > value_{0} {{\n    x * 2\n  }} else {{\n    x + value_{0}\n  }}\n}}\n\n",
+      i
+    ))
+  }
+
+  let millis = |duration: Duration| duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1_000_000.0;
+
+  let started = Instant::now();
+  let whole   = shape(&Source::from_text("bench.snek", text.clone()));
+  let parsing = started.elapsed();
+
+  let started      = Instant::now();
+  let mut document = Document::new("bench.snek", &text);
+  let chunking     = started.elapsed();
+
+  let mut times = Vec::new();
+
+  {
+    let mut edit = |document: &mut Document, start: (usize, usize), end: (usize, usize), text: &str| {
+      let started = Instant::now();
+
+      document.edit(start, end, text);
+      times.push(started.elapsed())
+    };
+
+    // typing into a declaration, breaking a line and mending it, and changing an operator in a function
+    for cycle in 0 .. 100 {
+      let line   = (cycle * 7919) % (lines / 10).max(1) * 10 + 1;
+      let length = document.lines()[line].chars().count();
+
+      edit(&mut document, (line, length), (line, length), "1");
+      edit(&mut document, (line, length), (line, length + 1), "");
+      edit(&mut document, (line, length), (line, length), "\nextra := \"");
+      edit(&mut document, (line + 1, 10), (line + 1, 10), "\"");
+      edit(&mut document, (line, length), (line + 1, 11), "");
+      edit(&mut document, (line + 2, 7), (line + 2, 8), "<");
+      edit(&mut document, (line + 2, 7), (line + 2, 8), ">");
     }
   }
 
-  let tokens_ref = tokens.iter().map(|x| &*x).collect::<Vec<&Token>>();
+  let total   = times.iter().fold(Duration::new(0, 0), |total, time| total + *time);
+  let slowest = times.iter().max().cloned().unwrap_or_default();
+
+  let parsed  = document.chunks().iter().flat_map(|chunk| erase_all(chunk.statements())).collect::<Vec<Statement>>();
+  let matches = document.text() == text && whole == Ok(parsed);
 
-  let mut parser = Parser::new(tokens_ref, &source);
-  
-  match parser.parse() {
-    Ok(ast) => {
-      println!("{:#?}", ast);
-  
-      let mut visitor = Visitor::new(&source, &ast);
+  println!("lines:          {}", text.lines().count());
+  println!("chunks:         {}", document.chunks().len());
+  println!("whole parse:    {:.2}ms", millis(parsing));
+  println!("first chunking: {:.2}ms", millis(chunking));
+  println!("edits:          {}, {:.3}ms on average, {:.3}ms at most", times.len(), millis(total) / times.len() as f64, millis(slowest));
+  println!("matches whole:  {}", if matches { "yes" } else { "no" });
+
+  if matches { 0 } else { 1 }
+}
+
+fn main() {
+  match env::args().nth(1) {
+    Some(ref command) if command == "fmt" => process::exit(fmt(env::args().skip(2).collect())),
+    Some(ref command) if command == "doc" => process::exit(doc(env::args().skip(2).collect())),
+    Some(ref command) if command == "lsp" => process::exit(lsp()),
 
-      match visitor.visit() {
-        Ok(_) => {
-          let mut vm = VirtualMachine::new();
-          let block  = Compiler::new(&mut vm, &source).compile_main(&ast, "entry").unwrap();
+    Some(ref command) if command == "grammar" => print!("{}", textmate_grammar()),
 
-          vm.execute(&block);
+    Some(ref command) if command == "bench-edits" => process::exit(bench_edits(env::args().skip(2).collect())),
 
-          println!("{:#?}", vm.stack)
-        },
-        _     => ()
-      }
+
+    Some(path) => if Path::new(&path).is_file() {
+      let mut vm = VirtualMachine::new();
+
+      vm.capabilities = Capabilities::all();
+      vm.args         = env::args().skip(2).collect();
+
+      let code = match ModuleLoader::new(search_path()).run(&mut vm, &Source::new(path)) {
+        Ok(_)  => vm.exit_code.unwrap_or(0),
+        Err(_) => vm.exit_code.unwrap_or(1),
+      };
+
+      process::exit(code)
+    } else {
+      response!(
+        Wrong(format!("can't find file `{}`", path))
+      );
+
+      process::exit(1)
     },
 
-    _ => (),
+    None => {
+      println!("usage: snek <file> [args...]");
+      println!("       snek fmt [--check] [files...]");
+      println!("       snek doc [--html] <file>");
+      println!("       snek lsp");
+      println!("       snek grammar");
+      println!("       snek bench-edits [lines]")
+    },
   }
 }
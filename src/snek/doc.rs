@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+
+use super::lexer::*;
+use super::parser::*;
+use super::visitor::*;
+use super::syntax::*;
+use super::module::ModuleLoader;
+use super::interpreter::VirtualMachine;
+
+
+
+// a top-level declaration and the doc comment right above it
+pub struct Item {
+  pub name:     String,
+  pub t:        Type,
+  pub exported: bool,
+  pub doc:      String,
+}
+
+pub struct Documentation {
+  pub title: String,
+  pub doc:   String, // the doc comment opening the file, set apart by a blank line
+  pub items: Vec<Item>,
+}
+
+impl Documentation {
+  pub fn new(source: &Source, search_path: Vec<PathBuf>) -> Result<Self, ()> {
+    let ast = Parser::parse_source(source)?;
+
+    // types come from checking the file for real, imports and all
+    let mut vm      = VirtualMachine::new();
+    let mut loader  = ModuleLoader::new(search_path);
+
+    loader.checking = true;
+
+    let mut visitor = Visitor::new(source, &ast);
+
+    visitor.imports = loader.load_imports(&mut vm, &ast, source, "")?;
+    visitor.visit()?;
+
+    let (doc, docs) = Self::doc_comments(&Cst::new(source)?);
+
+    let mut items: Vec<Item> = Vec::new();
+
+    for statement in &ast {
+      let line = match source.locate(statement.pos) {
+        Some((line, _)) => line,
+        None            => continue,
+      };
+
+      let (declaration, exported) = match statement.node {
+        StatementNode::Export(ref declaration) => (&**declaration, true),
+        _                                      => (statement, false),
+      };
+
+      let left = match declaration.node {
+        StatementNode::Variable(_, ref left, _) | StatementNode::Constant(_, ref left, _) => left,
+        _                                                                                 => continue,
+      };
+
+      let names = match left.node {
+        ExpressionNode::Set(ref names) => names.iter().collect(),
+        _                              => vec!(left),
+      };
+
+      for name in names {
+        if let ExpressionNode::Identifier(ref name) = name.node {
+          // only the first declaration of a name is listed, with the type it ends up with
+          if items.iter().any(|item| item.name == *name) {
+            continue
+          }
+
+          items.push(
+            Item {
+              name: name.clone(),
+              t:    visitor.global_type(name)?,
+              doc:  docs.get(&line).cloned().unwrap_or_default(),
+              exported,
+            }
+          )
+        }
+      }
+    }
+
+    let title = Path::new(&source.file.0).file_stem().map_or(source.file.0.clone(), |stem| stem.to_string_lossy().into_owned());
+
+    Ok(
+      Documentation {
+        title,
+        doc,
+        items,
+      }
+    )
+  }
+
+  // doc comments by the line of the top-level statement they come right before
+  fn doc_comments(cst: &Cst) -> (String, HashMap<usize, String>) {
+    let mut docs    = HashMap::new();
+    let mut module  = String::new();
+    let mut pending = Vec::new();
+
+    let mut depth      = 0;
+    let mut line_start = true;
+    let mut declared   = false;
+
+    for token in cst.tokens() {
+      let eol = token.token.token_type == TokenType::EOL;
+
+      if depth == 0 && line_start {
+        pending.extend(token.leading.iter().filter_map(Trivia::doc));
+
+        if !eol {
+          if !pending.is_empty() {
+            docs.insert(token.token.line.0, pending.join("\n"));
+            pending.clear()
+          }
+
+          declared = true
+
+        // a blank line ends a doc comment, other comments may come in between
+        } else if !token.leading.iter().any(Trivia::is_comment) {
+          if !declared && module.is_empty() {
+            module = pending.join("\n")
+          }
+
+          pending.clear()
+        }
+      }
+
+      if token.opens() {
+        depth += 1
+      } else if token.closes() && depth > 0 {
+        depth -= 1
+      }
+
+      line_start = eol
+    }
+
+    (module, docs)
+  }
+
+
+
+  fn is_item(&self, name: &str) -> bool {
+    self.items.iter().any(|item| item.name == name)
+  }
+
+  // splits a signature into names of other documented items and whatever is between them
+  fn runs(&self, item: &Item) -> Vec<(String, bool)> {
+    let text = Self::signature(item);
+
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    let mut word = String::new();
+
+    let is_name = |c: char| c.is_alphanumeric() || "_!?".contains(c);
+
+    for c in text.chars().chain(Some('\0')) {
+      if is_name(c) {
+        word.push(c);
+
+        continue
+      }
+
+      if !word.is_empty() {
+        let linked = word != item.name && self.is_item(&word);
+
+        match runs.last_mut() {
+          Some(&mut (ref mut run, false)) if !linked => run.push_str(&word),
+          _                                          => runs.push((word.clone(), linked)),
+        }
+
+        word.clear()
+      }
+
+      if c != '\0' {
+        match runs.last_mut() {
+          Some(&mut (ref mut run, false)) => run.push(c),
+          _                               => runs.push((c.to_string(), false)),
+        }
+      }
+    }
+
+    runs
+  }
+
+  fn signature(item: &Item) -> String {
+    format!("{}{}: {}", if item.exported { "export " } else { "" }, item.name, item.t)
+  }
+
+
+
+  pub fn markdown(&self) -> String {
+    let mut out = format!("# {}\n\n", self.title);
+
+    if !self.doc.is_empty() {
+      out.push_str(&format!("{}\n\n", self.markdown_text(&self.doc)))
+    }
+
+    for item in &self.items {
+      out.push_str(&format!("- [`{}`](#{})\n", item.name, item.name))
+    }
+
+    for item in &self.items {
+      out.push_str(&format!("\n<a id=\"{}\"></a>\n## `{}`\n\n", item.name, item.name));
+
+      // code spans can't hold links, so they're broken up around them
+      for (run, linked) in self.runs(item) {
+        if linked {
+          out.push_str(&format!("[`{}`](#{})", run, run))
+        } else {
+          out.push_str(&format!("`{}`", run))
+        }
+      }
+
+      out.push('\n');
+
+      if !item.doc.is_empty() {
+        out.push_str(&format!("\n{}\n", self.markdown_text(&item.doc)))
+      }
+    }
+
+    out
+  }
+
+  // `name` in a doc comment links to the item of that name
+  fn markdown_text(&self, text: &str) -> String {
+    let mut out = String::new();
+
+    for (i, part) in text.split('`').enumerate() {
+      if i % 2 == 0 {
+        out.push_str(part)
+      } else if self.is_item(part) {
+        out.push_str(&format!("[`{}`](#{})", part, part))
+      } else {
+        out.push_str(&format!("`{}`", part))
+      }
+    }
+
+    out
+  }
+
+
+
+  pub fn html(&self) -> String {
+    let mut out = format!(
+      "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+      escape(&self.title), STYLE, escape(&self.title)
+    );
+
+    if !self.doc.is_empty() {
+      out.push_str(&self.html_text(&self.doc))
+    }
+
+    out.push_str("<ul>\n");
+
+    for item in &self.items {
+      out.push_str(&format!("<li><a href=\"#{}\"><code>{}</code></a></li>\n", escape(&item.name), escape(&item.name)))
+    }
+
+    out.push_str("</ul>\n");
+
+    for item in &self.items {
+      out.push_str(&format!("<h2 id=\"{}\"><code>{}</code></h2>\n<pre><code>", escape(&item.name), escape(&item.name)));
+
+      for (run, linked) in self.runs(item) {
+        if linked {
+          out.push_str(&format!("<a href=\"#{}\">{}</a>", escape(&run), escape(&run)))
+        } else {
+          out.push_str(&escape(&run))
+        }
+      }
+
+      out.push_str("</code></pre>\n");
+
+      if !item.doc.is_empty() {
+        out.push_str(&self.html_text(&item.doc))
+      }
+    }
+
+    out.push_str("</body>\n</html>\n");
+
+    out
+  }
+
+  // paragraphs are separated by blank lines
+  fn html_text(&self, text: &str) -> String {
+    let mut out = String::new();
+
+    for paragraph in text.split("\n\n").filter(|paragraph| !paragraph.trim().is_empty()) {
+      out.push_str("<p>");
+
+      for (i, part) in paragraph.trim().split('`').enumerate() {
+        if i % 2 == 0 {
+          out.push_str(&escape(part))
+        } else if self.is_item(part) {
+          out.push_str(&format!("<a href=\"#{}\"><code>{}</code></a>", escape(part), escape(part)))
+        } else {
+          out.push_str(&format!("<code>{}</code>", escape(part)))
+        }
+      }
+
+      out.push_str("</p>\n")
+    }
+
+    out
+  }
+}
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 48em; margin: 2em auto; line-height: 1.5 }
+code, pre { font-family: monospace; background: #f5f5f5 }
+pre { padding: 0.5em 1em }
+h2 { margin-top: 2em; border-bottom: 1px solid #ddd }";
+
+fn escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
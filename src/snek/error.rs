@@ -1,6 +1,10 @@
 use std::fmt;
+use std::cell::RefCell;
 use colored::Colorize;
 
+use super::lexer::TokenElement;
+use super::source::{ FilePath, Location };
+
 pub enum Response<T: fmt::Display> {
   Wrong(T),
   Weird(T),
@@ -12,10 +16,20 @@ use self::Response::*;
 #[macro_export]
 macro_rules! response {
   ( $( $r:expr ),+ ) => {{
-    $(
-        print!("{}", $r);
-    )*
-    println!("");
+    if $crate::snek::error::collecting() {
+      let mut diagnostic = $crate::snek::error::Diagnostic::default();
+
+      $(
+          $crate::snek::error::Report::report(&$r, &mut diagnostic);
+      )*
+
+      $crate::snek::error::push(diagnostic)
+    } else {
+      $(
+          print!("{}", $r);
+      )*
+      println!("");
+    }
   }};
 }
 
@@ -34,4 +48,77 @@ impl<T: fmt::Display> fmt::Display for Response<T> {
 
     write!(f, "{}", message)
   }
-}
\ No newline at end of file
+}
+
+
+
+// a response kept as data instead of printed, for tools that show errors themselves
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostic {
+  pub message:  String,
+  pub severity: u8, // 1 when wrong, 2 when weird and 3 for notes
+  pub file:     Option<String>,
+  pub location: Option<Location>,
+}
+
+// the parts a response is made of, each filling in what it knows
+pub trait Report {
+  fn report(&self, diagnostic: &mut Diagnostic);
+}
+
+impl<T: fmt::Display> Report for Response<T> {
+  fn report(&self, diagnostic: &mut Diagnostic) {
+    let (severity, message) = match *self {
+      Wrong(ref m) => (1, m),
+      Weird(ref m) => (2, m),
+      Note(ref m)  => (3, m),
+    };
+
+    diagnostic.severity = severity;
+    diagnostic.message  = message.to_string()
+  }
+}
+
+impl Report for FilePath {
+  fn report(&self, diagnostic: &mut Diagnostic) {
+    diagnostic.file = Some(self.0.clone())
+  }
+}
+
+impl<'t> Report for TokenElement<'t> {
+  fn report(&self, diagnostic: &mut Diagnostic) {
+    if diagnostic.location.is_none() {
+      diagnostic.location = self.location()
+    }
+  }
+}
+
+impl<R: Report + ?Sized> Report for &R {
+  fn report(&self, diagnostic: &mut Diagnostic) {
+    (**self).report(diagnostic)
+  }
+}
+
+thread_local! {
+  static DIAGNOSTICS: RefCell<Option<Vec<Diagnostic>>> = const { RefCell::new(None) };
+}
+
+pub fn collecting() -> bool {
+  DIAGNOSTICS.with(|diagnostics| diagnostics.borrow().is_some())
+}
+
+pub fn push(diagnostic: Diagnostic) {
+  DIAGNOSTICS.with(|diagnostics| if let Some(ref mut diagnostics) = *diagnostics.borrow_mut() {
+    diagnostics.push(diagnostic)
+  })
+}
+
+// runs `f` with responses collected rather than printed
+pub fn collect<T, F: FnOnce() -> T>(f: F) -> (T, Vec<Diagnostic>) {
+  let outer  = DIAGNOSTICS.with(|diagnostics| diagnostics.replace(Some(Vec::new())));
+  let result = f();
+
+  let collected = DIAGNOSTICS.with(|diagnostics| diagnostics.replace(outer));
+
+  (result, collected.unwrap_or_default())
+}
@@ -1,11 +1,14 @@
 use super::*;
 use super::error::Response::Wrong;
-use super::source::Source;
+use super::source::{ Source, Span };
+use super::module::global_name;
+use super::visitor::TypeNode;
 
 
 use std::mem;
+use std::rc::Rc;
 
-use std::collections::HashMap;
+use std::collections::{ HashMap, HashSet };
 
 #[derive(Clone, Copy)]
 struct JumpPatch(usize);
@@ -20,26 +23,56 @@ pub struct CompiledBlock {
   pub code:   Box<[Instruction]>,
   pub consts: Vec<Value>,
   pub locals: Box<[String]>,
+  pub arity:  usize,
+}
+
+
+
+// pending `break` and `continue` jumps of the innermost loops
+struct LoopPatches {
+  breaks:    Vec<JumpPatch>,
+  continues: Vec<JumpPatch>,
+  tries:     usize, // `try`s entered outside of the loop
 }
 
 
 
 pub struct Compiler<'c> {
-  locals: HashMap<String, u32>,
-  code:   Vec<Instruction>,
-  consts: Vec<Value>,
-  vm:     &'c mut VirtualMachine,
+  locals:      HashMap<String, u32>, // names visible in the current scope
+  constants:   HashMap<String, Value>, // folded constants, inlined where referred to
+  literals:    Rc<HashMap<Span, IntKind>>, // int literals the visitor gave a sized type
+  builtins:    Rc<HashSet<Span>>,   // names the visitor resolved to built-in operations
+  local_names: Vec<String>,          // every slot of the frame
+  loops:       Vec<LoopPatches>,
+  tries:       usize,                // `try`s entered in the current frame
+  code:        Vec<Instruction>,
+  consts:      Vec<Value>,
+  vm:          &'c mut VirtualMachine,
+  module:      String,
+  depth:       usize,                // 0 while compiling top-level statements
 
   source: &'c Source,
 }
 
 impl<'c> Compiler<'c> {
   pub fn new(vm: &'c mut VirtualMachine, source: &'c Source) -> Self {
+    Self::module(vm, source, "")
+  }
+
+  pub fn module(vm: &'c mut VirtualMachine, source: &'c Source, module: &str) -> Self {
     Compiler {
-      locals: HashMap::new(),
-      code:   Vec::new(),
-      consts: Vec::new(),
+      locals:      HashMap::new(),
+      constants:   HashMap::new(),
+      literals:    Rc::new(HashMap::new()),
+      builtins:    Rc::new(HashSet::new()),
+      local_names: Vec::new(),
+      loops:       Vec::new(),
+      tries:       0,
+      code:        Vec::new(),
+      consts:      Vec::new(),
       vm,
+      module:      module.to_string(),
+      depth:       0,
 
       source,
     }
@@ -47,12 +80,20 @@ impl<'c> Compiler<'c> {
 
 
 
+  // declaring a name again shadows it with a fresh slot
   fn declare_local(&mut self, name: &str) -> Result<u32, ()> {
-    use std::collections::hash_map::Entry;
+    let index = self.declare_temporary(name)?;
+
+    self.locals.insert(name.to_string(), index);
+    self.constants.remove(name);
+
+    Ok(index)
+  }
 
-    let index = self.locals.len();
+  fn declare_temporary(&mut self, name: &str) -> Result<u32, ()> {
+    let index = self.local_names.len();
 
-    if index > self.locals.len() {
+    if index > u32::max_value() as usize {
       Err(
         response!(
           Wrong(format!("local overflow at `{}`", name)),
@@ -60,29 +101,111 @@ impl<'c> Compiler<'c> {
         )
       )
     } else {
-      let index = index as u32;
+      self.local_names.push(name.to_string());
 
-      let entry = self.locals.entry(name.to_string());
+      Ok(index as u32)
+    }
+  }
 
-      match entry {
-        Entry::Occupied(_) => Err(
-          response!(
-            Wrong(format!("redeclared local `{}`", name)),
-            self.source.file
-          )
-        ),
+  fn fetch_local(&mut self, name: &str) -> Result<u32, ()> {
+    Ok(self.locals.get(name).copied().unwrap())
+  }
 
-        Entry::Vacant(vacant) => {
-          vacant.insert(index);
+  // top-level names are globals, anything nested lives in the frame
+  fn declare_variable(&mut self, name: &str) -> Result<Instruction, ()> {
+    if self.depth == 0 {
+      self.constants.remove(name);
 
-          Ok(index)
-        }
-      }
+      Ok(Instruction::StoreGlobal(self.vm.declare_global(&global_name(&self.module, name))))
+    } else {
+      Ok(Instruction::StoreLocal(self.declare_local(name)?))
     }
   }
 
-  fn fetch_local(&mut self, name: &str) -> Result<u32, ()> {
-    Ok(self.locals.get(name).map(|i| *i).unwrap())
+  fn fetch_variable(&mut self, name: &str, pos: Span) -> Result<(Instruction, Instruction), ()> {
+    if self.locals.contains_key(name) {
+      let index = self.fetch_local(name)?;
+
+      Ok((Instruction::LoadLocal(index), Instruction::StoreLocal(index)))
+    } else if let Some(index) = self.vm.fetch_global(&global_name(&self.module, name)) {
+      Ok((Instruction::LoadGlobal(index), Instruction::StoreGlobal(index)))
+    } else {
+      Err(
+        response!(
+          Wrong(format!("no such value `{}` in this scope", name)),
+          self.source.file,
+          self.source.resolve(pos)
+        )
+      )
+    }
+  }
+
+  fn fold_constant(&mut self, expression: &Expression) -> Result<Option<Value>, ()> {
+    use self::ExpressionNode::*;
+
+    let value = match Parser::fold_expression(expression)?.node {
+      Int(a)    => match self.literals.get(&expression.pos) {
+        Some(&kind) => Value::Sized(a as i128, kind),
+        None        => Value::Int(a as i128),
+      },
+
+      Double(a) => Value::Double(a),
+      Bool(a)   => Value::Bool(a),
+      Char(a)   => Value::Char(a),
+
+      Str(ref content) => self.vm.allocate(HeapValueType::Str(content.clone().into_boxed_str())),
+
+      _ => return Ok(None)
+    };
+
+    Ok(Some(value))
+  }
+
+  // every name of the outermost scope, up front, as hoisted functions may read a global before
+  // its declaration has run, which the VM then reports
+  pub fn declare_globals(&mut self, names: &[String]) {
+    for name in names {
+      self.vm.declare_global(&global_name(&self.module, name));
+    }
+  }
+
+  pub fn declare_literals(&mut self, literals: HashMap<Span, IntKind>) {
+    self.literals = Rc::new(literals)
+  }
+
+  pub fn declare_builtins(&mut self, builtins: HashSet<Span>) {
+    self.builtins = Rc::new(builtins)
+  }
+
+  // top-level functions, which may be referred to before their declaration
+  fn hoisted(statement: &'c Statement) -> Option<(&'c str, &'c Expression)> {
+    use self::StatementNode::*;
+
+    match statement.node {
+      Variable(_, ref left, Some(ref right)) | Constant(_, ref left, ref right) => match (&left.node, &right.node) {
+        (&ExpressionNode::Identifier(ref name), &ExpressionNode::Function(..)) => Some((name, right)),
+        _                                                                      => None,
+      },
+
+      Export(ref statement) => Self::hoisted(statement),
+
+      _ => None,
+    }
+  }
+
+  // instructions count arguments in a byte
+  fn argument_count(&self, count: usize, pos: Span) -> Result<u8, ()> {
+    if count > u8::max_value() as usize {
+      Err(
+        response!(
+          Wrong(format!("too many arguments, a call takes at most {}", u8::max_value())),
+          self.source.file,
+          self.source.resolve(pos)
+        )
+      )
+    } else {
+      Ok(count as u8)
+    }
   }
 
   fn emit(&mut self, instr: Instruction) {
@@ -116,10 +239,17 @@ impl<'c> Compiler<'c> {
     result
   }
 
-  fn emit_branch_true(&mut self) -> JumpPatch {
+  fn emit_jump(&mut self) -> JumpPatch {
+    let result = JumpPatch(self.code.len());
+
+    self.emit(Instruction::Jump(0));
+    result
+  }
+
+  fn emit_try(&mut self) -> JumpPatch {
     let result = JumpPatch(self.code.len());
 
-    self.emit(Instruction::BranchTrue(0));
+    self.emit(Instruction::Try(0));
     result
   }
 
@@ -146,6 +276,7 @@ impl<'c> Compiler<'c> {
         Instruction::Jump(_)        => self.code[branch_pos] = Instruction::Jump(delta),
         Instruction::BranchTrue(_)  => self.code[branch_pos] = Instruction::BranchTrue(delta),
         Instruction::BranchFalse(_) => self.code[branch_pos] = Instruction::BranchFalse(delta),
+        Instruction::Try(_)         => self.code[branch_pos] = Instruction::Try(delta),
       
         _ => unreachable!(),
       }
@@ -177,39 +308,296 @@ impl<'c> Compiler<'c> {
 
 
 
-  fn compile_statement(&mut self, statement: &'c Statement<'c>) -> Result<(), ()> {
+  fn compile_statement(&mut self, statement: &'c Statement) -> Result<(), ()> {
     use self::StatementNode::*;
     use self::ExpressionNode::*;
     
     match statement.node {
-      Variable(_, ref left, ref right) => {
-        if let Identifier(ref name) = left.node {
-          if let Some(ref right) = *right {
-            self.compile_expression(&*right)?;
+      Variable(_, ref left, ref right) => self.compile_declaration(left, right.as_ref())?,
+
+      Constant(_, ref left, ref right) => {
+        let folded = match left.node {
+          Identifier(ref name) => self.fold_constant(right)?.map(|value| (name, value)),
+          _                    => None,
+        };
 
-            let index = self.declare_local(name)?;
+        match folded {
+          Some((name, value)) => {
+            // top-level constants are still stored, for importers and the host
+            if self.depth == 0 {
+              self.compile_declaration(left, Some(right))?
+            }
 
-            self.emit(Instruction::StoreLocal(index))
-          } else {
-            self.declare_local(name)?;
-          }
+            self.constants.insert(name.clone(), value);
+          },
+
+          None => self.compile_declaration(left, Some(right))?,
         }
       },
 
       Expression(ref expression) => self.compile_expression(expression)?,
 
+      Assignment(ref left, ref right) => match left.node {
+        Index(ref array, ref index) => {
+          self.compile_expression(&**array)?;
+          self.compile_expression(&**index)?;
+          self.compile_expression(right)?;
+
+          self.emit(Instruction::StoreIndex)
+        },
+
+        Identifier(ref name) => {
+          self.compile_expression(right)?;
+
+          let (_, store) = self.fetch_variable(name, left.pos)?;
+
+          self.emit(store)
+        },
+
+        Set(ref names) => {
+          self.compile_expression(right)?;
+
+          self.emit(Instruction::Unpack(names.len() as u32));
+
+          for name in names.iter().rev() {
+            if let Identifier(ref identifier) = name.node {
+              let (_, store) = self.fetch_variable(identifier, name.pos)?;
+
+              self.emit(store)
+            }
+          }
+        },
+
+        _ => (),
+      },
+
+      For(ref name, ref iterable, ref body) => {
+        let locals    = self.locals.clone();
+        let constants = self.constants.clone();
+
+        self.depth += 1;
+
+        self.compile_expression(iterable)?;
+        self.emit(Instruction::Iter);
+
+        let elements = self.declare_temporary("")?;
+        self.emit(Instruction::StoreLocal(elements));
+
+        self.emit_load_constant(Value::Int(0))?;
+
+        let counter = self.declare_temporary("")?;
+        self.emit(Instruction::StoreLocal(counter));
+
+        let start = self.save_branch_target();
+
+        self.emit(Instruction::LoadLocal(counter));
+        self.emit(Instruction::LoadLocal(elements));
+        self.emit(Instruction::Len);
+        self.emit(Instruction::Lt);
+
+        let exit = self.emit_branch_false();
+
+        self.emit(Instruction::LoadLocal(elements));
+        self.emit(Instruction::LoadLocal(counter));
+        self.emit(Instruction::LoadIndex);
+
+        if let Identifier(ref name) = name.node {
+          let index = self.declare_local(name)?;
+          self.emit(Instruction::StoreLocal(index))
+        }
+
+        self.loops.push(
+          LoopPatches {
+            breaks:    Vec::new(),
+            continues: Vec::new(),
+            tries:     self.tries,
+          }
+        );
+
+        self.compile_expression(body)?;
+        self.emit(Instruction::Pop);
+
+        let patches = self.loops.pop().unwrap();
+
+        for patch in patches.continues {
+          self.patch_jump(patch)?
+        }
+
+        self.emit(Instruction::LoadLocal(counter));
+        self.emit_load_constant(Value::Int(1))?;
+        self.emit(Instruction::Add);
+        self.emit(Instruction::StoreLocal(counter));
+
+        self.emit_jump_to(start)?;
+
+        self.patch_jump(exit)?;
+
+        for patch in patches.breaks {
+          self.patch_jump(patch)?
+        }
+
+        self.depth -= 1;
+        self.locals    = locals;
+        self.constants = constants
+      },
+
+      // exported bindings are top-level, hence already globals importers are linked to
+      Export(ref exported) => self.compile_statement(exported)?,
+
+      Return(ref value) => {
+        match *value {
+          Some(ref value) => self.compile_expression(value)?,
+          None            => self.emit_load_constant(Value::Nil)?,
+        }
+
+        self.emit(Instruction::Return)
+      },
+
+      Break | Continue => {
+        // leaving the loop leaves the `try`s entered within it
+        let entered = self.loops.last().map_or(0, |patches| self.tries - patches.tries);
+
+        for _ in 0 .. entered {
+          self.emit(Instruction::EndTry)
+        }
+
+        let patch = self.emit_jump();
+
+        match self.loops.last_mut() {
+          Some(patches) => if let Break = statement.node {
+            patches.breaks.push(patch)
+          } else {
+            patches.continues.push(patch)
+          },
+
+          None => return Err(
+            response!(
+              Wrong("can't `break` or `continue` outside of loop"),
+              self.source.file,
+              self.source.resolve(statement.pos)
+            )
+          )
+        }
+      },
+
       _ => (),
     }
 
     Ok(())
   }
 
-  fn compile_expression(&mut self, expression: &'c Expression<'c>) -> Result<(), ()> {
+  fn compile_declaration(&mut self, left: &'c Expression, right: Option<&'c Expression>) -> Result<(), ()> {
     use self::ExpressionNode::*;
 
+    match left.node {
+      Identifier(ref name) => if let Some(right) = right {
+        if let Function(..) = right.node {
+          self.compile_function(right, name)?
+        } else {
+          self.compile_expression(right)?
+        }
+
+        let store = self.declare_variable(name)?;
+
+        self.emit(store)
+      } else {
+        let store = self.declare_variable(name)?;
+
+        // a global declared without a value is nil, where a local already is
+        if self.depth == 0 {
+          self.emit_load_constant(Value::Nil)?;
+          self.emit(store)
+        }
+      },
+
+      Set(ref names) => {
+        if let Some(right) = right {
+          self.compile_expression(right)?;
+
+          self.emit(Instruction::Unpack(names.len() as u32));
+        }
+
+        let mut stores = Vec::new();
+
+        for name in names {
+          if let Identifier(ref name) = name.node {
+            stores.push(self.declare_variable(name)?)
+          }
+        }
+
+        if right.is_some() {
+          for store in stores.into_iter().rev() {
+            self.emit(store)
+          }
+        } else if self.depth == 0 {
+          for store in stores {
+            self.emit_load_constant(Value::Nil)?;
+            self.emit(store)
+          }
+        }
+      },
+
+      _ => (),
+    }
+
+    Ok(())
+  }
+
+  fn compile_expression(&mut self, expression: &'c Expression) -> Result<(), ()> {
+    use self::ExpressionNode::*;
+
+    if self.literals.contains_key(&expression.pos) {
+      if let Some(value) = self.fold_constant(expression)? {
+        return self.emit_load_constant(value)
+      }
+    }
+
     match expression.node {
       Int(a)    => self.emit_load_constant(Value::Int(a as i128))?,
       Double(a) => self.emit_load_constant(Value::Double(a))?,
+      Bool(a)   => self.emit_load_constant(Value::Bool(a))?,
+      Char(a)   => self.emit_load_constant(Value::Char(a))?,
+
+      // trailing blank lines parse to an `EOF`, popped like any other expression statement
+      EOF => self.emit_load_constant(Value::Nil)?,
+
+      Str(ref content) => {
+        let value = self.vm.allocate(HeapValueType::Str(content.clone().into_boxed_str()));
+
+        self.emit_load_constant(value)?
+      },
+
+      Map(ref content) => {
+        for &(ref key, ref value) in content {
+          self.compile_expression(key)?;
+          self.compile_expression(value)?
+        }
+
+        self.emit(Instruction::MakeMap(content.len() as u32))
+      },
+
+      Array(ref content) => {
+        for element in content {
+          self.compile_expression(element)?
+        }
+
+        self.emit(Instruction::MakeArray(content.len() as u32))
+      },
+
+      Set(ref content) => {
+        for element in content {
+          self.compile_expression(element)?
+        }
+
+        self.emit(Instruction::MakeSet(content.len() as u32))
+      },
+
+      Index(ref array, ref index) => {
+        self.compile_expression(&**array)?;
+        self.compile_expression(&**index)?;
+
+        self.emit(Instruction::LoadIndex)
+      },
 
       Binary(ref left, ref op, ref right) => {
         self.compile_expression(&**left)?;
@@ -223,60 +611,184 @@ impl<'c> Compiler<'c> {
           Mul    => self.emit(Instruction::Mul),
           Div    => self.emit(Instruction::Div),
           Mod    => self.emit(Instruction::Mod),
-          _   => (),
+          Pow    => self.emit(Instruction::Pow),
+          Eq     => self.emit(Instruction::Eq),
+          NEq    => self.emit(Instruction::NEq),
+          Lt     => self.emit(Instruction::Lt),
+          LtEq   => self.emit(Instruction::LtEq),
+          Gt     => self.emit(Instruction::Gt),
+          GtEq   => self.emit(Instruction::GtEq),
+          Concat => self.emit(Instruction::Concat),
+
+          // the parser turns these into calls, only an AST built by hand still has them
+          Custom(ref operator) => return Err(
+            response!(
+              Wrong(format!("can't compile operator `{}`, it has no function to call", operator)),
+              self.source.file,
+              self.source.resolve(expression.pos)
+            )
+          ),
         }
       },
 
-      Identifier(ref name) => {
-        let index = self.fetch_local(name)?;
-        self.emit(Instruction::LoadLocal(index))
-      },
+      // lowered to concatenating each part onto the leading segment
+      Interpolation(ref parts) => for (i, part) in parts.iter().enumerate() {
+        self.compile_expression(part)?;
 
-      Block(ref content) => {
-        for element in content {
-          self.compile_statement(&element)?
+        if i > 0 {
+          self.emit(Instruction::Concat)
         }
+      },
 
-        self.emit(Instruction::Return)
+      Identifier(ref name) => if let Some(value) = self.constants.get(name).cloned() {
+        self.emit_load_constant(value)?
+      } else {
+        let (load, _) = self.fetch_variable(name, expression.pos)?;
+
+        self.emit(load)
       },
 
-      Function(ref params, _, ref body) => {
-        let function = {
-          let mut locals = HashMap::<String, u32>::new();
+      If(ref condition, ref body, ref elses) => {
+        let mut exits = Vec::new();
+
+        self.compile_expression(&**condition)?;
 
-          for (i, param) in params.iter().enumerate() {
-            match param.node {
-              StatementNode::Variable(_, ref left, _) => {
-                if let Identifier(ref name) = left.node {
-                  locals.insert(name.to_owned(), i as u32);
-                }
-              },
+        let mut next = self.emit_branch_false();
 
-              _ => unimplemented!()
+        self.compile_expression(&**body)?;
+
+        exits.push(self.emit_jump());
+
+        let mut has_else = false;
+
+        if let Some(ref elses) = *elses {
+          for &(ref condition, ref body, _) in elses {
+            self.patch_jump(next)?;
+
+            if let Some(ref condition) = *condition {
+              self.compile_expression(condition)?;
+
+              next = self.emit_branch_false();
+
+              self.compile_expression(body)?;
+
+              exits.push(self.emit_jump())
+            } else {
+              self.compile_expression(body)?;
+
+              has_else = true;
+
+              break
             }
           }
+        }
 
-          let mut compiler = Compiler {
-            vm: self.vm,
+        if !has_else {
+          self.patch_jump(next)?;
+          self.emit_load_constant(Value::Nil)?
+        }
 
-            locals,
+        for exit in exits {
+          self.patch_jump(exit)?
+        }
+      },
 
-            code:   Vec::new(),
-            consts: Vec::new(),
+      Try(ref body, ref name, ref handler) => {
+        let entry = self.emit_try();
 
-            source: self.source,
-          };
+        self.tries += 1;
+        self.compile_expression(body)?;
+        self.tries -= 1;
 
-          compiler.compile_main(&vec!(Statement::new(StatementNode::Expression((**body).clone()), body.pos.clone())), "")?
-        };
+        self.emit(Instruction::EndTry);
 
-        let func_value = self.vm.allocate(HeapValueType::Function(function));
+        let exit = self.emit_jump();
 
-        self.emit_load_constant(func_value)?;
-      }
+        // errors resume here with their message on the stack
+        self.patch_jump(entry)?;
+
+        let locals    = self.locals.clone();
+        let constants = self.constants.clone();
+
+        self.depth += 1;
+
+        let index = self.declare_local(name)?;
+        self.emit(Instruction::StoreLocal(index));
+
+        self.compile_expression(handler)?;
+
+        self.depth -= 1;
+        self.locals    = locals;
+        self.constants = constants;
+
+        self.patch_jump(exit)?
+      },
+
+      Block(ref content) => {
+        let locals    = self.locals.clone();
+        let constants = self.constants.clone();
+
+        self.depth += 1;
+
+        for (i, element) in content.iter().enumerate() {
+          self.compile_statement(element)?;
+
+          if let StatementNode::Expression(_) = element.node {
+            if i < content.len() - 1 {
+              self.emit(Instruction::Pop)
+            }
+          }
+        }
+
+        match content.last() {
+          Some(&Statement { node: StatementNode::Expression(_), .. }) => (),
+          _                                                           => self.emit_load_constant(Value::Nil)?,
+        }
+
+        self.depth -= 1;
+        self.locals    = locals;
+        self.constants = constants
+      },
+
+      Function(..) => self.compile_function(expression, "")?,
 
       Call(ref called, ref args) => {
-        let args_count = args.len() as u8;
+        let args_count = self.argument_count(args.len(), called.pos)?;
+
+        if let Identifier(ref name) = called.node {
+          let instruction = match name.as_str() {
+            "len"     => Some(Instruction::Len),
+            "push"    => Some(Instruction::ArrayPush),
+            "pop"     => Some(Instruction::ArrayPop),
+            "has"     => Some(Instruction::MapHas),
+            "remove"  => Some(Instruction::MapRemove),
+            "print"   => Some(Instruction::Put(args_count)),
+            "println" => Some(Instruction::PutLine(args_count)),
+            "format"  => args_count.checked_sub(1).map(Instruction::Format), // past the format string
+            "raise"   => Some(Instruction::Raise),
+
+            "wrapping_add"   => Some(Instruction::WrappingAdd),
+            "wrapping_sub"   => Some(Instruction::WrappingSub),
+            "wrapping_mul"   => Some(Instruction::WrappingMul),
+            "saturating_add" => Some(Instruction::SaturatingAdd),
+            "saturating_sub" => Some(Instruction::SaturatingSub),
+            "saturating_mul" => Some(Instruction::SaturatingMul),
+
+            _         => None,
+          };
+
+          if let Some(instruction) = instruction {
+            if self.builtins.contains(&called.pos) {
+              for arg in args.iter() {
+                self.compile_expression(&*arg)?
+              }
+
+              self.emit(instruction);
+
+              return Ok(())
+            }
+          }
+        }
 
         self.compile_expression(&**called)?;
 
@@ -285,9 +797,18 @@ impl<'c> Compiler<'c> {
         }
 
         self.emit(Instruction::Call(args_count))
-      }
+      },
 
-      _ => (),
+      Cast(ref expression, ref t) => {
+        self.compile_expression(expression)?;
+
+        match t.node {
+          TypeNode::Int         => self.emit(Instruction::ToInt),
+          TypeNode::Double      => self.emit(Instruction::ToFloat),
+          TypeNode::Sized(kind) => self.emit(Instruction::ToSized(kind)),
+          _                     => unreachable!(),
+        }
+      },
     }
 
     Ok(())
@@ -295,27 +816,99 @@ impl<'c> Compiler<'c> {
 
 
 
-  pub fn compile_main(&mut self, block: &'c Vec<Statement<'c>>, name: &str) -> Result<CompiledBlock, ()> {
+  // functions bound to a name carry it, for rendering and diagnostics
+  fn compile_function(&mut self, expression: &'c Expression, name: &str) -> Result<(), ()> {
+    use self::ExpressionNode::*;
+
+    if let Function(_, ref params, _, ref body) = expression.node {
+      let function = {
+        let mut compiler = Compiler::module(self.vm, self.source, &self.module);
+
+        compiler.depth     = 1;
+        compiler.constants = self.constants.clone();
+        compiler.literals  = self.literals.clone();
+        compiler.builtins  = self.builtins.clone();
+
+        // the visitor only lets through parameters bound to plain names
+        for param in params.iter() {
+          match param.node {
+            StatementNode::Variable(_, ref left, _) |
+            StatementNode::Constant(_, ref left, _) => if let Identifier(ref name) = left.node {
+              compiler.declare_local(name)?;
+            } else {
+              return Err(
+                response!(
+                  Wrong("can't destructure a parameter"),
+                  self.source.file,
+                  self.source.resolve(left.pos)
+                )
+              )
+            },
+
+            _ => unreachable!()
+          }
+        }
+
+        compiler.compile_expression(&**body)?;
+        compiler.emit(Instruction::Return);
+
+        CompiledBlock {
+          arity: params.len(),
+          .. compiler.finish(name)
+        }
+      };
+
+      let func_value = self.vm.allocate(HeapValueType::Function(function));
+
+      self.emit_load_constant(func_value)?;
+    }
+
+    Ok(())
+  }
+
+
+
+  pub fn compile_main(&mut self, block: &'c Vec<Statement>, name: &str) -> Result<CompiledBlock, ()> {
     for element in block {
-      self.compile_statement(element)?
+      if let Some((name, _)) = Self::hoisted(element) {
+        self.vm.declare_global(&global_name(&self.module, name));
+      }
+    }
+
+    for element in block {
+      if let Some((name, function)) = Self::hoisted(element) {
+        self.compile_function(function, name)?;
+
+        let index = self.vm.declare_global(&global_name(&self.module, name));
+        self.emit(Instruction::StoreGlobal(index))
+      }
+    }
+
+    for element in block {
+      if Self::hoisted(element).is_none() {
+        self.compile_statement(element)?;
+
+        if let StatementNode::Expression(_) = element.node {
+          self.emit(Instruction::Pop)
+        }
+      }
     }
 
     self.emit_load_constant(Value::Nil)?;
     self.code.push(Instruction::Return);
 
-    let mut local_names = vec![String::new(); self.locals.len()];
+    Ok(self.finish(name))
+  }
 
-    for (name, i) in self.locals.drain() {
-      local_names[i as usize] = name
-    }
+  fn finish(&mut self, name: &str) -> CompiledBlock {
+    self.locals.clear();
 
-    Ok(
-      CompiledBlock {
-        name:   name.to_string(),
-        code:   mem::replace(&mut self.code, Vec::new()).into_boxed_slice(),
-        consts: mem::replace(&mut self.consts, Vec::new()),
-        locals: local_names.into_boxed_slice()
-      }
-    )
+    CompiledBlock {
+      name:   name.to_string(),
+      code:   mem::take(&mut self.code).into_boxed_slice(),
+      consts: mem::take(&mut self.consts),
+      locals: mem::take(&mut self.local_names).into_boxed_slice(),
+      arity:  0,
+    }
   }
 }
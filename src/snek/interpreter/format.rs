@@ -0,0 +1,56 @@
+use super::*;
+
+
+
+// `{}` is replaced by the next argument, `{{` and `}}` stand for literal braces
+fn expand<F: FnMut(usize, &mut String) -> Result<(), String>>(template: &str, mut placeholder: F) -> Result<String, String> {
+  let mut result = String::new();
+  let mut chars  = template.chars().peekable();
+  let mut count  = 0;
+
+  while let Some(c) = chars.next() {
+    match (c, chars.peek().cloned()) {
+      ('{', Some('{')) | ('}', Some('}')) => {
+        chars.next();
+        result.push(c)
+      },
+
+      ('{', Some('}')) => {
+        chars.next();
+
+        placeholder(count, &mut result)?;
+        count += 1
+      },
+
+      ('{', _) | ('}', _) => return Err(format!("unmatched `{}` in format string", c)),
+
+      _ => result.push(c),
+    }
+  }
+
+  Ok(result)
+}
+
+pub fn placeholders(template: &str) -> Result<usize, String> {
+  let mut count = 0;
+
+  expand(template, |_, _| {
+    count += 1;
+    Ok(())
+  })?;
+
+  Ok(count)
+}
+
+pub fn format(template: &str, args: &[Value]) -> Result<String, String> {
+  let expected = placeholders(template)?;
+
+  if expected != args.len() {
+    return Err(format!("format string has {} placeholder(s) but {} argument(s) were given", expected, args.len()))
+  }
+
+  expand(template, |index, result| {
+    result.push_str(&args[index].to_string());
+    Ok(())
+  })
+}
@@ -1,6 +1,7 @@
 pub mod value;
 pub mod vm;
 pub mod compiler;
+pub mod format;
 
 use super::*;
 use super::parser::*;
@@ -1,12 +1,25 @@
 use std::hash::*;
-use std::mem;
+use std::fmt::{ self, Display, Formatter };
+
+use std::collections::HashMap;
 
 use super::*;
 
 pub enum HeapValueType {
   Str(Box<str>),
   Array(Vec<Value>),
+  Set(Vec<Value>),
+  Map(OrderedMap),
   Function(CompiledBlock),
+  Native(Native),
+}
+
+// a function implemented by the host, failing with a message
+pub type NativeFunction = fn(&mut VirtualMachine, &[Value]) -> Result<Value, String>;
+
+pub struct Native {
+  pub name:     String,
+  pub function: NativeFunction,
 }
 
 pub struct HeapValue {
@@ -16,9 +29,178 @@ pub struct HeapValue {
 
 
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+// the entries of a map in the order their keys were first inserted, so iterating and printing it is stable
+pub struct OrderedMap {
+  entries: Vec<(Value, Value)>,
+  index:   HashMap<Value, usize>, // where the entry of each key is
+}
+
+impl OrderedMap {
+  pub fn new() -> Self {
+    OrderedMap {
+      entries: Vec::new(),
+      index:   HashMap::new(),
+    }
+  }
+
+  pub fn get(&self, key: &Value) -> Option<&Value> {
+    self.index.get(key).map(|&i| &self.entries[i].1)
+  }
+
+  pub fn contains_key(&self, key: &Value) -> bool {
+    self.index.contains_key(key)
+  }
+
+  // a key already there keeps its place
+  pub fn insert(&mut self, key: Value, value: Value) {
+    match self.index.get(&key) {
+      Some(&i) => self.entries[i].1 = value,
+      None     => {
+        self.index.insert(key, self.entries.len());
+        self.entries.push((key, value))
+      },
+    }
+  }
+
+  pub fn remove(&mut self, key: &Value) -> Option<Value> {
+    let i = self.index.remove(key)?;
+
+    for position in self.index.values_mut() {
+      if *position > i {
+        *position -= 1
+      }
+    }
+
+    Some(self.entries.remove(i).1)
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn keys(&self) -> impl Iterator<Item = &Value> {
+    self.entries.iter().map(|&(ref key, _)| key)
+  }
+
+  pub fn iter(&self) -> ::std::slice::Iter<'_, (Value, Value)> {
+    self.entries.iter()
+  }
+}
+
+
+
+// the fixed-width integer types, `int` itself is unsized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntKind {
+  I8, I16, I32, I64,
+  U8, U16, U32, U64,
+}
+
+impl IntKind {
+  pub const ALL: &'static [IntKind] = &[
+    IntKind::I8, IntKind::I16, IntKind::I32, IntKind::I64,
+    IntKind::U8, IntKind::U16, IntKind::U32, IntKind::U64,
+  ];
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    use self::IntKind::*;
+
+    let kind = match name {
+      "i8"  => I8,
+      "i16" => I16,
+      "i32" => I32,
+      "i64" => I64,
+      "u8"  => U8,
+      "u16" => U16,
+      "u32" => U32,
+      "u64" => U64,
+      _     => return None,
+    };
+
+    Some(kind)
+  }
+
+  pub fn min(&self) -> i128 {
+    use self::IntKind::*;
+
+    match *self {
+      I8  => i8::min_value() as i128,
+      I16 => i16::min_value() as i128,
+      I32 => i32::min_value() as i128,
+      I64 => i64::min_value() as i128,
+      _   => 0,
+    }
+  }
+
+  pub fn max(&self) -> i128 {
+    use self::IntKind::*;
+
+    match *self {
+      I8  => i8::max_value() as i128,
+      I16 => i16::max_value() as i128,
+      I32 => i32::max_value() as i128,
+      I64 => i64::max_value() as i128,
+      U8  => u8::max_value() as i128,
+      U16 => u16::max_value() as i128,
+      U32 => u32::max_value() as i128,
+      U64 => u64::max_value() as i128,
+    }
+  }
+
+  pub fn fits(&self, n: i128) -> bool {
+    n >= self.min() && n <= self.max()
+  }
+
+  // keeps the low bits of `n`, like a two's complement machine would
+  pub fn wrap(&self, n: i128) -> i128 {
+    use self::IntKind::*;
+
+    match *self {
+      I8  => n as i8 as i128,
+      I16 => n as i16 as i128,
+      I32 => n as i32 as i128,
+      I64 => n as i64 as i128,
+      U8  => n as u8 as i128,
+      U16 => n as u16 as i128,
+      U32 => n as u32 as i128,
+      U64 => n as u64 as i128,
+    }
+  }
+
+  pub fn saturate(&self, n: i128) -> i128 {
+    n.max(self.min()).min(self.max())
+  }
+}
+
+impl Display for IntKind {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    use self::IntKind::*;
+
+    let name = match *self {
+      I8  => "i8",
+      I16 => "i16",
+      I32 => "i32",
+      I64 => "i64",
+      U8  => "u8",
+      U16 => "u16",
+      U32 => "u32",
+      U64 => "u64",
+    };
+
+    write!(f, "{}", name)
+  }
+}
+
+
+
+#[derive(Debug, Clone, Copy)]
 pub enum Value {
   Int(i128),
+  Sized(i128, IntKind),
   Double(f64),
   Char(char),
   Bool(bool),
@@ -28,24 +210,59 @@ pub enum Value {
 
 impl Value {
   pub fn is_truthy(&self) -> bool {
-    *self != Value::Bool(true)
+    match *self {
+      Value::Bool(false) | Value::Nil => false,
+      _                               => true,
+    }
   }
 }
 
+// strings are compared by content, every other heap value by identity
+impl PartialEq for Value {
+  fn eq(&self, other: &Value) -> bool {
+    use self::Value::*;
+
+    match (*self, *other) {
+      (Int(a),    Int(b))    => a == b,
+      (Double(a), Double(b)) => a == b,
+      (Char(a),   Char(b))   => a == b,
+      (Bool(a),   Bool(b))   => a == b,
+      (Nil,       Nil)       => true,
+
+      (Sized(a, k), Sized(b, l)) => a == b && k == l,
+
+      (HeapValue(a), HeapValue(b)) => a == b || match unsafe { (&(*a).kind, &(*b).kind) } {
+        (&HeapValueType::Str(ref a), &HeapValueType::Str(ref b)) => a == b,
+        _                                                         => false,
+      },
+
+      _ => false,
+    }
+  }
+}
+
+impl Eq for Value {}
+
 impl Hash for Value {
   fn hash<H: Hasher>(&self, state: &mut H) {
     use self::Value::*;
 
     match *self {
-      Int(n)    => n.hash(state),
-      Double(n) => {
+      Int(n)      => n.hash(state),
+      Sized(n, _) => n.hash(state),
+      Double(n)   => {
         state.write_u8(0);
-        state.write_u64(unsafe { mem::transmute(n) })
+        state.write_u64(n.to_bits())
       },
 
       HeapValue(p) => {
         state.write_u8(1);
-        state.write_usize(p as usize)
+
+        if let HeapValueType::Str(ref content) = unsafe { &*p }.kind {
+          content.hash(state)
+        } else {
+          state.write_usize(p as usize)
+        }
       },
 
       Bool(b) => {
@@ -61,4 +278,92 @@ impl Hash for Value {
       Nil => state.write_u8(0),
     }
   }
-}
\ No newline at end of file
+}
+
+
+impl Display for Value {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    use self::Value::*;
+
+    match *self {
+      Int(n)       => write!(f, "{}", n),
+      Sized(n, _)  => write!(f, "{}", n),
+      Double(n)    => write!(f, "{:?}", n),
+      Char(c)      => write!(f, "{}", c),
+      Bool(b)      => write!(f, "{}", if b { "yes" } else { "no" }),
+      HeapValue(p) => write!(f, "{}", unsafe { &(*p).kind }),
+      Nil          => write!(f, "nil"),
+    }
+  }
+}
+
+impl Value {
+  // strings and chars are quoted inside of collections
+  fn fmt_element(&self, f: &mut Formatter) -> fmt::Result {
+    match *self {
+      Value::Char(c) => write!(f, "{:?}", c),
+
+      Value::HeapValue(p) => if let HeapValueType::Str(ref content) = unsafe { &*p }.kind {
+        write!(f, "{:?}", content)
+      } else {
+        write!(f, "{}", self)
+      },
+
+      _ => write!(f, "{}", self),
+    }
+  }
+}
+
+impl Display for HeapValueType {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    use self::HeapValueType::*;
+
+    match *self {
+      Str(ref content) => write!(f, "{}", content),
+
+      Array(ref content) | Set(ref content) => {
+        let (open, close) = if let Array(_) = *self { ("[", "]") } else { ("(", ")") };
+
+        write!(f, "{}", open)?;
+
+        for (i, element) in content.iter().enumerate() {
+          if i > 0 {
+            write!(f, ", ")?
+          }
+
+          element.fmt_element(f)?
+        }
+
+        write!(f, "{}", close)
+      },
+
+      Map(ref content) => {
+        if content.is_empty() {
+          return write!(f, "[:]")
+        }
+
+        let entries = content.iter().map(|&(ref key, ref value)| {
+          format!("{}: {}", Element(key), Element(value))
+        }).collect::<Vec<String>>();
+
+        write!(f, "[{}]", entries.join(", "))
+      },
+
+      Function(ref block) => if block.name.is_empty() {
+        write!(f, "<fun>")
+      } else {
+        write!(f, "<fun {}>", block.name)
+      },
+
+      Native(ref native) => write!(f, "<native {}>", native.name),
+    }
+  }
+}
+
+struct Element<'a>(&'a Value);
+
+impl<'a> Display for Element<'a> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    self.0.fmt_element(f)
+  }
+}
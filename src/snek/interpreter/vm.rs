@@ -1,12 +1,26 @@
 use super::*;
+use super::super::error::Response::Wrong;
+use super::super::visitor::Type;
 
 use std::ptr;
 use std::mem;
+use std::io::{ self, Write, BufRead };
+
+use std::collections::HashMap;
 
 pub struct CallInfo {
   locals: Box<[Value]>,
   ip:     usize,
   func:   *const CompiledBlock,
+  base:   usize, // stack height of the caller, restored when returning
+}
+
+// an entered `try`, which errors unwind to
+#[derive(Debug, Clone, Copy)]
+pub struct Handler {
+  depth:  usize, // number of calls when entering
+  height: usize, // stack height when entering
+  target: usize, // where the `catch` starts
 }
 
 
@@ -18,6 +32,7 @@ pub enum Instruction {
   Mul,
   Div,
   Mod,
+  Pow,
   Concat,
 
   Neg,
@@ -32,23 +47,87 @@ pub enum Instruction {
   LoadConst(u32),
   LoadLocal(u32),
   StoreLocal(u32),
+  LoadGlobal(u32),
+  StoreGlobal(u32),
 
   BranchTrue(i32),
   BranchFalse(i32),
   Jump(i32),
 
+  MakeArray(u32),
+  MakeSet(u32),
+  MakeMap(u32),
+  Unpack(u32),
+  LoadIndex,
+  StoreIndex,
+  Len,
+  ArrayPush,
+  ArrayPop,
+  MapHas,
+  MapRemove,
+  Iter,
+
+  WrappingAdd,
+  WrappingSub,
+  WrappingMul,
+  SaturatingAdd,
+  SaturatingSub,
+  SaturatingMul,
+
+  ToInt,
+  ToFloat,
+  ToSized(IntKind),
+
   Pop,
   Return,
-  Put,
+  Try(i32),
+  EndTry,
+  Raise,
+  Put(u8),
+  PutLine(u8),
+  Format(u8),
   Call(u8),
 }
 
 
 
+// what scripts may do outside of the vm, nothing unless the host allows it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+  pub files:   bool, // reading and writing files
+  pub console: bool, // reading standard input
+  pub process: bool, // arguments, environment and exiting
+}
+
+impl Capabilities {
+  pub fn all() -> Self {
+    Capabilities {
+      files:   true,
+      console: true,
+      process: true,
+    }
+  }
+}
+
+
+
 pub struct VirtualMachine {
-  pub stack: Vec<Value>,
+  pub stack:   Vec<Value>,
+  pub globals: Vec<Value>,
 
-  calls: Vec<CallInfo>,
+  global_names: HashMap<String, u32>,
+  initialized:  Vec<bool>,         // globals are declared up front, and only readable once stored to
+  host_types:   Vec<(String, Type)>, // of the globals the host defined, for the checker to know them by
+
+  calls:    Vec<CallInfo>,
+  handlers: Vec<Handler>,
+
+  output: Box<dyn Write>,
+  input:  Box<dyn BufRead>,
+
+  pub capabilities: Capabilities,
+  pub args:         Vec<String>,
+  pub exit_code:    Option<i32>, // set by `io.exit`, which stops execution
 
   pub next: *mut HeapValue,
 }
@@ -56,22 +135,166 @@ pub struct VirtualMachine {
 impl VirtualMachine {
   pub fn new() -> Self {
     VirtualMachine {
-      stack: Vec::new(),
-      calls: Vec::new(),
-      next:  ptr::null_mut(),
+      stack:   Vec::new(),
+      globals: Vec::new(),
+
+      global_names: HashMap::new(),
+      initialized:  Vec::new(),
+      host_types:   Vec::new(),
+
+      calls:    Vec::new(),
+      handlers: Vec::new(),
+
+      output: Box::new(io::stdout()),
+      input:  Box::new(io::BufReader::new(io::stdin())),
+
+      capabilities: Capabilities::default(),
+      args:         Vec::new(),
+      exit_code:    None,
+
+      next: ptr::null_mut(),
     }
   }
 
 
 
-  pub fn execute(&mut self, initial: *const CompiledBlock) {
+  pub fn declare_global(&mut self, name: &str) -> u32 {
+    if let Some(index) = self.global_names.get(name) {
+      return *index
+    }
+
+    let index = self.globals.len() as u32;
+
+    self.globals.push(Value::Nil);
+    self.initialized.push(false);
+    self.global_names.insert(name.to_string(), index);
+
+    index
+  }
+
+  // makes `name` refer to an existing global, e.g. an export of another module
+  pub fn alias_global(&mut self, name: &str, index: u32) {
+    self.global_names.insert(name.to_string(), index);
+  }
+
+  pub fn fetch_global(&self, name: &str) -> Option<u32> {
+    self.global_names.get(name).cloned()
+  }
+
+  // the name a global was declared by, without the path of its module
+  fn global_name(&self, index: u32) -> &str {
+    self.global_names.iter()
+      .filter(|&(_, &global)| global == index)
+      .map(|(name, _)| name.rsplit("::").next().unwrap())
+      .min_by_key(|name| name.len())
+      .unwrap_or("?")
+  }
+
+  // where `print` and `println` write to, so embedders can capture output
+  pub fn set_output(&mut self, output: Box<dyn Write>) {
+    self.output = output
+  }
+
+  pub fn set_input(&mut self, input: Box<dyn BufRead>) {
+    self.input = input
+  }
+
+  // a line of input without its line break, or none at the end of input
+  pub fn read_line(&mut self) -> io::Result<Option<String>> {
+    let mut line = String::new();
+
+    if self.input.read_line(&mut line)? == 0 {
+      return Ok(None)
+    }
+
+    if line.ends_with('\n') {
+      line.pop();
+
+      if line.ends_with('\r') {
+        line.pop();
+      }
+    }
+
+    Ok(Some(line))
+  }
+
+  // host access to globals by name, before or after a run
+  pub fn get_global(&self, name: &str) -> Option<Value> {
+    self.fetch_global(name).map(|index| self.globals[index as usize])
+  }
+
+  pub fn set_global(&mut self, name: &str, value: Value) {
+    let index = self.declare_global(name);
+
+    self.globals[index as usize]     = value;
+    self.initialized[index as usize] = true
+  }
+
+  // a global the host provides before a run, which programs use as a value of type `t`
+  pub fn define_global(&mut self, name: &str, t: Type, value: Value) {
+    self.set_global(name, value);
+
+    self.host_types.retain(|&(ref defined, _)| defined != name);
+    self.host_types.push((name.to_string(), t))
+  }
+
+  pub fn host_globals(&self) -> &[(String, Type)] {
+    &self.host_types
+  }
+
+
+
+  pub fn execute(&mut self, initial: &CompiledBlock) -> Result<(), ()> {
     use self::Instruction::*;
     use self::Value::*;
 
     let mut ip: usize           = 0;
-    let mut fun: &CompiledBlock = unsafe { &*initial };
+    let mut fun: &CompiledBlock = initial;
     let mut locals              = vec![Nil; fun.locals.len()].into_boxed_slice();
 
+    let handlers = self.handlers.len();
+
+    // resumes at the innermost `catch` with the message, or fails the run without one
+    macro_rules! throw {
+      ($message:expr) => {{
+        let message: String = $message.into();
+
+        if self.handlers.len() > handlers {
+          let handler = self.handlers.pop().unwrap();
+
+          while self.calls.len() > handler.depth {
+            let call_info = self.calls.pop().unwrap();
+
+            fun    = unsafe { &*call_info.func };
+            locals = call_info.locals;
+          }
+
+          self.stack.truncate(handler.height);
+
+          let error = self.allocate(HeapValueType::Str(message.into_boxed_str()));
+
+          self.stack.push(error);
+
+          ip = handler.target;
+
+          continue
+        }
+
+        self.calls.clear();
+
+        return Err(response!(Wrong(message)))
+      }};
+    }
+
+    macro_rules! attempt {
+      ($result:expr) => {
+        match $result {
+          Ok(value)    => value,
+          Err(message) => throw!(message),
+        }
+      };
+    }
+
     macro_rules! match_binop {
       ($($pat:pat => $block:block)+) => {{
         let _a = self.stack.pop().unwrap();
@@ -79,7 +302,7 @@ impl VirtualMachine {
         
         let _result = match (_b, _a) {
             $($pat => $block)+,
-            _ => panic!("Invalid operands"),
+            _ => throw!("invalid operands"),
         };
         
         self.stack.push(_result);
@@ -96,6 +319,18 @@ impl VirtualMachine {
           locals[index as usize] = self.stack.pop().unwrap();
         },
 
+        LoadGlobal(index)  => if self.initialized[index as usize] {
+          self.stack.push(self.globals[index as usize])
+        } else {
+          // only a hoisted function called early gets here
+          throw!(format!("`{}` is read before it's initialized", self.global_name(index)))
+        },
+
+        StoreGlobal(index) => {
+          self.globals[index as usize]     = self.stack.pop().unwrap();
+          self.initialized[index as usize] = true
+        },
+
         BranchTrue(delta) => {
           if self.stack.pop().unwrap().is_truthy() {
             ip = ip.wrapping_add(delta as isize as usize)
@@ -124,41 +359,371 @@ impl VirtualMachine {
 
         Pop => { self.stack.pop().unwrap(); },
 
-        Add => match_binop! {
-          (Int(a), Int(b))       => { Int(a + b) }
-          (Double(a), Double(b)) => { Double(a + b) }
+        MakeArray(len) => {
+          let start   = self.stack.len() - len as usize;
+          let content = self.stack.split_off(start);
+
+          let array = self.allocate(HeapValueType::Array(content));
+
+          self.stack.push(array)
+        },
+
+        MakeSet(len) => {
+          let start   = self.stack.len() - len as usize;
+          let content = self.stack.split_off(start);
+
+          let set = self.allocate(HeapValueType::Set(content));
+
+          self.stack.push(set)
+        },
+
+        Unpack(len) => {
+          let set = attempt!(Self::elements_of(self.stack.pop().unwrap()));
+
+          if set.len() != len as usize {
+            throw!(format!("can't unpack {} values into {} names", set.len(), len))
+          }
+
+          self.stack.extend_from_slice(set)
         },
 
-        Sub => match_binop! {
-          (Int(a), Int(b))       => { Int(a - b) }
-          (Double(a), Double(b)) => { Double(a - b) }
+        MakeMap(len) => {
+          let start   = self.stack.len() - 2 * len as usize;
+          let content = self.stack.split_off(start);
+
+          let mut map = OrderedMap::new();
+
+          for entry in content.chunks(2) {
+            map.insert(entry[0], entry[1]);
+          }
+
+          let map = self.allocate(HeapValueType::Map(map));
+
+          self.stack.push(map)
         },
 
-        Mul => match_binop! {
-          (Int(a), Int(b))       => { Int(a * b) }
-          (Double(a), Double(b)) => { Double(a * b) }
+        LoadIndex => {
+          let index     = self.stack.pop().unwrap();
+          let container = self.stack.pop().unwrap();
+
+          let element = match *attempt!(Self::heap_of(container)) {
+            HeapValueType::Array(ref content) |
+            HeapValueType::Set(ref content)   => content[attempt!(Self::index_of(index, content.len()))],
+
+            HeapValueType::Map(ref content) => match content.get(&index) {
+              Some(value) => *value,
+              None        => throw!(format!("no such key `{}` in map", index)),
+            },
+
+            _ => throw!(format!("can't index `{:?}`", container)),
+          };
+
+          self.stack.push(element)
         },
 
-        Div => match_binop! {
-          (Int(a), Int(b))       => { Int(a / b) }
-          (Double(a), Double(b)) => { Double(a / b) }
+        StoreIndex => {
+          let value     = self.stack.pop().unwrap();
+          let index     = self.stack.pop().unwrap();
+          let container = self.stack.pop().unwrap();
+
+          match *attempt!(Self::heap_of(container)) {
+            HeapValueType::Array(ref mut content) => {
+              let index = attempt!(Self::index_of(index, content.len()));
+
+              content[index] = value
+            },
+
+            HeapValueType::Map(ref mut content) => {
+              content.insert(index, value);
+            },
+
+            _ => throw!(format!("can't assign to index of `{:?}`", container)),
+          }
         },
 
-        Mod => match_binop! {
-          (Int(a), Int(b))       => { Int(a % b) }
-          (Double(a), Double(b)) => { Double(a % b) }
+        Len => {
+          let container = self.stack.pop().unwrap();
+
+          let len = match *attempt!(Self::heap_of(container)) {
+            HeapValueType::Array(ref content) |
+            HeapValueType::Set(ref content)   => content.len(),
+            HeapValueType::Map(ref content)   => content.len(),
+
+            _ => throw!(format!("can't take length of `{:?}`", container)),
+          };
+
+          self.stack.push(Int(len as i128))
+        },
+
+        ArrayPush => {
+          let value = self.stack.pop().unwrap();
+          let array = attempt!(Self::array_of(self.stack.pop().unwrap()));
+
+          array.push(value);
+
+          self.stack.push(Nil)
+        },
+
+        ArrayPop => {
+          let array = attempt!(Self::array_of(self.stack.pop().unwrap()));
+
+          match array.pop() {
+            Some(value) => self.stack.push(value),
+            None        => throw!("can't pop from empty array"),
+          }
+        },
+
+        MapHas => {
+          let key = self.stack.pop().unwrap();
+          let map = attempt!(Self::map_of(self.stack.pop().unwrap()));
+
+          self.stack.push(Bool(map.contains_key(&key)))
+        },
+
+        MapRemove => {
+          let key = self.stack.pop().unwrap();
+          let map = attempt!(Self::map_of(self.stack.pop().unwrap()));
+
+          match map.remove(&key) {
+            Some(value) => self.stack.push(value),
+            None        => throw!(format!("no such key `{}` in map", key)),
+          }
+        },
+
+        // arrays are iterated in place, sets and maps through a snapshot of their elements and keys
+        Iter => {
+          let container = self.stack.pop().unwrap();
+
+          let elements = match *attempt!(Self::heap_of(container)) {
+            HeapValueType::Array(_)         => container,
+            HeapValueType::Set(ref content) => self.allocate(HeapValueType::Array(content.clone())),
+            HeapValueType::Map(ref content) => self.allocate(HeapValueType::Array(content.keys().cloned().collect())),
+
+            _ => throw!(format!("can't iterate over `{:?}`", container)),
+          };
+
+          self.stack.push(elements)
+        },
+
+        Eq  => {
+          let b = self.stack.pop().unwrap();
+          let a = self.stack.pop().unwrap();
+
+          self.stack.push(Bool(a == b))
+        },
+
+        NEq => {
+          let b = self.stack.pop().unwrap();
+          let a = self.stack.pop().unwrap();
+
+          self.stack.push(Bool(a != b))
+        },
+
+        Lt => match_binop! {
+          (Int(a), Int(b))           => { Bool(a < b) }
+          (Sized(a, _), Sized(b, _)) => { Bool(a < b) }
+          (Double(a), Double(b))     => { Bool(a < b) }
+          (Char(a), Char(b))         => { Bool(a < b) }
+        },
+
+        LtEq => match_binop! {
+          (Int(a), Int(b))           => { Bool(a <= b) }
+          (Sized(a, _), Sized(b, _)) => { Bool(a <= b) }
+          (Double(a), Double(b))     => { Bool(a <= b) }
+          (Char(a), Char(b))         => { Bool(a <= b) }
+        },
+
+        Gt => match_binop! {
+          (Int(a), Int(b))           => { Bool(a > b) }
+          (Sized(a, _), Sized(b, _)) => { Bool(a > b) }
+          (Double(a), Double(b))     => { Bool(a > b) }
+          (Char(a), Char(b))         => { Bool(a > b) }
+        },
+
+        GtEq => match_binop! {
+          (Int(a), Int(b))           => { Bool(a >= b) }
+          (Sized(a, _), Sized(b, _)) => { Bool(a >= b) }
+          (Double(a), Double(b))     => { Bool(a >= b) }
+          (Char(a), Char(b))         => { Bool(a >= b) }
+        },
+
+        Add | Sub | Mul | Div | Mod | Pow => match_binop! {
+          (Int(a), Int(b))           => { attempt!(Self::arithmetic(op, a, b, None)) }
+          (Sized(a, k), Sized(b, _)) => { attempt!(Self::arithmetic(op, a, b, Some(k))) }
+
+          (Double(a), Double(b)) => {
+            Double(
+              match op {
+                Add => a + b,
+                Sub => a - b,
+                Mul => a * b,
+                Div => a / b,
+                Mod => a % b,
+                _   => a.powf(b),
+              }
+            )
+          }
+        },
+
+        // anything is written onto a string as it's displayed
+        Concat => {
+          let b = self.stack.pop().unwrap();
+          let a = self.stack.pop().unwrap();
+
+          let mut content = match *attempt!(Self::heap_of(a)) {
+            HeapValueType::Str(ref content) => content.to_string(),
+            _                               => throw!(format!("can't concatenate onto `{}`", a)),
+          };
+
+          content.push_str(&b.to_string());
+
+          let value = self.allocate(HeapValueType::Str(content.into_boxed_str()));
+
+          self.stack.push(value)
+        },
+
+        WrappingAdd | WrappingSub | WrappingMul | SaturatingAdd | SaturatingSub | SaturatingMul => match_binop! {
+          (Int(a), Int(b))           => { Int(Self::overflowing(op, a, b, None)) }
+          (Sized(a, k), Sized(b, _)) => { Sized(Self::overflowing(op, a, b, Some(k)), k) }
+        },
+
+        ToInt => {
+          let value = match self.stack.pop().unwrap() {
+            Int(n) | Sized(n, _) => Int(n),
+
+            Double(n) => if n.is_finite() && n.abs() < 2f64.powi(127) {
+              Int(n.trunc() as i128)
+            } else {
+              throw!(format!("`{:?}` doesn't fit in `int`", n))
+            },
+
+            value => throw!(format!("can't cast `{}` to `int`", value)),
+          };
+
+          self.stack.push(value)
+        },
+
+        ToFloat => {
+          let value = match self.stack.pop().unwrap() {
+            Int(n) | Sized(n, _) => Double(n as f64),
+            Double(n)            => Double(n),
+
+            value => throw!(format!("can't cast `{}` to `float`", value)),
+          };
+
+          self.stack.push(value)
+        },
+
+        ToSized(kind) => {
+          let n = match self.stack.pop().unwrap() {
+            Int(n) | Sized(n, _) => n,
+
+            Double(n) => if n.is_finite() && n.abs() < 2f64.powi(127) {
+              n.trunc() as i128
+            } else {
+              throw!(format!("`{:?}` doesn't fit in `{}`", n, kind))
+            },
+
+            value => throw!(format!("can't cast `{}` to `{}`", value, kind)),
+          };
+
+          if !kind.fits(n) {
+            throw!(format!("`{}` doesn't fit in `{}`", n, kind))
+          }
+
+          self.stack.push(Sized(n, kind))
+        },
+
+        Put(count) | PutLine(count) => {
+          let start  = self.stack.len() - count as usize;
+          let values = self.stack.split_off(start);
+
+          let mut line = values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(" ");
+
+          if let PutLine(_) = op {
+            line.push('\n')
+          }
+
+          if let Err(error) = self.output.write_all(line.as_bytes()).and_then(|_| self.output.flush()) {
+            throw!(format!("can't write output: {}", error))
+          }
+
+          self.stack.push(Nil)
+        },
+
+        Format(count) => {
+          let start = self.stack.len() - count as usize;
+          let args  = self.stack.split_off(start);
+
+          let result = match *attempt!(Self::heap_of(self.stack.pop().unwrap())) {
+            HeapValueType::Str(ref template) => format::format(template, &args),
+            _                                => unreachable!(),
+          };
+
+          match result {
+            Ok(content) => {
+              let value = self.allocate(HeapValueType::Str(content.into_boxed_str()));
+
+              self.stack.push(value)
+            },
+
+            Err(message) => throw!(message),
+          }
         },
 
         Return => {
           if let Some(call_info) = self.calls.pop() {
+            // every compiled `return` loads its value, so having none above the frame means the code is broken
+            if self.stack.len() <= call_info.base {
+              self.calls.clear();
+
+              return Err(response!(Wrong(format!("internal error: `{}` returned without a value", fun.name))))
+            }
+
+            // a return from within a nested expression leaves its operands behind
+            let result = self.stack.pop().unwrap();
+
+            self.stack.truncate(call_info.base);
+            self.stack.push(result);
+
             fun    = unsafe { &*call_info.func };
             locals = call_info.locals;
-            ip     = call_info.ip
+            ip     = call_info.ip;
+
+            // handlers entered by the returning function are gone with it
+            let depth = self.calls.len();
+
+            while self.handlers.last().is_some_and(|handler| handler.depth > depth) {
+              self.handlers.pop();
+            }
           } else {
+            self.handlers.truncate(handlers);
+
             break
           }
         },
 
+        Try(delta) => {
+          self.handlers.push(
+            Handler {
+              depth:  self.calls.len(),
+              height: self.stack.len(),
+              target: ip.wrapping_add(delta as isize as usize),
+            }
+          )
+        },
+
+        EndTry => { self.handlers.pop(); },
+
+        Raise => {
+          let message = match *attempt!(Self::heap_of(self.stack.pop().unwrap())) {
+            HeapValueType::Str(ref content) => content.to_string(),
+            _                               => unreachable!(),
+          };
+
+          throw!(message)
+        },
+
         Call(args) => {
           let args = args as usize;
 
@@ -170,9 +735,44 @@ impl VirtualMachine {
           if let Value::HeapValue(pointer) = func_val {
             let object = unsafe { &*pointer };
 
-            if let HeapValueType::Function(ref function) = object.kind {
-              fun = function
+            match object.kind {
+              HeapValueType::Function(ref function) => if function.arity == args {
+                fun = function
+              } else {
+                throw!(
+                  format!(
+                    "`{}` takes {} argument{}, got {}",
+                    func_val, function.arity, if function.arity == 1 { "" } else { "s" }, args
+                  )
+                )
+              },
+
+              HeapValueType::Native(ref native) => {
+                let args = self.stack.split_off(func_index + 1);
+
+                self.stack.pop();
+
+                match (native.function)(self, &args) {
+                  Ok(value)    => self.stack.push(value),
+                  Err(message) => throw!(message),
+                }
+
+                if self.exit_code.is_some() {
+                  self.calls.clear();
+                  self.handlers.truncate(handlers);
+
+                  return Ok(())
+                }
+
+                ip = ip.wrapping_add(1);
+
+                continue
+              },
+
+              _ => throw!(format!("can't call `{}`", func_val)),
             }
+          } else {
+            throw!(format!("can't call `{}`", func_val))
           }
 
           let mut new_locals = vec![Value::Nil; fun.locals.len()].into_boxed_slice();
@@ -192,6 +792,7 @@ impl VirtualMachine {
               ip,
               locals: old_locals,
               func: func_backup,
+              base: func_index,
             }
           );
 
@@ -205,6 +806,110 @@ impl VirtualMachine {
 
       ip = ip.wrapping_add(1)
     }
+
+    Ok(())
+  }
+
+
+
+  // checked integer arithmetic, where sized results have to fit their kind
+  fn arithmetic(op: Instruction, a: i128, b: i128, kind: Option<IntKind>) -> Result<Value, String> {
+    use self::Instruction::*;
+
+    let (symbol, result) = match op {
+      Add => ("+", a.checked_add(b)),
+      Sub => ("-", a.checked_sub(b)),
+      Mul => ("*", a.checked_mul(b)),
+
+      Div | Mod if b == 0 => return Err(format!("division by zero in `{} {} {}`", a, if let Div = op { "/" } else { "%" }, b)),
+
+      Div => ("/", a.checked_div(b)),
+      Mod => ("%", a.checked_rem(b)),
+
+      _ => if b < 0 {
+        return Err(format!("negative exponent in `{} ^ {}`", a, b))
+      } else {
+        ("^", a.checked_pow(b.min(u32::max_value() as i128) as u32))
+      },
+    };
+
+    match (result, kind) {
+      (Some(n), None)                       => Ok(Value::Int(n)),
+      (Some(n), Some(kind)) if kind.fits(n) => Ok(Value::Sized(n, kind)),
+
+      _ => Err(
+        match kind {
+          Some(kind) => format!("`{}` overflowed in `{} {} {}`", kind, a, symbol, b),
+          None       => format!("integer overflow in `{} {} {}`", a, symbol, b),
+        }
+      ),
+    }
+  }
+
+  // the explicit alternatives to failing on overflow
+  fn overflowing(op: Instruction, a: i128, b: i128, kind: Option<IntKind>) -> i128 {
+    use self::Instruction::*;
+
+    let n = match op {
+      WrappingAdd   => a.wrapping_add(b),
+      WrappingSub   => a.wrapping_sub(b),
+      WrappingMul   => a.wrapping_mul(b),
+      SaturatingAdd => a.saturating_add(b),
+      SaturatingSub => a.saturating_sub(b),
+      _             => a.saturating_mul(b),
+    };
+
+    match (op, kind) {
+      (_, None)                                           => n,
+      (WrappingAdd, Some(kind)) | (WrappingSub, Some(kind)) |
+      (WrappingMul, Some(kind))                           => kind.wrap(n),
+      (_, Some(kind))                                     => kind.saturate(n),
+    }
+  }
+
+  fn heap_of<'a>(value: Value) -> Result<&'a mut HeapValueType, String> {
+    if let Value::HeapValue(pointer) = value {
+      Ok(&mut unsafe { &mut *pointer }.kind)
+    } else {
+      Err(format!("expected heap value, found `{:?}`", value))
+    }
+  }
+
+  fn array_of<'a>(value: Value) -> Result<&'a mut Vec<Value>, String> {
+    if let HeapValueType::Array(ref mut content) = *Self::heap_of(value)? {
+      Ok(content)
+    } else {
+      Err(format!("expected array, found `{:?}`", value))
+    }
+  }
+
+  fn map_of<'a>(value: Value) -> Result<&'a mut OrderedMap, String> {
+    if let HeapValueType::Map(ref mut content) = *Self::heap_of(value)? {
+      Ok(content)
+    } else {
+      Err(format!("expected map, found `{:?}`", value))
+    }
+  }
+
+  fn elements_of<'a>(value: Value) -> Result<&'a mut Vec<Value>, String> {
+    match *Self::heap_of(value)? {
+      HeapValueType::Array(ref mut content) |
+      HeapValueType::Set(ref mut content)   => Ok(content),
+
+      _ => Err(format!("expected array or set, found `{:?}`", value)),
+    }
+  }
+
+  fn index_of(index: Value, len: usize) -> Result<usize, String> {
+    match index {
+      Value::Int(i) | Value::Sized(i, _) => if i >= 0 && (i as usize) < len {
+        Ok(i as usize)
+      } else {
+        Err(format!("index out of bounds: the len is {} but the index is {}", len, i))
+      },
+
+      _ => Err(format!("can't index with `{:?}`", index)),
+    }
   }
 
 
@@ -223,4 +928,168 @@ impl VirtualMachine {
 
     Value::HeapValue(object)
   }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::super::error;
+  use super::super::super::lexer::LexerBuilder;
+  use super::super::super::source::Source;
+  use super::super::super::module::tests::output;
+
+  // compiles and runs a program the visitor never saw, so the VM has to catch what it would have
+  fn run_unchecked(text: &str) -> (Result<(), ()>, Vec<String>) {
+    let source = Source::from("<test>", text.lines().map(|line| line.to_string()).collect());
+
+    let mut vm = VirtualMachine::new();
+
+    let (result, diagnostics) = error::collect(|| {
+      let ast   = Parser::parse_dialect(&source, &LexerBuilder::new())?;
+      let block = Compiler::new(&mut vm, &source).compile_main(&ast, "entry")?;
+
+      vm.execute(&block)
+    });
+
+    (result, diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect())
+  }
+
+  #[test]
+  fn calls_with_the_wrong_number_of_arguments_are_errors() {
+    let (result, messages) = run_unchecked("f := fun(n: int) -> int { n }\nf()");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("`<fun f>` takes 1 argument, got 0".to_string()));
+  }
+
+  #[test]
+  fn returning_without_a_value_is_an_internal_error() {
+    let mut vm = VirtualMachine::new();
+
+    let broken = CompiledBlock {
+      name:   "broken".to_string(),
+      code:   vec!(Instruction::Return).into_boxed_slice(),
+      consts: Vec::new(),
+      locals: Vec::new().into_boxed_slice(),
+      arity:  0,
+    };
+
+    let function = vm.allocate(HeapValueType::Function(broken));
+
+    let main = CompiledBlock {
+      name:   "entry".to_string(),
+      code:   vec!(Instruction::LoadConst(0), Instruction::Call(0), Instruction::Return).into_boxed_slice(),
+      consts: vec!(function),
+      locals: Vec::new().into_boxed_slice(),
+      arity:  0,
+    };
+
+    let (result, diagnostics) = error::collect(|| vm.execute(&main));
+
+    assert!(result.is_err());
+    assert_eq!(diagnostics[0].message, "internal error: `broken` returned without a value");
+  }
+
+  #[test]
+  fn wrong_calls_can_be_caught() {
+    let (result, messages) = run_unchecked("f := fun() -> int { 1 }\nx := try { f(1, 2) } catch e { 0 }");
+
+    assert!(result.is_ok(), "{:?}", messages);
+  }
+
+  #[test]
+  fn arrays_are_indexed_mutated_and_grown() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"a := [1, 2, 3]
+a[1] = 20
+push(a, 4)
+last := pop(a)
+grid: [[int]] = [[1, 2], [3]]
+grid[1][0] = 30
+push(grid[1], 40)
+println(a, len(a), last, grid, grid[1][1])
+e: [int] = []
+println(len(e))"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "[1, 20, 3] 3 4 [[1, 2], [30, 40]] 40\n0\n");
+  }
+
+  #[test]
+  fn arrays_are_bounds_checked() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"a := [1, 2, 3]
+e: [int] = []
+bounds := try {
+  x := a[5]
+  ""
+} catch err { err }
+empty := try {
+  x := pop(e)
+  ""
+} catch err { err }
+println(bounds)
+println(empty)
+a[3] = 4"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_err());
+    assert_eq!(printed, "index out of bounds: the len is 3 but the index is 5\ncan't pop from empty array\n");
+    assert_eq!(messages, vec!("index out of bounds: the len is 3 but the index is 3".to_string()));
+  }
+
+  #[test]
+  fn tuples_are_built_destructured_and_indexed() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"(a, b) := (1, "two")
+pair := fun(x: int) -> (int, int) { (x, x * 2) }
+(c, d) := pair(4)
+(a, c) = (c, a)
+t := (1, 'x', 2.5)
+println(a, b, c, d, t, t[1], pair(5)[1])"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "4 two 1 8 (1, 'x', 2.5) x 10\n");
+  }
+
+  #[test]
+  fn maps_are_keyed_by_content() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"m: {string: int} = ["a": 1]
+key := "b"
+m[key] = 2
+m["a"] = 10
+removed := remove(m, "a")
+println(m, len(m), has(m, "b"), has(m, "a"), removed)
+missing := try {
+  x := m["zzz"]
+  ""
+} catch err { err }
+println(missing)"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "[\"b\": 2] 1 yes no 10\nno such key `zzz` in map\n");
+  }
+
+  #[test]
+  fn map_keys_have_to_be_hashable() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages, _) = output(&mut vm, "m := [1.5: 1]");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("can't use `float` as map key, must be `int`, `char`, `bool` or `string`".to_string()));
+  }
+}
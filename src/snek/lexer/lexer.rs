@@ -1,9 +1,19 @@
 use super::*;
 
 use super::super::error::Response::Wrong;
+use super::super::parser::Operators;
 
 use std::rc::Rc;
 
+// what `Lexer::default` matches, editor grammars are generated from these too
+pub const KEYWORDS: &[&str] = &[
+  "->", "struct", "fun", "funky", "if", "elif", "else", "for", "in", "return", "continue", "break", "as", "import", "export", "try", "catch"
+];
+
+pub const BOOLS:     &[&str] = &["yes", "no"];
+pub const OPERATORS: &[&str] = &["++", "==", "!=", "<=", ">=", "^", "+", "-", "*", "/", "%", "<", ">"]; // longer ones first
+pub const SYMBOLS:   &[char] = &['(', ')', '[', ']', '{', '}', ',', ':', ';', '=', '.', '|'];
+
 pub struct Lexer<'l> {
   tokenizer: Tokenizer<'l>,
   matchers:  Vec<Rc<Matcher<'l>>>,
@@ -19,28 +29,87 @@ impl<'l> Lexer<'l> {
     }
   }
 
+  // lexes snek alone, dialects are lexed by what their `LexerBuilder` builds
   pub fn default(data: Vec<char>, source: &'l Source) -> Self {
+    LexerBuilder::new().build(data, source).0
+  }
+
+  pub fn match_token(&mut self) -> Result<Option<Token<'l>>, ()> {
+    for matcher in &mut self.matchers {
+      match self.tokenizer.try_match_token(matcher.as_ref())? {
+        Some(t) => return Ok(Some(t)),
+        None    => continue,
+      }
+    }
+
+    Ok(None)
+  }
+}
+
+// what lexers match beyond snek itself, for dialects of it like one with `5s` durations or `#ff0000` colors
+#[derive(Clone, Default)]
+pub struct LexerBuilder {
+  matchers:  Vec<Rc<for<'t> Matcher<'t>>>,
+  keywords:  Vec<String>,
+  operators: Vec<(String, u8, String)>,
+}
+
+impl LexerBuilder {
+  pub fn new() -> Self {
+    LexerBuilder::default()
+  }
+
+  // tried before any of snek's own, in the order they were added
+  pub fn matcher<M: for<'t> Matcher<'t> + 'static>(mut self, matcher: M) -> Self {
+    self.matchers.push(Rc::new(matcher));
+    self
+  }
+
+  pub fn keyword(mut self, keyword: &str) -> Self {
+    self.keywords.push(keyword.to_string());
+    self
+  }
+
+  // `left operator right` parses to `function(left, right)`, binding as tight as snek's own of the same precedence,
+  // from 1 for comparisons up to 4 for `^`
+  pub fn operator(mut self, operator: &str, precedence: u8, function: &str) -> Self {
+    self.operators.push((operator.to_string(), precedence, function.to_string()));
+    self
+  }
+
+  // a lexer of the dialect, along with the operators a parser of what it lexes has to know
+  pub fn build<'l>(&self, data: Vec<char>, source: &'l Source) -> (Lexer<'l>, Operators) {
     use self::TokenType::*;
 
     let tokenizer = Tokenizer::new(data, source);
-    let mut lexer = Self::new(tokenizer, source);
+    let mut lexer = Lexer::new(tokenizer, source);
+
+    for matcher in &self.matchers {
+      lexer.matchers.push(matcher.clone())
+    }
+
+    let keywords = KEYWORDS.iter().cloned().chain(self.keywords.iter().map(|keyword| keyword.as_str())).collect::<Vec<&str>>();
+
+    // longer operators first, so none is cut short by one it starts with
+    let mut operators = self.operators.iter().map(|&(ref operator, _, _)| operator.as_str()).collect::<Vec<&str>>();
 
-    lexer.matchers.push(Rc::new(CommentMatcher));  
+    operators.sort_by_key(|operator| ::std::cmp::Reverse(operator.len()));
+    operators.extend(OPERATORS);
+
+    lexer.matchers.push(Rc::new(CommentMatcher));
 
     lexer.matchers.push(Rc::new(EOLMatcher));
     lexer.matchers.push(Rc::new(StringLiteralMatcher));
 
     lexer.matchers.push(
       Rc::new(
-        KeyMatcher::new(Keyword, &[
-          "->", "struct", "fun", "funky", "if", "elif", "else", "return", "continue", "break", "as"
-        ])
+        KeyMatcher::new(Keyword, &keywords)
       )
     );
 
     lexer.matchers.push(
       Rc::new(
-        KeyMatcher::new(Bool, &["yes", "no"])
+        KeyMatcher::new(Bool, BOOLS)
       )
     );
 
@@ -50,35 +119,31 @@ impl<'l> Lexer<'l> {
 
     lexer.matchers.push(
       Rc::new(
-        ConstantStringMatcher::new(Operator, &["^", "++", "+", "-", "*", "/", "%", "<", ">", "==", "!=", "<=", ">="])
+        ConstantStringMatcher::new(Operator, &operators)
       )
     );
 
     lexer.matchers.push(
       Rc::new(
-        ConstantCharMatcher::new(Symbol, &['(', ')', '[', ']', '{', '}', ',', ':', ';', '=', '.', '|'])
+        ConstantCharMatcher::new(Symbol, SYMBOLS)
       )
     );
 
-    lexer
-  }
+    let mut table = Operators::default();
 
-  pub fn match_token(&mut self) -> Result<Option<Token<'l>>, ()> {
-    for matcher in &mut self.matchers {
-      match self.tokenizer.try_match_token(matcher.as_ref())? {
-        Some(t) => return Ok(Some(t)),
-        None    => continue,
-      }
+    for &(ref operator, precedence, ref function) in &self.operators {
+      table.register(operator, precedence, function)
     }
 
-    Ok(None)
+    (lexer, table)
   }
 }
 
-impl<'l> Iterator for Lexer<'l> {
-  type Item = Result<Token<'l>, ()>;
+impl<'l> Lexer<'l> {
+  // every token, whitespace and comments included, along with the exact text it was lexed from
+  pub fn next_lossless(&mut self) -> Option<Result<(Token<'l>, String), ()>> {
+    let start = self.tokenizer.index;
 
-  fn next(&mut self) -> Option<Result<Token<'l>, ()>> {
     let token = match self.match_token() {
       Ok(hmm) => match hmm {
         Some(n) => n,
@@ -91,7 +156,7 @@ impl<'l> Iterator for Lexer<'l> {
                 Wrong("bumped into weird character"),
                 self.source.file,
                 TokenElement::Pos(
-                  (pos.0, &self.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(self.source.lines.last().unwrap_or(&String::new()))),
+                  (pos.0, self.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(self.source.lines.last().unwrap_or(&String::new()))),
                   (pos.1 + 1, pos.1 + 1),
                 )
               )
@@ -103,10 +168,37 @@ impl<'l> Iterator for Lexer<'l> {
       Err(_) => return Some(Err(())),
     };
 
+    if token.token_type == TokenType::EOF {
+      return None
+    }
+
+    let end  = self.tokenizer.index.min(self.tokenizer.items.len());
+    let text = self.tokenizer.items[start .. end].iter().collect();
+
+    Some(Ok((token, text)))
+  }
+}
+
+impl<'l> Iterator for Lexer<'l> {
+  type Item = Result<Token<'l>, ()>;
+
+  fn next(&mut self) -> Option<Result<Token<'l>, ()>> {
+    let token = match self.next_lossless()? {
+      Ok((token, _)) => token,
+      Err(_)         => return Some(Err(())),
+    };
+
     match token.token_type {
-      TokenType::EOF        => None,
       TokenType::Whitespace => self.next(),
-      _                     => Some(Ok(token)),
+
+      // the line break after a line comment is a token of its own, block comments separate statements like one
+      TokenType::Comment => if token.lexeme.starts_with("---") {
+        Some(Ok(Token { token_type: TokenType::EOL, lexeme: "\n".into(), ..token }))
+      } else {
+        self.next()
+      },
+
+      _ => Some(Ok(token)),
     }
   }
-}
\ No newline at end of file
+}
@@ -16,10 +16,10 @@ macro_rules! token {
 
     let line = tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap());
 
-    if TokenType::Str == token_type || TokenType::Char == token_type {
-      Token::new(token_type, (pos.0, &line), (pos.1 + 1, pos.1 + accum.len() + 2), &accum) // delimeters
+    if [TokenType::Str, TokenType::StrPart, TokenType::StrEnd, TokenType::Char].contains(&token_type) {
+      Token::new(token_type, (pos.0, &line), (pos.1 + 1, pos.1 + accum.chars().count() + 2), &accum) // delimeters
     } else {
-      Token::new(token_type, (pos.0, &line), (pos.1 + 1, pos.1 + accum.len()), &accum)
+      Token::new(token_type, (pos.0, &line), (pos.1 + 1, pos.1 + accum.chars().count()), &accum)
     }
   }};
 }
@@ -36,29 +36,36 @@ pub struct CommentMatcher;
 
 impl<'t> Matcher<'t> for CommentMatcher {
   fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token<'t>>, ()> {
-    if tokenizer.peek_range(3).unwrap_or_else(String::new) == "---" {
+    let start = tokenizer.index;
+
+    if tokenizer.peek_range(3).unwrap_or_default() == "---" {
       tokenizer.advance_n(3);
 
       while !tokenizer.end() {
-        if tokenizer.peek_range(3).unwrap_or_else(String::new) == "---" {
+        if tokenizer.peek_range(3).unwrap_or_default() == "---" {
           tokenizer.advance_n(3);
           break
         }
 
-        tokenizer.advance()
+        if tokenizer.peek() == Some('\n') {
+          break_line(tokenizer)
+        } else {
+          tokenizer.advance()
+        }
       }
 
-      Ok(Some(token!(tokenizer, EOL, "\n".into())))
-
-    } else if tokenizer.peek_range(2).unwrap_or_else(String::new) == "--" {
+    } else if tokenizer.peek_range(2).unwrap_or_default() == "--" {
       while !tokenizer.end() && tokenizer.peek() != Some('\n') {
         tokenizer.advance()
       }
 
-      Ok(Some(token!(tokenizer, EOL, "\n".into())))
     } else {
-      Ok(None)
+      return Ok(None)
     }
+
+    let comment = tokenizer.items[start .. tokenizer.index.min(tokenizer.items.len())].iter().collect();
+
+    Ok(Some(token!(tokenizer, Comment, comment)))
   }
 }
 
@@ -66,25 +73,25 @@ impl<'t> Matcher<'t> for CommentMatcher {
 
 pub struct ConstantStringMatcher {
   token_type: TokenType,
-  constants: &'static [&'static str],
+  constants: Vec<String>,
 }
 
 impl ConstantStringMatcher {
-  pub fn new(token_type: TokenType, constants: &'static [&'static str]) -> Self {
+  pub fn new(token_type: TokenType, constants: &[&str]) -> Self {
     ConstantStringMatcher {
       token_type,
-      constants,
+      constants: constants.iter().map(|constant| constant.to_string()).collect(),
     }
   }
 }
 
 impl<'t> Matcher<'t> for ConstantStringMatcher {
   fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token<'t>>, ()> {
-    for constant in self.constants {
+    for constant in &self.constants {
       let len = constant.len();
       let c   = match tokenizer.peek_range(len) {
         Some(len) => len,
-        _         => return Ok(None),
+        _         => continue,
       };
 
       if c == *constant {
@@ -146,125 +153,206 @@ impl<'t> Matcher<'t> for ConstantCharMatcher {
 
 pub struct StringLiteralMatcher;
 
+impl StringLiteralMatcher {
+  // `r"..."`, with as many `#` around the quotes as it takes to not end early
+  fn raw<'t>(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token<'t>>, ()> {
+    let pos = tokenizer.pos;
+
+    let mut hashes = 0;
+
+    while tokenizer.peek_n(hashes + 1) == Some('#') {
+      hashes += 1
+    }
+
+    match tokenizer.peek_n(hashes + 1) {
+      Some('"') => (),
+
+      Some('\'') if hashes == 0 => return Err(
+        lex_error(tokenizer, "no such thing as a raw character literal", pos.0, (pos.1 + 1, pos.1 + 2))
+      ),
+
+      _ => return Ok(None),
+    }
+
+    tokenizer.advance_n(hashes + 2);
+
+    let closing = format!("\"{}", "#".repeat(hashes));
+    let mut string = String::new();
+
+    loop {
+      if tokenizer.end() {
+        return Err(
+          lex_error(tokenizer, format!("unterminated delimeter `{}`", closing), pos.0, (pos.1 + 1, pos.1 + hashes + 2))
+        )
+      }
+
+      if tokenizer.peek_range(hashes + 1).as_ref() == Some(&closing) {
+        break
+      }
+
+      if tokenizer.peek() == Some('\n') {
+        break_line(tokenizer)
+      } else {
+        tokenizer.advance()
+      }
+
+      string.push(tokenizer.items[tokenizer.index - 1])
+    }
+
+    tokenizer.advance_n(hashes + 1);
+
+    Ok(Some(token!(tokenizer, Str, string)))
+  }
+}
+
 impl<'t> Matcher<'t> for StringLiteralMatcher {
   fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token<'t>>, ()> {
-    let mut raw_marker = false;
+    let pos = tokenizer.pos;
 
-    let mut pos = tokenizer.pos;
+    // braces are counted inside of an interpolated expression, the unmatched `}` resumes its string
+    let resumed = match tokenizer.interpolations.last_mut() {
+      Some(interpolation) => match tokenizer.items[tokenizer.index] {
+        '{' => {
+          interpolation.depth += 1;
 
-    let delimeter  = match tokenizer.peek().unwrap() {
-      '"'  => '"',
-      '\'' => '\'',
-      'r' => {
-        if tokenizer.peek_n(1) == Some('"') {
-          raw_marker = true;
-          tokenizer.advance();
+          return Ok(None)
+        },
 
-          pos = tokenizer.pos;
+        '}' if interpolation.depth > 0 => {
+          interpolation.depth -= 1;
 
-          '"'
-        } else if tokenizer.peek_n(1) == Some('\'') {
-          return Err(
-            response!(
-              Wrong("no such thing as a raw character literal"),
-              tokenizer.source.file,
-              TokenElement::Pos(
-                (pos.0, &tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
-                (pos.1 - 1, pos.1),
-              )
-            )
-          )
-        } else {
           return Ok(None)
-        }
+        },
+
+        '}' => true,
+        _   => false,
       },
-      _ => return Ok(None),
+
+      None => false,
+    };
+
+    // only multi-line strings have their indentation stripped
+    let mut indent = None;
+
+    let delimeter = if resumed {
+      indent = tokenizer.interpolations.pop().unwrap().indent;
+      tokenizer.advance();
+
+      '"'
+    } else {
+      match tokenizer.peek().unwrap() {
+        '"' => if tokenizer.peek_range(3).as_deref() == Some("\"\"\"") {
+          tokenizer.advance_n(3);
+
+          let common = common_indent(tokenizer);
+
+          // a line break right after the quotes isn't part of the string
+          if tokenizer.peek() == Some('\n') {
+            break_line(tokenizer);
+            strip_indent(tokenizer, common)
+          }
+
+          indent = Some(common);
+
+          '"'
+        } else {
+          tokenizer.advance();
+
+          '"'
+        },
+
+        '\'' => {
+          tokenizer.advance();
+
+          '\''
+        },
+
+        'r' => return self.raw(tokenizer),
+        _   => return Ok(None),
+      }
     };
 
-    tokenizer.advance();
+    let closing = if indent.is_some() { "\"\"\"" } else if delimeter == '"' { "\"" } else { "'" };
 
-    let mut string       = String::new();
-    let mut found_escape = false;
+    let mut string = String::new();
+
+    // where the last line of a multi-line string starts, and whether it's only indentation so far
+    let mut last_line = None;
+    let mut blank     = false;
 
     loop {
       if tokenizer.end() {
         return Err(
-          response!(
-            Wrong(format!("unterminated delimeter `{}`", delimeter)),
-            tokenizer.source.file,
-            TokenElement::Pos(
-              (pos.0 + 1, &tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
-              (pos.1.saturating_sub(1), pos.1 + 1),
-            )
-          )
+          lex_error(tokenizer, format!("unterminated delimeter `{}`", closing), pos.0, (pos.1 + 1, pos.1 + closing.len()))
         )
       }
 
-      if raw_marker {
-        if tokenizer.peek().unwrap() == '"' {
-          break
-        }
+      match tokenizer.peek().unwrap() {
+        '\\' => {
+          string.push(escape(tokenizer)?);
+          blank = false
+        },
 
-        string.push(tokenizer.next().unwrap())
-      } else if found_escape {
-        string.push(
-          match tokenizer.next().unwrap() {
-            c @ '\\' | c @ '\'' | c @ '"' => c,
-            'n' => '\n',
-            'r' => '\r',
-            't' => '\t',
-            escaped => return Err(
-              response!(
-                Wrong(format!("unexpected escape character: {}", escaped)),
-                tokenizer.source.file,
-                TokenElement::Pos(
-                  (tokenizer.pos.0, &tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
-                  (tokenizer.pos.1 - 1, tokenizer.pos.1),
-                )
-              )
-            ),
-          }
-        );
+        '\n' => {
+          break_line(tokenizer);
+          string.push('\n');
 
-        found_escape = false
-      } else {
-        match tokenizer.peek().unwrap() {
-          '\\' => {
-            tokenizer.next();
-            found_escape = true
+          if let Some(indent) = indent {
+            strip_indent(tokenizer, indent);
+
+            last_line = Some(string.len());
+            blank     = true
+          }
+        },
+
+        // `{}` and `{{` are left to `format`, any other `{` starts an embedded expression
+        '{' if delimeter == '"' => match tokenizer.peek_n(1) {
+          Some('}') | Some('{') => {
+            string.push(tokenizer.next().unwrap());
+            string.push(tokenizer.next().unwrap());
+            blank = false
           },
 
-          // check for valid closing delimeter and alternative
-          c => if c == delimeter {
-            if string.len() > 0 && string != " " {
-              break
-            } else {
-              string.push(tokenizer.next().unwrap())
-            }
-          } else {
-            string.push(tokenizer.next().unwrap())
+          _ => {
+            tokenizer.advance();
+            tokenizer.interpolations.push(Interpolation { depth: 0, indent });
+
+            return Ok(Some(token!(tokenizer, StrPart, string)))
           },
-        }
+        },
+
+        c => if c == delimeter && tokenizer.peek_range(closing.len()).as_deref() == Some(closing) {
+          break
+        } else {
+          string.push(tokenizer.next().unwrap());
+          blank &= c == ' ' || c == '\t'
+        },
       }
     }
 
-    tokenizer.advance();
-    
-    if delimeter == '"' {
+    tokenizer.advance_n(closing.len());
+
+    // the line of the closing quotes only sets the indentation
+    if let (Some(start), true) = (last_line, blank) {
+      string.truncate(start - 1)
+    }
+
+    if resumed {
+      Ok(Some(token!(tokenizer, StrEnd, string)))
+    } else if delimeter == '"' {
       Ok(Some(token!(tokenizer, Str, string)))
     } else {
-      if string.len() > 1 {
+      if string.is_empty() {
         let pos = tokenizer.last_position();
 
         Err(
-          response!(
-            Wrong("character literal may not contain more than one codepoint"),
-            tokenizer.source.file,
-            TokenElement::Pos(
-              (pos.0, &tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
-              (pos.1 + 2, pos.1 + string.len() + 1),
-            )
-          )
+          lex_error(tokenizer, "empty character literal", pos.0, (pos.1 + 1, pos.1 + 3))
+        )
+      } else if string.chars().count() > 1 {
+        let pos = tokenizer.last_position();
+
+        Err(
+          lex_error(tokenizer, "character literal may not contain more than one codepoint", pos.0, (pos.1 + 2, pos.1 + string.len() + 1))
         )
       } else {
         Ok(Some(token!(tokenizer, Char, string)))
@@ -273,6 +361,130 @@ impl<'t> Matcher<'t> for StringLiteralMatcher {
   }
 }
 
+// reads an escape sequence, starting at its `\`
+fn escape(tokenizer: &mut Tokenizer) -> Result<char, ()> {
+  let pos = tokenizer.pos;
+
+  tokenizer.advance();
+
+  let escaped = match tokenizer.next() {
+    Some(c) => c,
+    None    => return Err(lex_error(tokenizer, "unterminated escape sequence", pos.0, (pos.1 + 1, pos.1 + 1))),
+  };
+
+  let c = match escaped {
+    c @ '\\' | c @ '\'' | c @ '"' | c @ '{' | c @ '}' => c,
+    'n' => '\n',
+    'r' => '\r',
+    't' => '\t',
+    '0' => '\0',
+
+    // `\x7F` at most, anything above isn't a character on its own
+    'x' => {
+      let digits = tokenizer.peek_range(2).unwrap_or_default();
+
+      if digits.len() < 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(lex_error(tokenizer, "expected two hex digits after `\\x`", pos.0, (pos.1 + 1, tokenizer.pos.1)))
+      }
+
+      tokenizer.advance_n(2);
+
+      match u8::from_str_radix(&digits, 16) {
+        Ok(n) if n <= 0x7F => n as char,
+        _                  => return Err(
+          lex_error(tokenizer, format!("hex escape `\\x{}` is out of range, use `\\u{{..}}` above `\\x7F`", digits), pos.0, (pos.1 + 1, tokenizer.pos.1))
+        ),
+      }
+    },
+
+    'u' => {
+      if tokenizer.peek() != Some('{') {
+        return Err(lex_error(tokenizer, "expected `{` after `\\u`", pos.0, (pos.1 + 1, tokenizer.pos.1)))
+      }
+
+      tokenizer.advance();
+
+      let digits = tokenizer.collect_while(|c| c.is_ascii_hexdigit() || c == '_').replace('_', "");
+
+      if tokenizer.peek() != Some('}') {
+        return Err(lex_error(tokenizer, "unterminated unicode escape", pos.0, (pos.1 + 1, tokenizer.pos.1)))
+      }
+
+      tokenizer.advance();
+
+      if digits.is_empty() || digits.len() > 6 {
+        return Err(lex_error(tokenizer, "unicode escape must have between 1 and 6 hex digits", pos.0, (pos.1 + 1, tokenizer.pos.1)))
+      }
+
+      match u32::from_str_radix(&digits, 16).ok().and_then(::std::char::from_u32) {
+        Some(c) => c,
+        None    => return Err(
+          lex_error(tokenizer, format!("`\\u{{{}}}` isn't a unicode scalar value", digits), pos.0, (pos.1 + 1, tokenizer.pos.1))
+        ),
+      }
+    },
+
+    escaped => return Err(
+      lex_error(tokenizer, format!("unexpected escape character: {}", escaped), pos.0, (pos.1 + 1, tokenizer.pos.1))
+    ),
+  };
+
+  Ok(c)
+}
+
+// the least indentation among the lines of a multi-line string, not counting blank lines and the opening line
+fn common_indent(tokenizer: &Tokenizer) -> usize {
+  let mut content = String::new();
+  let mut index   = tokenizer.index;
+
+  while let Some(&c) = tokenizer.items.get(index) {
+    if c == '"' && tokenizer.items.get(index + 1 .. index + 3) == Some(&['"', '"']) {
+      break
+    }
+
+    // an escaped quote can't close the string
+    if c == '\\' {
+      index += 1
+    }
+
+    content.push(c);
+    index += 1
+  }
+
+  content.split('\n').skip(1)
+    .filter(|line| line.chars().any(|c| c != ' ' && c != '\t'))
+    .map(|line| line.chars().take_while(|&c| c == ' ' || c == '\t').count())
+    .min()
+    .unwrap_or(0)
+}
+
+fn strip_indent(tokenizer: &mut Tokenizer, indent: usize) {
+  for _ in 0 .. indent {
+    match tokenizer.peek() {
+      Some(' ') | Some('\t') => tokenizer.advance(),
+      _                      => break,
+    }
+  }
+}
+
+// steps over a line break inside of a token, still counting lines
+fn break_line(tokenizer: &mut Tokenizer) {
+  tokenizer.pos.0 += 1;
+  tokenizer.pos.1 = 0;
+  tokenizer.index += 1;
+}
+
+fn lex_error<T: ::std::fmt::Display>(tokenizer: &Tokenizer, message: T, line: usize, span: (usize, usize)) {
+  response!(
+    Wrong(message),
+    tokenizer.source.file,
+    TokenElement::Pos(
+      (line, tokenizer.source.lines.get(line.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
+      span,
+    )
+  )
+}
+
 
 
 pub struct IdentifierMatcher;
@@ -301,18 +513,23 @@ impl<'t> Matcher<'t> for NumberLiteralMatcher {
     let mut accum = String::new();
 
     let curr = tokenizer.next().unwrap();
-    if curr.is_digit(10) {
+    if curr.is_ascii_digit() || curr == '.' && tokenizer.peek().is_some_and(|c| c.is_ascii_digit()) {
       accum.push(curr)
-    } else if curr == '.' {
-      accum.push_str("0.")
     } else {
       return Ok(None)
     }
 
+    let prefixed = curr == '0' && tokenizer.peek().is_some_and(|c| "xbo".contains(c));
+
+    // the literal is checked as a whole below, so anything glued to it is taken in
     while !tokenizer.end() {
       let current = tokenizer.peek().unwrap();
-      if !current.is_whitespace() && current.is_digit(10) || current == '.' {
-        if current == '.' && accum.contains('.') {
+
+      let point    = current == '.' && tokenizer.peek_n(1).is_some_and(|c| c.is_ascii_digit());
+      let exponent = "+-".contains(current) && !prefixed && (accum.ends_with('e') || accum.ends_with('E'));
+
+      if current.is_alphanumeric() || current == '_' || point || exponent {
+        if point && accum.contains('.') {
           let pos = tokenizer.pos;
 
           return Err(
@@ -320,57 +537,55 @@ impl<'t> Matcher<'t> for NumberLiteralMatcher {
               Wrong("unexpected extra decimal point"),
               tokenizer.source.file,
               TokenElement::Pos(
-                (pos.0, &tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
+                (pos.0, tokenizer.source.lines.get(pos.0.saturating_sub(1)).unwrap_or(tokenizer.source.lines.last().unwrap())),
                 (pos.1 + 1, pos.1 + 1),
               )
             )
           )
         }
+
         accum.push(tokenizer.next().unwrap())
       } else {
         break
       }
     }
 
-    if &accum == "0." {
-      Ok(None)
-    } else {
-      if accum.contains(".") {
-        let literal: String = match accum.parse::<f64>() {
-          Ok(result) => result.to_string(),
-          Err(error) => panic!("unable to parse float: {}", error)
-        };
+    match parse_number(&accum) {
+      Ok(Number::Int(..))  => Ok(Some(token!(tokenizer, Int, accum))),
+      Ok(Number::Float(_)) => Ok(Some(token!(tokenizer, Double, accum))),
 
-        Ok(Some(token!(tokenizer, Double, accum)))
-      } else {
-        let literal: String = match accum.parse::<i64>() {
-          Ok(result) => result.to_string(),
-          Err(error) => panic!("unable to parse int: {}", error)
-        };
+      Err(message) => {
+        let token = token!(tokenizer, Int, accum);
 
-        Ok(Some(token!(tokenizer, Int, accum)))
-      }
+        Err(
+          response!(
+            Wrong(message),
+            tokenizer.source.file,
+            TokenElement::Ref(&token)
+          )
+        )
+      },
     }
   }
 }
 
 pub struct KeyMatcher {
   token_type: TokenType,
-  constants: &'static [&'static str],
+  constants: Vec<String>,
 }
 
 impl KeyMatcher {
-  pub fn new(token_type: TokenType, constants:  &'static [&'static str]) -> Self {
+  pub fn new(token_type: TokenType, constants: &[&str]) -> Self {
     KeyMatcher {
       token_type,
-      constants,
+      constants: constants.iter().map(|constant| constant.to_string()).collect(),
     }
   }
 }
 
 impl<'t> Matcher<'t> for KeyMatcher {
   fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token<'t>>, ()> {
-    for constant in self.constants {
+    for constant in &self.constants {
       if let Some(s) = tokenizer.peek_range(constant.len()) {
         if s == *constant {
           if let Some(c) = tokenizer.peek_n(constant.len()) {
@@ -2,10 +2,12 @@ pub mod token;
 pub mod matcher;
 pub mod tokenizer;
 pub mod lexer;
+pub mod number;
 
 pub use super::source::*;
 
 pub use self::token::*;
 pub use self::matcher::*;
 pub use self::tokenizer::*;
-pub use self::lexer::*;
\ No newline at end of file
+pub use self::lexer::*;
+pub use self::number::*;
\ No newline at end of file
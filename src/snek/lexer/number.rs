@@ -0,0 +1,94 @@
+use super::super::interpreter::IntKind;
+
+// the value of a number literal, as written in the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+  Int(u128, Option<IntKind>),
+  Float(f64),
+}
+
+// reads `0x`, `0b` and `0o` prefixes, `_` separators, exponents and sized int suffixes
+pub fn parse_number(lexeme: &str) -> Result<Number, String> {
+  let (radix, body) = match lexeme.get(.. 2) {
+    Some("0x") => (16, &lexeme[2 ..]),
+    Some("0b") => (2,  &lexeme[2 ..]),
+    Some("0o") => (8,  &lexeme[2 ..]),
+    _          => (10, lexeme),
+  };
+
+  let is_float = radix == 10 && body.contains(['.', 'e', 'E']);
+
+  // whatever follows the digits is the suffix
+  let split = body.find(|c: char| !(c.is_digit(radix) || c == '_' || is_float && ".eE+-".contains(c))).unwrap_or(body.len());
+
+  let (digits, suffix) = body.split_at(split);
+  let digits           = digits.replace('_', "");
+
+  if digits.is_empty() {
+    return Err(format!("expected digits in `{}`", lexeme))
+  }
+
+  if suffix.starts_with(|c: char| c.is_ascii_digit()) {
+    return Err(format!("invalid digit `{}` in base {} literal `{}`", &suffix[.. 1], radix, lexeme))
+  }
+
+  let kind = match suffix {
+    ""     => None,
+    suffix => match IntKind::from_name(suffix) {
+      Some(kind) if !is_float => Some(kind),
+      Some(kind)              => return Err(format!("float literal `{}` can't have the suffix `{}`", lexeme, kind)),
+      None                    => return Err(format!("invalid suffix `{}` on number literal", suffix)),
+    },
+  };
+
+  if is_float {
+    return match digits.parse::<f64>() {
+      Ok(n) if n.is_finite() => Ok(Number::Float(n)),
+      Ok(_)                  => Err(format!("float literal `{}` is out of range", lexeme)),
+      Err(_)                 => Err(format!("malformed float literal `{}`", lexeme)),
+    }
+  }
+
+  let n = match u128::from_str_radix(&digits, radix) {
+    Ok(n)  => n,
+    Err(_) => return Err(format!("literal `{}` doesn't fit in 128 bits", lexeme)),
+  };
+
+  match kind {
+    Some(kind) if n > kind.max() as u128 => Err(format!("literal `{}` doesn't fit in `{}`", lexeme, kind)),
+    None       if n > i128::max_value() as u128 => Err(format!("literal `{}` doesn't fit in `int`", lexeme)),
+
+    _ => Ok(Number::Int(n, kind)),
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn prefixes_separators_exponents_and_suffixes_are_read() {
+    assert_eq!(parse_number("0xff"),        Ok(Number::Int(255, None)));
+    assert_eq!(parse_number("0b1010"),      Ok(Number::Int(10, None)));
+    assert_eq!(parse_number("0o17"),        Ok(Number::Int(15, None)));
+    assert_eq!(parse_number("1_000_000"),   Ok(Number::Int(1_000_000, None)));
+    assert_eq!(parse_number("0xffu8"),      Ok(Number::Int(255, Some(IntKind::U8))));
+    assert_eq!(parse_number("1.5e-3"),      Ok(Number::Float(1.5e-3)));
+    assert_eq!(parse_number("2E3"),         Ok(Number::Float(2000.0)));
+    assert_eq!(parse_number("1_0.2_5"),     Ok(Number::Float(10.25)));
+  }
+
+  #[test]
+  fn malformed_and_out_of_range_literals_are_errors() {
+    assert_eq!(parse_number("0x"),     Err("expected digits in `0x`".to_string()));
+    assert_eq!(parse_number("0b102"),  Err("invalid digit `2` in base 2 literal `0b102`".to_string()));
+    assert_eq!(parse_number("12abc"),  Err("invalid suffix `abc` on number literal".to_string()));
+    assert_eq!(parse_number("1.5i32"), Err("float literal `1.5i32` can't have the suffix `i32`".to_string()));
+    assert_eq!(parse_number("256u8"),  Err("literal `256u8` doesn't fit in `u8`".to_string()));
+    assert_eq!(parse_number("1e999"),  Err("float literal `1e999` is out of range".to_string()));
+
+    assert_eq!(parse_number(&format!("{}", i128::max_value() as u128 + 1)), Err(format!("literal `{}` doesn't fit in `int`", i128::max_value() as u128 + 1)));
+  }
+}
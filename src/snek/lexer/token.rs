@@ -1,11 +1,15 @@
 use colored::Colorize;
 use std::fmt;
 
+use super::super::source::Location;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
   Int,
   Double,
   Str,
+  StrPart, // a string segment followed by an embedded expression
+  StrEnd,  // the segment closing an interpolated string
   Char,
   Bool,
   Identifier,
@@ -13,6 +17,7 @@ pub enum TokenType {
   Symbol,
   Operator,
   Whitespace,
+  Comment,
   EOL,
   EOF,
 }
@@ -25,6 +30,8 @@ impl fmt::Display for TokenType {
       Int        => write!(f, "Int"),
       Double     => write!(f, "Double"),
       Str        => write!(f, "Str"),
+      StrPart    => write!(f, "StrPart"),
+      StrEnd     => write!(f, "StrEnd"),
       Char       => write!(f, "Char"),
       Bool       => write!(f, "Bool"),
       Identifier => write!(f, "Identifier"),
@@ -32,6 +39,7 @@ impl fmt::Display for TokenType {
       Keyword    => write!(f, "Keyword"),
       Operator   => write!(f, "Operator"),
       Whitespace => write!(f, "Whitespace"),
+      Comment    => write!(f, "Comment"),
       EOL        => write!(f, "EOL"),
       EOF        => write!(f, "EOF"),
     }
@@ -57,12 +65,31 @@ impl<'t> PartialEq<Token<'t>> for TokenElement<'t> {
   }
 }
 
+impl<'s> TokenElement<'s> {
+  // the line and the columns of the first and last char pointed at, as in `Pos`
+  pub fn location(&self) -> Option<Location> {
+    match *self {
+      Ref(r)           => Some((r.line.0, (r.slice.0, r.slice.1.min(r.line.1.chars().count())))),
+      Pos(line, slice) => Some((line.0, slice)),
+      Line(line)       => Some((line.0, (1, line.1.chars().count()))),
+      Row(row)         => match (row.first(), row.last()) {
+        (Some(first), Some(last)) => Some((first.line.0, (first.slice.0, last.slice.1))),
+        _                         => None,
+      },
+
+      _ => None,
+    }
+  }
+}
+
 impl<'s> fmt::Display for TokenElement<'s> {
   fn fmt (&self, f: &mut fmt::Formatter) -> fmt::Result {
     match *self {
       Ref (r) => {
-        if r.slice.1 > r.line.1.len() {
-          write!(f, "{}", Pos(r.line, (r.slice.0, r.line.1.len())))
+        let len = r.line.1.chars().count();
+
+        if r.slice.1 > len {
+          write!(f, "{}", Pos(r.line, (r.slice.0, len)))
         } else {
           write!(f, "{}", Pos(r.line, r.slice))
         }
@@ -83,7 +110,13 @@ impl<'s> fmt::Display for TokenElement<'s> {
       Pos(line, slice) => {
         let linepad = format!("{:5} │", " ").blue().bold();
         let lineno = format!("{:5} │ ", line.0).blue().bold();
-        let mut mark = line.1[slice.0.saturating_sub(1) .. slice.1].to_string();
+
+        // slices count chars, which may span several bytes
+        let offset = |n: usize| line.1.char_indices().nth(n).map_or(line.1.len(), |(i, _)| i);
+        let (start, end) = (offset(slice.0.saturating_sub(1)), offset(slice.1));
+        let end          = end.max(start);
+
+        let mut mark = line.1[start .. end].to_string();
 
         if mark.split_whitespace().count() == 0 {
           mark = format!("{:─>count$}", ">".bold().magenta(), count=mark.len());
@@ -93,7 +126,7 @@ impl<'s> fmt::Display for TokenElement<'s> {
 
         write!(f, "\n{}\n{}{}{}{}\n{}",
           linepad,
-          lineno, &line.1[..slice.0.saturating_sub(1)], mark, &line.1[slice.1..],
+          lineno, &line.1[.. start], mark, &line.1[end ..],
           linepad
         )
       },
@@ -144,9 +177,9 @@ impl<'t> PartialEq<TokenElement<'t>> for Token<'t> {
   fn eq (&self, rhs: &TokenElement<'t>) -> bool {
     match *rhs {
       Type (ref t)        => self.token_type == *t,
-      Lexeme (ref l)      => self.lexeme     == *l,
-      Pair (ref t, ref l) => self.lexeme     == *l && self.token_type == *t,
-      Ref (ref t)         => self            == *t,
+      Lexeme (l)          => self.lexeme     == l,
+      Pair (ref t, l)     => self.lexeme     == l && self.token_type == *t,
+      Ref (t)             => self            == t,
       _                   => false
     }
   }
@@ -16,6 +16,13 @@ impl Snapshot {
 }
 
 
+// an embedded expression being lexed, and how to go on with the string around it
+pub struct Interpolation {
+  pub depth:  usize,         // unmatched `{` inside of the expression
+  pub indent: Option<usize>, // stripped from every line of a multi-line string
+}
+
+
 
 pub struct Tokenizer<'t> {
   pub pos: (usize, usize),
@@ -23,7 +30,9 @@ pub struct Tokenizer<'t> {
   pub index:     usize,
   pub items:     Vec<char>,
   pub source:    &'t Source,
-  pub snapshots: Vec<Snapshot>
+  pub snapshots: Vec<Snapshot>,
+
+  pub interpolations: Vec<Interpolation>,
 }
 
 impl<'t> Tokenizer<'t> {
@@ -35,6 +44,8 @@ impl<'t> Tokenizer<'t> {
       source,
       index:     0,
       snapshots: Vec::new(),
+
+      interpolations: Vec::new(),
     }
   }
 
@@ -43,7 +54,7 @@ impl<'t> Tokenizer<'t> {
   }
 
   pub fn advance(&mut self) {
-    if let Some(item) = self.items.get(self.index + 1) {
+    if self.items.get(self.index + 1).is_some() {
       self.pos.1 += 1
     }
 
@@ -96,8 +107,8 @@ impl<'t> Tokenizer<'t> {
         Some(
           Token::new(
             TokenType::EOF,
-            (self.pos.0, if self.source.lines.len() > 0 {
-                &self.source.lines.get(self.pos.0).unwrap_or(self.source.lines.first().unwrap())
+            (self.pos.0, if !self.source.lines.is_empty() {
+                self.source.lines.get(self.pos.0).unwrap_or(self.source.lines.first().unwrap())
               } else {
                 ""
               }
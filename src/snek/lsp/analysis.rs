@@ -0,0 +1,623 @@
+use std::collections::{ HashMap, HashSet };
+use std::path::PathBuf;
+use std::panic::{ self, AssertUnwindSafe };
+use std::ops::Range;
+use std::rc::Rc;
+use std::mem;
+
+use super::super::lexer::*;
+use super::super::parser::*;
+use super::super::visitor::*;
+use super::super::error::{ self, Diagnostic };
+use super::super::error::Response::Wrong;
+use super::super::module::{ ModuleLoader, Module };
+use super::super::interpreter::VirtualMachine;
+use super::super::syntax::incremental::Document;
+
+
+
+// a top-level declaration
+pub struct Symbol {
+  pub name:     String,
+  pub location: Location,
+  pub t:        Option<Type>, // none when checking stopped before getting to it
+}
+
+pub fn is_constant(t: &Type) -> bool {
+  match t.mode {
+    TypeMode::Immutable => true,
+    _                   => false,
+  }
+}
+
+// what checking a file taught about it, still useful after its text has moved on
+pub struct Index {
+  pub symbols: Vec<Symbol>,
+  pub names:   HashMap<Location, Type>, // types of names where declared and used
+  pub trace:   Trace,
+  pub frames:  Vec<(SymTab, TypeTab)>, // scopes as they were when closed
+  pub globals: (SymTab, TypeTab),      // the outermost scope, as far as checking got
+
+  resume: Option<Resume>,
+}
+
+// where checking the next version of a document can pick up from, the state checking was in around the chunks
+// edited last, which the edits after are likely to fall in again
+struct Resume {
+  chunks:  Vec<Vec<String>>,                          // the lines of each chunk, to tell which the next version still has
+  region:  Range<usize>,                              // of the chunks edited last
+  before:  ((SymTab, TypeTab), usize),                // the globals at its start, with how many frames were closed by then
+  after:   Option<((SymTab, TypeTab), usize)>,        // and at its end, when all of the document checked
+  hoisted: Vec<(String, Option<Type>)>,               // the top-level functions, declared before anything's checked
+  imports: Vec<(String, String)>,
+  modules: HashMap<String, Rc<Module>>,
+
+  diagnostics: Vec<Diagnostic>,
+}
+
+impl Index {
+  // the type of the innermost expression at a line and column
+  pub fn type_at(&self, line: usize, column: usize) -> Option<(Location, Type)> {
+    let innermost = self.trace.types.iter()
+      .filter(|&(location, _)| contains(location, line, column))
+      .min_by_key(|&(&(_, (start, end)), _)| end - start);
+
+    if let Some((location, t)) = innermost {
+      return Some((*location, t.clone()))
+    }
+
+    self.symbols.iter()
+      .find(|symbol| contains(&symbol.location, line, column))
+      .and_then(|symbol| symbol.t.clone().map(|t| (symbol.location, t)))
+  }
+
+  // where the name at a line and column was declared
+  pub fn definition_at(&self, line: usize, column: usize) -> Option<Location> {
+    self.trace.definitions.iter()
+      .filter(|&(location, _)| contains(location, line, column))
+      .min_by_key(|&(&(_, (start, end)), _)| end - start)
+      .map(|(_, declared)| *declared)
+  }
+
+  // names in scope at a line, innermost first, with their types where known
+  pub fn names_at(&self, line: usize) -> Vec<(String, Option<Type>)> {
+    let scope = self.trace.scopes.iter()
+      .filter(|&&((first, last), frame)| first <= line && line <= last && frame < self.frames.len())
+      .min_by_key(|&&((first, last), _)| last - first)
+      .map(|&(_, frame)| &self.frames[frame]);
+
+    let mut names = Vec::new();
+    let mut seen  = HashSet::new();
+
+    for &(ref symtab, ref typetab) in scope.into_iter().chain(Some(&self.globals)) {
+      let mut tabs = Some((symtab, typetab));
+
+      while let Some((symtab, typetab)) = tabs {
+        let mut scope_names = symtab.names.borrow().iter()
+          .filter(|&(name, _)| seen.insert(name.clone()))
+          .map(|(name, &index)| (name.clone(), typetab.types.borrow().get(index).cloned()))
+          .collect::<Vec<(String, Option<Type>)>>();
+
+        scope_names.sort_by(|a, b| a.0.cmp(&b.0));
+        names.extend(scope_names);
+
+        tabs = match (&symtab.parent, &typetab.parent) {
+          (&Some(ref symtab), &Some(ref typetab)) => Some((&**symtab, &**typetab)),
+          _                                       => None,
+        }
+      }
+    }
+
+    names
+  }
+}
+
+// columns count from 1, a cursor right after the last char still touches it
+fn contains(location: &Location, line: usize, column: usize) -> bool {
+  let &(at, (start, end)) = location;
+
+  at == line && start <= column && column <= end + 1
+}
+
+
+
+pub struct Analysis {
+  pub diagnostics: Vec<Diagnostic>,
+  pub index:       Option<Index>, // none unless the checker got through it without crashing
+}
+
+impl Analysis {
+  // checks a document as it is in memory, which needn't match what's on disk, from the statements its chunks hold,
+  // picking up from where checking its last version left off when the edits since fall in the chunks edited before them
+  pub fn new(document: &Document, search_path: Vec<PathBuf>, last: Option<Index>) -> Self {
+    let source = Source::from(&document.path, document.lines().iter().map(|line| line.to_string()).collect());
+
+    let mut index = None;
+
+    let (_, diagnostics) = error::collect(|| {
+      let checked = panic::catch_unwind(AssertUnwindSafe(|| Self::check(document, &source, search_path, last, &mut index)));
+
+      if checked.is_err() {
+        response!(
+          Wrong("the checker crashed on this file"),
+          source.file
+        )
+      }
+    });
+
+    if let Some(resume) = index.as_mut().and_then(|index: &mut Index| index.resume.as_mut()) {
+      resume.diagnostics = diagnostics.clone()
+    }
+
+    Analysis {
+      diagnostics,
+      index,
+    }
+  }
+
+  fn check(document: &Document, source: &Source, search_path: Vec<PathBuf>, last: Option<Index>, index: &mut Option<Index>) -> Result<(), ()> {
+    let mut ast    = Vec::new();
+    let mut bounds = vec!(0); // where the statements of each chunk start in `ast`, and where the last ones end
+
+    // spans of a chunk's statements count from its own start
+    for chunk in document.chunks() {
+      let offset = source.span(chunk.first + 1, (1, 0)).start;
+      let moved  = |span: Span| Span { file: source.id, start: span.start + offset, end: span.end + offset };
+
+      ast.extend(chunk.statements().iter().map(|statement| statement.map_spans(&moved)));
+      bounds.push(ast.len())
+    }
+
+    let chunks  = document.chunks().iter().map(|chunk| chunk.lines().to_vec()).collect::<Vec<Vec<String>>>();
+    let imports = ast.iter().filter_map(|statement| match statement.node {
+      StatementNode::Import(ref path, ref alias) => Some((path.clone(), alias.clone())),
+      _                                          => None,
+    }).collect::<Vec<(String, String)>>();
+
+    let mut last = last.and_then(|mut index| index.resume.take().map(|resume| (index, resume)));
+
+    // only the chunks edited since are checked again, as part of the region edited before them if they're all in it
+    let (region, resumes) = match last {
+      Some((_, ref resume)) => {
+        let (front, back) = shared(&resume.chunks, &chunks);
+
+        if resume.region.start <= front && resume.chunks.len() - resume.region.end <= back && resume.imports == imports {
+          (resume.region.start .. chunks.len() - (resume.chunks.len() - resume.region.end), true)
+        } else {
+          (front .. (chunks.len() - back).max(front), false)
+        }
+      },
+
+      None => (0 .. 0, false),
+    };
+
+    if !resumes {
+      last = None
+    }
+
+    let mut vm     = VirtualMachine::new();
+    let mut loader = ModuleLoader::new(search_path);
+
+    loader.checking = true;
+
+    let mut visitor = Visitor::new(source, &ast);
+
+    visitor.trace = Some(Trace::default());
+
+    let modules = match last {
+      Some((_, ref resume)) => Ok(resume.modules.clone()),
+      None                  => loader.load_imports(&mut vm, &ast, source, ""),
+    };
+
+    let mut hoisted = Vec::new();
+    let mut before  = None;
+    let mut after   = None;
+
+    let checked = modules.and_then(|modules| {
+      visitor.imports = modules;
+      visitor.declare_functions()?;
+
+      hoisted = typed_names(&visitor.tabs[0]);
+
+      let mut start = 0;
+      let mut rest  = None;
+
+      if let Some((old, resume)) = last {
+        let lines = |chunks: &[Vec<String>]| chunks.iter().map(|chunk| chunk.len()).sum::<usize>();
+
+        let first = lines(&resume.chunks[.. resume.region.start]) + 1;
+
+        let moved = Moved {
+          start: first,
+          end:   first + lines(&resume.chunks[resume.region.clone()]),
+          delta: lines(&chunks[region.clone()]) as isize - lines(&resume.chunks[resume.region.clone()]) as isize,
+        };
+
+        if resume.hoisted == hoisted {
+          if let Some(suffix) = Self::pick_up(&mut visitor, &ast[.. bounds[region.start]], old, resume, moved) {
+            start = region.start;
+            rest  = suffix
+          }
+        }
+      }
+
+      for chunk in start ..= chunks.len() {
+        if chunk == region.start {
+          before = Some((visitor.tabs[0].clone(), visitor.tab_frames.len()))
+        }
+
+        if chunk == region.end {
+          after = Some((visitor.tabs[0].clone(), visitor.tab_frames.len()));
+
+          if let Some(rest) = rest.take() {
+            if Self::reuse(&mut visitor, &ast[bounds[chunk] ..], rest).is_some() {
+              break
+            }
+          }
+        }
+
+        if chunk < chunks.len() {
+          for statement in &ast[bounds[chunk] .. bounds[chunk + 1]] {
+            visitor.visit_statement(statement)?
+          }
+        }
+      }
+
+      visitor.close();
+
+      Ok(())
+    });
+
+    let globals = visitor.tabs[0].clone();
+    let symbols = Self::symbols(&ast, source, &globals);
+    let trace   = visitor.trace.take().unwrap_or_default();
+
+    let mut names = trace.types.clone();
+
+    names.extend(visitor.declared_types());
+
+    let resume = before.map(|before| Resume {
+      chunks,
+      region,
+      before,
+      after: if checked.is_ok() { after } else { None },
+      hoisted,
+      imports,
+      modules: visitor.imports.clone(),
+
+      diagnostics: Vec::new(),
+    });
+
+    *index = Some(
+      Index {
+        symbols,
+        names,
+        trace,
+        frames: mem::take(&mut visitor.tab_frames),
+        globals,
+        resume,
+      }
+    );
+
+    checked
+  }
+
+  // puts the visitor where the last check was at the start of its region, with what it learned before it,
+  // giving what it learned after the region unless that has to be checked again anyway
+  fn pick_up(visitor: &mut Visitor, prefix: &[Statement], old: Index, resume: Resume, moved: Moved) -> Option<Option<Rest>> {
+    let ((symtab, typetab), frames) = resume.before;
+
+    // functions were declared up front, the ones past the start of the region may have moved since
+    let hoisted  = visitor.tabs[0].0.locations.borrow().clone();
+    let declared = symtab.locations.borrow().iter()
+      .filter(|&(_, location)| location.0 >= moved.start)
+      .map(|(name, location)| (*location, name.clone()))
+      .collect::<HashMap<Location, String>>();
+
+    let mut locations = symtab.locations.borrow().clone();
+
+    for (name, location) in locations.iter_mut() {
+      if location.0 >= moved.start {
+        *location = *hoisted.get(name)?
+      }
+    }
+
+    let mut definitions = Vec::new();
+
+    for (&used, &declared_at) in old.trace.definitions.iter().filter(|&(used, _)| used.0 < moved.start) {
+      definitions.push((used, moved.relocate(declared_at, &declared, &hoisted)?))
+    }
+
+    *symtab.locations.borrow_mut() = locations;
+
+    visitor.tabs[0] = (symtab, typetab);
+
+    // what went wrong before the region isn't found again, as it isn't checked again
+    let (earlier, pending): (Vec<Diagnostic>, Vec<Diagnostic>) = resume.diagnostics.into_iter()
+      .partition(|diagnostic| diagnostic.location.is_none_or(|(line, _)| line < moved.start));
+
+    for diagnostic in earlier {
+      error::push(diagnostic)
+    }
+
+    if let Some(trace) = visitor.trace.as_mut() {
+      trace.types.extend(old.trace.types.iter().filter(|&(location, _)| location.0 < moved.start).map(|(location, t)| (*location, t.clone())));
+      trace.definitions.extend(definitions);
+      trace.scopes.extend(old.trace.scopes.iter().filter(|&&(_, frame)| frame < frames).cloned());
+
+      for statement in prefix {
+        if let Some((line, _)) = visitor.source.locate(statement.pos) {
+          trace.top_level(line)
+        }
+      }
+    }
+
+    let Index { trace, frames: mut later, globals, .. } = old;
+
+    visitor.tab_frames = later.drain(.. frames).collect();
+
+    Some(resume.after.map(|(after, closed)| {
+      let mut frames = later.split_off(closed - visitor.tab_frames.len());
+
+      frames.pop(); // the outermost scope, closed last
+
+      Rest {
+        trace,
+        globals,
+        frames,
+        closed,
+        after,
+        moved,
+        diagnostics: pending,
+      }
+    }))
+  }
+
+  // takes what the last check learned after its region as checked, when the region leaves the globals as they were
+  fn reuse(visitor: &mut Visitor, suffix: &[Statement], rest: Rest) -> Option<()> {
+    let Rest { trace: old, globals: (symtab, typetab), frames, closed: old_closed, after, moved, diagnostics } = rest;
+
+    if typed_names(&visitor.tabs[0]) != typed_names(&after) {
+      return None
+    }
+
+    // names declared in the region are found where they're declared now
+    let now      = visitor.tabs[0].0.locations.borrow().clone();
+    let declared = after.0.locations.borrow().iter()
+      .filter(|&(_, location)| moved.location(*location).is_none())
+      .map(|(name, location)| (*location, name.clone()))
+      .collect::<HashMap<Location, String>>();
+
+    let mut types       = Vec::new();
+    let mut definitions = Vec::new();
+
+    for (&location, t) in old.types.iter().filter(|&(location, _)| location.0 >= moved.end) {
+      types.push((moved.location(location)?, t.clone()))
+    }
+
+    for (&used, &declared_at) in old.definitions.iter().filter(|&(used, _)| used.0 >= moved.end) {
+      definitions.push((moved.location(used)?, moved.relocate(declared_at, &declared, &now)?))
+    }
+
+    let mut locations = HashMap::new();
+
+    for (name, &location) in symtab.locations.borrow().iter() {
+      locations.insert(name.clone(), moved.relocate(location, &declared, &now)?);
+    }
+
+    *symtab.locations.borrow_mut() = locations;
+
+    for &(ref frame, _) in frames.iter() {
+      let mut locations = frame.locations.borrow_mut();
+
+      for location in locations.values_mut() {
+        *location = moved.location(*location)?
+      }
+    }
+
+    let closed = visitor.tab_frames.len();
+
+    let scopes = old.scopes.iter()
+      .filter(|&&(_, frame)| frame >= old_closed && frame < old_closed + frames.len())
+      .map(|&((first, last), frame)| Some(((moved.line(first)?, moved.line(last)?), frame - old_closed + closed)))
+      .collect::<Option<Vec<((usize, usize), usize)>>>()?;
+
+    let diagnostics = diagnostics.into_iter()
+      .filter(|diagnostic| diagnostic.location.is_some_and(|(line, _)| line >= moved.end))
+      .map(|mut diagnostic| diagnostic.location.and_then(|location| moved.location(location)).map(|location| {
+        diagnostic.location = Some(location);
+        diagnostic
+      }))
+      .collect::<Option<Vec<Diagnostic>>>()?;
+
+    for diagnostic in diagnostics {
+      error::push(diagnostic)
+    }
+
+    visitor.tabs[0] = (symtab, typetab);
+    visitor.tab_frames.extend(frames);
+
+    if let Some(trace) = visitor.trace.as_mut() {
+      trace.types.extend(types);
+      trace.definitions.extend(definitions);
+      trace.scopes.extend(scopes);
+
+      for statement in suffix {
+        if let Some((line, _)) = visitor.source.locate(statement.pos) {
+          trace.top_level(line)
+        }
+      }
+    }
+
+    Some(())
+  }
+
+  fn symbols(ast: &[Statement], source: &Source, globals: &(SymTab, TypeTab)) -> Vec<Symbol> {
+    let mut symbols: Vec<Symbol> = Vec::new();
+
+    for statement in ast {
+      let declaration = match statement.node {
+        StatementNode::Export(ref declaration) => &**declaration,
+        _                                      => statement,
+      };
+
+      let left = match declaration.node {
+        StatementNode::Variable(_, ref left, _) | StatementNode::Constant(_, ref left, _) => left,
+        _                                                                                 => continue,
+      };
+
+      let names = match left.node {
+        ExpressionNode::Set(ref names) => names.iter().collect(),
+        _                              => vec!(left),
+      };
+
+      for name in names {
+        if let (&ExpressionNode::Identifier(ref name), Some(location)) = (&name.node, source.locate(name.pos)) {
+          if symbols.iter().any(|symbol| symbol.name == *name) {
+            continue
+          }
+
+          let t = globals.0.names.borrow().get(name).and_then(|&index| globals.1.types.borrow().get(index).cloned());
+
+          symbols.push(
+            Symbol {
+              name: name.clone(),
+              location,
+              t,
+            }
+          )
+        }
+      }
+    }
+
+    symbols
+  }
+}
+
+
+
+// what the last check learned after the region it was picking up from
+struct Rest {
+  trace:   Trace,
+  globals: (SymTab, TypeTab),
+  frames:  Vec<(SymTab, TypeTab)>, // closed after the region, the outermost scope left out
+  closed:  usize,                  // frames closed before them
+  after:   (SymTab, TypeTab),      // the globals at the end of the region
+  moved:   Moved,
+
+  diagnostics: Vec<Diagnostic>, // what went wrong from the region on
+}
+
+// where lines of the last version of a document are in this one, lines of the region it was checked again around
+// are nowhere, counting from 1
+#[derive(Clone, Copy)]
+struct Moved {
+  start: usize,
+  end:   usize,
+  delta: isize,
+}
+
+impl Moved {
+  fn line(&self, line: usize) -> Option<usize> {
+    if line < self.start {
+      Some(line)
+    } else if line >= self.end {
+      Some((line as isize + self.delta) as usize)
+    } else {
+      None
+    }
+  }
+
+  fn location(&self, (line, slice): Location) -> Option<Location> {
+    self.line(line).map(|line| (line, slice))
+  }
+
+  // a declaration of the region is found by its name, in where names are declared now
+  fn relocate(
+    &self,
+    location: Location,
+    declared: &HashMap<Location, String>,
+    now:      &HashMap<String, Location>
+  ) -> Option<Location> {
+    self.location(location).or_else(|| declared.get(&location).and_then(|name| now.get(name)).cloned())
+  }
+}
+
+// the names of a scope with their types, in order
+fn typed_names(&(ref symtab, ref typetab): &(SymTab, TypeTab)) -> Vec<(String, Option<Type>)> {
+  let types = typetab.types.borrow();
+
+  let mut names = symtab.names.borrow().iter()
+    .map(|(name, &index)| (name.clone(), types.get(index).cloned()))
+    .collect::<Vec<(String, Option<Type>)>>();
+
+  names.sort_by(|a, b| a.0.cmp(&b.0));
+  names
+}
+
+// how many chunks two versions of a document share at the start, and then at the end
+fn shared(old: &[Vec<String>], new: &[Vec<String>]) -> (usize, usize) {
+  let front = old.iter().zip(new).take_while(|&(a, b)| a == b).count();
+  let back  = old[front ..].iter().rev().zip(new[front ..].iter().rev()).take_while(|&(a, b)| a == b).count();
+
+  (front, back)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEXT: &str = "limit := 10\n-- doubles what it's given\ndouble := fun(x: int) -> int {\n  y := x * 2\n  y\n}\n\ntwice := fun(x: int) -> int {\n  double(double(x))\n}\n\ntotal := twice(limit)\nfor i in [1, 2, 3] {\n  j := i + total\n  println(j)\n}\nlast := total + double(1)\n";
+
+  // what an analysis tells an editor, in an order that doesn't depend on how it was found
+  fn seen(analysis: &Analysis, lines: usize) -> Vec<String> {
+    let mut seen = analysis.diagnostics.iter().map(|diagnostic| format!("{:?} {} {:?}", diagnostic.location, diagnostic.severity, diagnostic.message)).collect::<Vec<String>>();
+
+    if let Some(ref index) = analysis.index {
+      seen.extend(index.trace.types.iter().map(|(location, t)| format!("type {:?} {:?}", location, t)));
+      seen.extend(index.trace.definitions.iter().map(|(used, declared)| format!("definition {:?} {:?}", used, declared)));
+      seen.extend(index.names.iter().map(|(location, t)| format!("name {:?} {:?}", location, t)));
+      seen.extend(index.symbols.iter().map(|symbol| format!("symbol {} {:?} {:?}", symbol.name, symbol.location, symbol.t)));
+      seen.extend((1 ..= lines).map(|line| format!("scope {} {:?}", line, index.names_at(line))));
+    } else {
+      seen.push("no index".to_string())
+    }
+
+    seen.sort();
+    seen
+  }
+
+  #[test]
+  fn edits_check_like_the_whole_file() {
+    let mut document = Document::new("<test>", TEXT);
+    let mut index    = Analysis::new(&document, Vec::new(), None).index;
+
+    let edits = [
+      ((3, 11), (3, 12), "3"),                    // in a function, left as it was typed
+      ((4, 0), (4, 0), "  z := y\n"),             // a line more in it
+      ((3, 11), (3, 12), "4"),                    // and again in the same place
+      ((0, 9), (0, 11), "\"ten\""),               // a global changing its type, breaking what uses it
+      ((0, 9), (0, 14), "10"),
+      ((0, 9), (0, 11), "11"),                    // the same type, so what comes after is taken as it was
+      ((0, 9), (0, 11), "1.5"),                   // not so for another
+      ((0, 9), (0, 12), "12"),
+      ((14, 7), (14, 8), "i * 2"),                // in a loop
+      ((17, 0), (17, 4), "first"),                // the last statement
+      ((2, 0), (2, 6), "halve"),                  // a function going away
+    ];
+
+    for &(start, end, text) in edits.iter() {
+      document.edit(start, end, text);
+
+      let lines = document.lines().len();
+      let fresh = Analysis::new(&document, Vec::new(), None);
+      let again = Analysis::new(&document, Vec::new(), index.take());
+
+      assert_eq!(seen(&again, lines), seen(&fresh, lines), "{}", document.text());
+
+      index = again.index
+    }
+  }
+}
@@ -0,0 +1,294 @@
+use std::fmt::{ self, Display, Formatter };
+use std::iter::Peekable;
+use std::str::Chars;
+
+
+
+// just enough JSON for the messages of the language server protocol
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  Str(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+static NULL: Json = Json::Null;
+
+impl Json {
+  pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+  }
+
+  pub fn str(text: &str) -> Json {
+    Json::Str(text.to_string())
+  }
+
+  // the field of an object, null when there's none
+  pub fn get(&self, key: &str) -> &Json {
+    match *self {
+      Json::Object(ref fields) => fields.iter().find(|&&(ref name, _)| name == key).map_or(&NULL, |&(_, ref value)| value),
+      _                        => &NULL,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match *self {
+      Json::Str(ref text) => Some(text),
+      _                   => None,
+    }
+  }
+
+  pub fn as_usize(&self) -> Option<usize> {
+    match *self {
+      Json::Number(n) if n >= 0.0 => Some(n as usize),
+      _                           => None,
+    }
+  }
+
+  pub fn as_array(&self) -> &[Json] {
+    match *self {
+      Json::Array(ref elements) => elements,
+      _                         => &[],
+    }
+  }
+
+  pub fn is_null(&self) -> bool {
+    *self == Json::Null
+  }
+
+
+
+  pub fn parse(text: &str) -> Result<Json, String> {
+    let mut chars = text.chars().peekable();
+    let value     = parse_value(&mut chars)?;
+
+    skip_whitespace(&mut chars);
+
+    match chars.next() {
+      Some(c) => Err(format!("unexpected `{}` after value", c)),
+      None    => Ok(value),
+    }
+  }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+  while chars.peek().is_some_and(|c| c.is_whitespace()) {
+    chars.next();
+  }
+}
+
+fn expect_word(chars: &mut Peekable<Chars>, word: &str, value: Json) -> Result<Json, String> {
+  for expected in word.chars() {
+    if chars.next() != Some(expected) {
+      return Err(format!("expected `{}`", word))
+    }
+  }
+
+  Ok(value)
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, String> {
+  skip_whitespace(chars);
+
+  match chars.peek().cloned() {
+    Some('n') => expect_word(chars, "null", Json::Null),
+    Some('t') => expect_word(chars, "true", Json::Bool(true)),
+    Some('f') => expect_word(chars, "false", Json::Bool(false)),
+    Some('"') => Ok(Json::Str(parse_string(chars)?)),
+
+    Some('[') => {
+      chars.next();
+
+      let mut elements = Vec::new();
+
+      skip_whitespace(chars);
+
+      if chars.peek() == Some(&']') {
+        chars.next();
+
+        return Ok(Json::Array(elements))
+      }
+
+      loop {
+        elements.push(parse_value(chars)?);
+
+        skip_whitespace(chars);
+
+        match chars.next() {
+          Some(',') => continue,
+          Some(']') => return Ok(Json::Array(elements)),
+          _         => return Err("expected `,` or `]` in array".to_string()),
+        }
+      }
+    },
+
+    Some('{') => {
+      chars.next();
+
+      let mut fields = Vec::new();
+
+      skip_whitespace(chars);
+
+      if chars.peek() == Some(&'}') {
+        chars.next();
+
+        return Ok(Json::Object(fields))
+      }
+
+      loop {
+        skip_whitespace(chars);
+
+        let key = parse_string(chars)?;
+
+        skip_whitespace(chars);
+
+        if chars.next() != Some(':') {
+          return Err("expected `:` after key".to_string())
+        }
+
+        fields.push((key, parse_value(chars)?));
+
+        skip_whitespace(chars);
+
+        match chars.next() {
+          Some(',') => continue,
+          Some('}') => return Ok(Json::Object(fields)),
+          _         => return Err("expected `,` or `}` in object".to_string()),
+        }
+      }
+    },
+
+    Some(c) if c == '-' || c.is_ascii_digit() => {
+      let mut number = String::new();
+
+      while let Some(c) = chars.peek().cloned() {
+        if c.is_ascii_digit() || "+-.eE".contains(c) {
+          number.push(c);
+          chars.next();
+        } else {
+          break
+        }
+      }
+
+      number.parse::<f64>().map(Json::Number).map_err(|_| format!("malformed number `{}`", number))
+    },
+
+    Some(c) => Err(format!("unexpected `{}`", c)),
+    None    => Err("unexpected end of input".to_string()),
+  }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+  if chars.next() != Some('"') {
+    return Err("expected string".to_string())
+  }
+
+  let mut text = String::new();
+
+  loop {
+    match chars.next() {
+      Some('"')  => return Ok(text),
+      Some('\\') => match chars.next() {
+        Some('n') => text.push('\n'),
+        Some('t') => text.push('\t'),
+        Some('r') => text.push('\r'),
+        Some('b') => text.push('\u{8}'),
+        Some('f') => text.push('\u{c}'),
+        Some('u') => {
+          let unit = parse_hex(chars)?;
+
+          // characters outside the basic plane come as a pair of surrogates
+          let c = if (0xD800 .. 0xDC00).contains(&unit) && chars.peek() == Some(&'\\') {
+            chars.next();
+
+            if chars.next() != Some('u') {
+              return Err("expected low surrogate".to_string())
+            }
+
+            let low = parse_hex(chars)?;
+
+            ::std::char::from_u32(0x10000 + ((unit - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF))
+          } else {
+            ::std::char::from_u32(unit)
+          };
+
+          text.push(c.unwrap_or('\u{FFFD}'))
+        },
+
+        Some(c) => text.push(c),
+        None    => return Err("unterminated string".to_string()),
+      },
+
+      Some(c) => text.push(c),
+      None    => return Err("unterminated string".to_string()),
+    }
+  }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Result<u32, String> {
+  let digits = chars.by_ref().take(4).collect::<String>();
+
+  u32::from_str_radix(&digits, 16).map_err(|_| format!("malformed escape `\\u{}`", digits))
+}
+
+
+
+impl Display for Json {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match *self {
+      Json::Null                          => write!(f, "null"),
+      Json::Bool(b)                       => write!(f, "{}", b),
+      Json::Number(n) if n == n.trunc()   => write!(f, "{}", n as i64),
+      Json::Number(n)                     => write!(f, "{}", n),
+
+      Json::Str(ref text) => {
+        write!(f, "\"")?;
+
+        for c in text.chars() {
+          match c {
+            '"'                => write!(f, "\\\"")?,
+            '\\'               => write!(f, "\\\\")?,
+            '\n'               => write!(f, "\\n")?,
+            '\r'               => write!(f, "\\r")?,
+            '\t'               => write!(f, "\\t")?,
+            c if c < ' '       => write!(f, "\\u{:04x}", c as u32)?,
+            c                  => write!(f, "{}", c)?,
+          }
+        }
+
+        write!(f, "\"")
+      },
+
+      Json::Array(ref elements) => {
+        write!(f, "[")?;
+
+        for (i, element) in elements.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?
+          }
+
+          write!(f, "{}", element)?
+        }
+
+        write!(f, "]")
+      },
+
+      Json::Object(ref fields) => {
+        write!(f, "{{")?;
+
+        for (i, &(ref key, ref value)) in fields.iter().enumerate() {
+          if i > 0 {
+            write!(f, ",")?
+          }
+
+          write!(f, "{}:{}", Json::Str(key.clone()), value)?
+        }
+
+        write!(f, "}}")
+      },
+    }
+  }
+}
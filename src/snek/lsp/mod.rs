@@ -0,0 +1,5 @@
+pub mod json;
+pub mod analysis;
+pub mod server;
+
+pub use self::server::*;
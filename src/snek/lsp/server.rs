@@ -0,0 +1,638 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::io::{ BufRead, Write };
+
+use super::json::Json;
+use super::analysis::*;
+use super::super::lexer::{ Source, Location };
+use super::super::syntax::{ highlight, Class };
+use super::super::syntax::incremental;
+use super::super::error::{ self, Diagnostic };
+
+
+
+// the kinds of semantic tokens, in the order the protocol numbers them
+const TOKEN_TYPES: &[&str] = &["keyword", "number", "string", "operator", "type", "function", "variable", "comment"];
+
+fn token_type(class: Class) -> Option<usize> {
+  let name = match class {
+    Class::Keyword | Class::Bool => "keyword",
+    Class::Number                => "number",
+    Class::Str | Class::Char     => "string",
+    Class::Operator              => "operator",
+    Class::Type                  => "type",
+    Class::Function              => "function",
+    Class::Value                 => "variable",
+    Class::Comment               => "comment",
+    Class::Symbol                => return None,
+  };
+
+  TOKEN_TYPES.iter().position(|token_type| *token_type == name)
+}
+
+
+
+struct Document {
+  path:  String,
+  text:  incremental::Document, // parsed again a top-level statement at a time as it's edited
+  index: Option<Index>,         // from the last version that parsed
+}
+
+impl Document {
+  fn lines(&self) -> Vec<&str> {
+    self.text.lines()
+  }
+}
+
+
+
+// speaks the language server protocol, a message at a time
+pub struct Server {
+  search_path: Vec<PathBuf>,
+  documents:   HashMap<String, Document>,
+  shutdown:    bool,
+}
+
+impl Server {
+  pub fn new(search_path: Vec<PathBuf>) -> Self {
+    Server {
+      search_path,
+      documents: HashMap::new(),
+      shutdown:  false,
+    }
+  }
+
+  // serves until `exit`, giving the code to exit with
+  pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> i32 {
+    // anything reported outside of an analysis would end up in the middle of the protocol
+    let (code, _) = error::collect(|| {
+      loop {
+        let message = match read_message(&mut input) {
+          Some(Ok(message)) => message,
+          Some(Err(error))  => {
+            send(&mut output, &Json::object(vec!(
+              ("jsonrpc", Json::str("2.0")),
+              ("id",      Json::Null),
+              ("error",   Json::object(vec!(("code", Json::Number(-32700.0)), ("message", Json::Str(error))))),
+            )));
+
+            continue
+          },
+
+          None => return 1,
+        };
+
+        if let Some(code) = self.handle(&message, &mut output) {
+          return code
+        }
+      }
+    });
+
+    code
+  }
+
+  fn handle<W: Write>(&mut self, message: &Json, output: &mut W) -> Option<i32> {
+    let id     = message.get("id");
+    let params = message.get("params");
+    let method = message.get("method").as_str().unwrap_or("");
+
+    let result = match method {
+      "initialize" => Ok(Json::object(vec!(
+        ("capabilities", Json::object(vec!(
+          ("textDocumentSync", Json::object(vec!(
+            ("openClose", Json::Bool(true)),
+            ("change",    Json::Number(2.0)), // incremental
+          ))),
+          ("hoverProvider",          Json::Bool(true)),
+          ("definitionProvider",     Json::Bool(true)),
+          ("documentSymbolProvider", Json::Bool(true)),
+          ("completionProvider",     Json::object(vec!(("triggerCharacters", Json::Array(vec!(Json::str("."))))))),
+          ("semanticTokensProvider", Json::object(vec!(
+            ("legend", Json::object(vec!(
+              ("tokenTypes",     Json::Array(TOKEN_TYPES.iter().map(|name| Json::str(name)).collect())),
+              ("tokenModifiers", Json::Array(Vec::new())),
+            ))),
+            ("full", Json::Bool(true)),
+          ))),
+        ))),
+        ("serverInfo", Json::object(vec!(("name", Json::str("snek"))))),
+      ))),
+
+      "shutdown" => {
+        self.shutdown = true;
+
+        Ok(Json::Null)
+      },
+
+      "exit" => return Some(if self.shutdown { 0 } else { 1 }),
+
+      "textDocument/didOpen" => {
+        let document = params.get("textDocument");
+
+        if let (Some(uri), Some(text)) = (document.get("uri").as_str(), document.get("text").as_str()) {
+          let path = path_of(uri);
+
+          self.documents.insert(
+            uri.to_string(),
+            Document {
+              text:  incremental::Document::new(&path, text),
+              path,
+              index: None,
+            }
+          );
+
+          self.analyze(uri, output)
+        }
+
+        return None
+      },
+
+      "textDocument/didChange" => {
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or("");
+
+        if let Some(document) = self.documents.get_mut(uri) {
+          for change in params.get("contentChanges").as_array() {
+            let text = change.get("text").as_str().unwrap_or("");
+
+            if change.get("range").is_null() {
+              document.text = incremental::Document::new(&document.path, text);
+
+              continue
+            }
+
+            let start = position(&document.lines(), change.get("range").get("start"));
+            let end   = position(&document.lines(), change.get("range").get("end"));
+
+            document.text.edit(start, end, text);
+          }
+        }
+
+        self.analyze(uri, output);
+
+        return None
+      },
+
+      "textDocument/didClose" => {
+        if let Some(uri) = params.get("textDocument").get("uri").as_str() {
+          self.documents.remove(uri);
+
+          publish(output, uri, Vec::new())
+        }
+
+        return None
+      },
+
+      "textDocument/hover"          => Ok(self.hover(params)),
+      "textDocument/definition"     => Ok(self.definition(params)),
+      "textDocument/documentSymbol" => Ok(self.symbols(params)),
+      "textDocument/completion"     => Ok(self.completion(params)),
+
+      "textDocument/semanticTokens/full" => Ok(self.semantic_tokens(params)),
+
+      _ => Err((-32601.0, format!("unknown method `{}`", method))),
+    };
+
+    // notifications don't get answers
+    if message.get("id").is_null() {
+      return None
+    }
+
+    let result = if self.shutdown && method != "shutdown" {
+      Err((-32600.0, "the server is shutting down".to_string()))
+    } else {
+      result
+    };
+
+    let (key, value) = match result {
+      Ok(result)             => ("result", result),
+      Err((code, message))   => ("error", Json::object(vec!(("code", Json::Number(code)), ("message", Json::Str(message))))),
+    };
+
+    send(output, &Json::object(vec!(
+      ("jsonrpc", Json::str("2.0")),
+      ("id",      id.clone()),
+      (key,       value),
+    )));
+
+    None
+  }
+
+
+
+  // checks a document again, keeping what's known about it if it no longer parses,
+  // which its chunks already tell without going over the whole of it
+  fn analyze<W: Write>(&mut self, uri: &str, output: &mut W) {
+    let document = match self.documents.get_mut(uri) {
+      Some(document) => document,
+      None           => return,
+    };
+
+    // checking picks up from the last version's index, so when the checker crashes there's none left until it gets through again
+    let diagnostics = if document.text.parses() {
+      let analysis = Analysis::new(&document.text, self.search_path.clone(), document.index.take());
+
+      document.index = analysis.index;
+
+      analysis.diagnostics
+    } else {
+      document.text.diagnostics()
+    };
+
+    let lines       = document.lines();
+    let diagnostics = diagnostics.iter().map(|diagnostic| diagnostic_json(diagnostic, &document.path, &lines)).collect();
+
+    publish(output, uri, diagnostics)
+  }
+
+  // the document at `textDocument`, and the line and column of `position` as counted by the checker
+  fn cursor(&self, params: &Json) -> Option<(&Document, usize, usize)> {
+    let document = self.documents.get(params.get("textDocument").get("uri").as_str()?)?;
+    let position = params.get("position");
+
+    let line   = position.get("line").as_usize()?;
+    let column = column(document.lines().get(line).cloned().unwrap_or(""), position.get("character").as_usize()?);
+
+    Some((document, line + 1, column + 1))
+  }
+
+  fn hover(&self, params: &Json) -> Json {
+    let (document, line, column) = match self.cursor(params) {
+      Some(cursor) => cursor,
+      None         => return Json::Null,
+    };
+
+    let (location, t) = match document.index.as_ref().and_then(|index| index.type_at(line, column)) {
+      Some(found) => found,
+      None        => return Json::Null,
+    };
+
+    let lines = document.lines();
+    let text  = lines.get(location.0 - 1)
+      .map(|line| line.chars().skip(location.1 .0 - 1).take(location.1 .1 + 1 - location.1 .0).collect::<String>())
+      .unwrap_or_default();
+
+    // names are shown along with their type
+    let shown = if !text.is_empty() && text.chars().all(|c| c.is_alphanumeric() || "_.!?".contains(c)) {
+      format!("{}: {}", text, t)
+    } else {
+      format!("{}", t)
+    };
+
+    Json::object(vec!(
+      ("contents", Json::object(vec!(
+        ("kind",  Json::str("markdown")),
+        ("value", Json::Str(format!("```snek\n{}\n```", shown))),
+      ))),
+      ("range", range(location, &lines)),
+    ))
+  }
+
+  fn definition(&self, params: &Json) -> Json {
+    let (document, line, column) = match self.cursor(params) {
+      Some(cursor) => cursor,
+      None         => return Json::Null,
+    };
+
+    match document.index.as_ref().and_then(|index| index.definition_at(line, column)) {
+      Some(declared) => Json::object(vec!(
+        ("uri",   params.get("textDocument").get("uri").clone()),
+        ("range", range(declared, &document.lines())),
+      )),
+
+      None => Json::Null,
+    }
+  }
+
+  fn symbols(&self, params: &Json) -> Json {
+    let document = match params.get("textDocument").get("uri").as_str().and_then(|uri| self.documents.get(uri)) {
+      Some(document) => document,
+      None           => return Json::Null,
+    };
+
+    let lines   = document.lines();
+    let symbols = document.index.iter().flat_map(|index| index.symbols.iter()).map(|symbol| {
+      let kind = match symbol.t {
+        Some(ref t) if t.node.is_function() => 12.0,
+        Some(ref t) if is_constant(t)       => 14.0,
+        _                                   => 13.0,
+      };
+
+      Json::object(vec!(
+        ("name",           Json::Str(symbol.name.clone())),
+        ("detail",         Json::Str(symbol.t.as_ref().map(|t| t.to_string()).unwrap_or_default())),
+        ("kind",           Json::Number(kind)),
+        ("range",          range(symbol.location, &lines)),
+        ("selectionRange", range(symbol.location, &lines)),
+      ))
+    }).collect();
+
+    Json::Array(symbols)
+  }
+
+  fn completion(&self, params: &Json) -> Json {
+    let (document, line, _) = match self.cursor(params) {
+      Some(cursor) => cursor,
+      None         => return Json::Null,
+    };
+
+    let names = document.index.as_ref().map(|index| index.names_at(line)).unwrap_or_default();
+
+    let items = names.into_iter().map(|(name, t)| {
+      let kind = match t {
+        Some(ref t) if t.node.is_function() => 3.0,
+        Some(ref t) if is_constant(t)       => 21.0,
+        _                                   => 6.0,
+      };
+
+      Json::object(vec!(
+        ("label",  Json::Str(name)),
+        ("kind",   Json::Number(kind)),
+        ("detail", Json::Str(t.map(|t| t.to_string()).unwrap_or_default())),
+      ))
+    }).collect();
+
+    Json::Array(items)
+  }
+
+  // classified from the text as it is now, with what's known of its names from the last version that parsed
+  fn semantic_tokens(&self, params: &Json) -> Json {
+    let (uri, document) = match params.get("textDocument").get("uri").as_str().and_then(|uri| self.documents.get(uri).map(|document| (uri, document))) {
+      Some(found) => found,
+      None        => return Json::Null,
+    };
+
+    let source     = Source::from_text(uri, document.text.text());
+    let names      = document.index.as_ref().map(|index| index.names.clone()).unwrap_or_default();
+    let highlights = highlight(&source, &names);
+
+    let lines = document.lines();
+
+    let mut data     = Vec::new();
+    let mut previous = (0, 0);
+
+    // each token is relative to the one before it
+    for highlight in highlights {
+      let (line, (start, end)) = highlight.location;
+
+      let token_type = match token_type(highlight.class) {
+        Some(token_type) => token_type,
+        None             => continue,
+      };
+
+      let text  = lines.get(line - 1).cloned().unwrap_or("");
+      let line  = line - 1;
+      let start = units(text, start - 1);
+      let end   = units(text, end);
+
+      let delta = if line == previous.0 { start - previous.1 } else { start };
+
+      data.extend(
+        [line - previous.0, delta, end - start, token_type, 0].iter().map(|&n| Json::Number(n as f64))
+      );
+
+      previous = (line, start)
+    }
+
+    Json::object(vec!(("data", Json::Array(data))))
+  }
+}
+
+
+
+fn read_message<R: BufRead>(input: &mut R) -> Option<Result<Json, String>> {
+  let mut length = None;
+
+  loop {
+    let mut header = String::new();
+
+    if input.read_line(&mut header).ok()? == 0 {
+      return None
+    }
+
+    let header = header.trim();
+
+    if header.is_empty() {
+      break
+    }
+
+    let mut parts = header.splitn(2, ':');
+
+    if parts.next().is_some_and(|name| name.eq_ignore_ascii_case("content-length")) {
+      length = parts.next().and_then(|value| value.trim().parse::<usize>().ok())
+    }
+  }
+
+  let length = match length {
+    Some(length) => length,
+    None         => return Some(Err("missing `Content-Length` header".to_string())),
+  };
+
+  let mut body = vec!(0; length);
+
+  input.read_exact(&mut body).ok()?;
+
+  Some(String::from_utf8(body).map_err(|_| "message isn't UTF-8".to_string()).and_then(|body| Json::parse(&body)))
+}
+
+fn send<W: Write>(output: &mut W, message: &Json) {
+  let body = message.to_string();
+
+  // a client that went away will be noticed when reading
+  let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body).and_then(|_| output.flush());
+}
+
+fn publish<W: Write>(output: &mut W, uri: &str, diagnostics: Vec<Json>) {
+  send(output, &Json::object(vec!(
+    ("jsonrpc", Json::str("2.0")),
+    ("method",  Json::str("textDocument/publishDiagnostics")),
+    ("params",  Json::object(vec!(
+      ("uri",         Json::str(uri)),
+      ("diagnostics", Json::Array(diagnostics)),
+    ))),
+  )))
+}
+
+// problems in imported files are shown at the top of the one importing them
+fn diagnostic_json(diagnostic: &Diagnostic, path: &str, lines: &[&str]) -> Json {
+  let (location, message) = match (&diagnostic.file, diagnostic.location) {
+    (&Some(ref file), _) if file != path => ((1, (1, 0)), format!("{}: {}", file, diagnostic.message)),
+    (_, Some(location))                  => (location, diagnostic.message.clone()),
+    (_, None)                            => ((1, (1, 0)), diagnostic.message.clone()),
+  };
+
+  Json::object(vec!(
+    ("range",    range(location, lines)),
+    ("severity", Json::Number(diagnostic.severity.max(1) as f64)),
+    ("source",   Json::str("snek")),
+    ("message",  Json::Str(message)),
+  ))
+}
+
+
+
+// file paths are what the checker knows documents by, e.g. to find their imports
+fn path_of(uri: &str) -> String {
+  let encoded = match uri.strip_prefix("file://") {
+    Some(path) => path.as_bytes(),
+    None       => return uri.to_string(),
+  };
+
+  let mut bytes = Vec::new();
+  let mut i     = 0;
+
+  while i < encoded.len() {
+    let escaped = if encoded[i] == b'%' && i + 2 < encoded.len() {
+      ::std::str::from_utf8(&encoded[i + 1 .. i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+    } else {
+      None
+    };
+
+    match escaped {
+      Some(byte) => {
+        bytes.push(byte);
+        i += 3
+      },
+
+      None => {
+        bytes.push(encoded[i]);
+        i += 1
+      },
+    }
+  }
+
+  String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// positions of the protocol count UTF-16 units from 0, the checker counts chars from 1
+fn units(line: &str, chars: usize) -> usize {
+  line.chars().take(chars).map(char::len_utf16).sum()
+}
+
+fn column(line: &str, units: usize) -> usize {
+  let mut counted = 0;
+
+  for (i, c) in line.chars().enumerate() {
+    if counted >= units {
+      return i
+    }
+
+    counted += c.len_utf16()
+  }
+
+  line.chars().count()
+}
+
+fn range(location: Location, lines: &[&str]) -> Json {
+  let (line, (start, end)) = location;
+
+  let line = line.max(1).min(lines.len().max(1)) - 1;
+  let text = lines.get(line).cloned().unwrap_or("");
+
+  let position = |chars: usize| Json::object(vec!(
+    ("line",      Json::Number(line as f64)),
+    ("character", Json::Number(units(text, chars) as f64)),
+  ));
+
+  Json::object(vec!(
+    ("start", position(start.saturating_sub(1))),
+    ("end",   position(end.max(start.saturating_sub(1)))),
+  ))
+}
+
+// the line and char of a protocol position, both from 0
+fn position(lines: &[&str], position: &Json) -> (usize, usize) {
+  let line  = position.get("line").as_usize().unwrap_or(0);
+  let units = position.get("character").as_usize().unwrap_or(0);
+
+  (line, column(lines.get(line).cloned().unwrap_or(""), units))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  fn framed(body: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+  }
+
+  // the code the server exits with and every message it sent, for messages sent to it
+  fn serve(input: &str) -> (i32, Vec<Json>) {
+    let mut output = Vec::new();
+    let code       = Server::new(Vec::new()).run(Cursor::new(input.as_bytes()), &mut output);
+
+    let mut output   = Cursor::new(output);
+    let mut messages = Vec::new();
+
+    while let Some(message) = read_message(&mut output) {
+      messages.push(message.expect("the server sent a malformed message"))
+    }
+
+    (code, messages)
+  }
+
+  const OPEN: &str = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///test.snek","version":1,"languageId":"snek","text":"limit := 10\nnext := limits + 1\n"}}}"#;
+
+  #[test]
+  fn answers_requests_and_publishes_diagnostics() {
+    let input = [
+      r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+      OPEN,
+      r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///test.snek"},"position":{"line":0,"character":1}}}"#,
+      r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/unknown","params":{}}"#,
+      r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#,
+      r#"{"jsonrpc":"2.0","method":"exit"}"#,
+    ].iter().map(|body| framed(body)).collect::<String>();
+
+    let (code, messages) = serve(&input);
+
+    assert_eq!(code, 0);
+    assert_eq!(messages.len(), 5);
+
+    assert_eq!(messages[0].get("id").as_usize(), Some(1));
+    assert_eq!(messages[0].get("result").get("capabilities").get("hoverProvider"), &Json::Bool(true));
+
+    assert_eq!(messages[1].get("method").as_str(), Some("textDocument/publishDiagnostics"));
+    assert_eq!(messages[1].get("params").get("diagnostics").as_array().len(), 1);
+
+    assert_eq!(messages[2].get("id").as_usize(), Some(2));
+    assert!(messages[2].get("result").get("contents").get("value").as_str().is_some_and(|value| value.contains("limit: int")));
+
+    assert_eq!(messages[3].get("error").get("code"), &Json::Number(-32601.0));
+    assert_eq!(messages[4].get("id").as_usize(), Some(4));
+  }
+
+  #[test]
+  fn answers_malformed_messages_with_errors_and_goes_on() {
+    let input = format!(
+      "{}{}Content-Type: text/plain\r\n\r\n{}{}",
+      framed(r#"{"jsonrpc":"2.0","id":1,"method":"#),
+      framed(r#"{"jsonrpc":"2.0","id":2,"method":"initialize","params":{}"#),
+      framed(OPEN),
+      framed(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+    );
+
+    let (code, messages) = serve(&input);
+
+    // exiting without a shutdown first
+    assert_eq!(code, 1);
+    assert_eq!(messages.len(), 4);
+
+    for message in &messages[.. 3] {
+      assert!(message.get("id").is_null());
+      assert_eq!(message.get("error").get("code"), &Json::Number(-32700.0));
+    }
+
+    assert_eq!(messages[3].get("method").as_str(), Some("textDocument/publishDiagnostics"));
+  }
+
+  #[test]
+  fn stops_when_the_input_ends() {
+    let (code, messages) = serve(&framed(OPEN));
+
+    assert_eq!(code, 1);
+    assert_eq!(messages.len(), 1);
+  }
+}
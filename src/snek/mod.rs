@@ -2,6 +2,11 @@
 pub mod error;
 pub mod source;
 pub mod lexer;
+pub mod syntax;
 pub mod parser;
 pub mod visitor;
-pub mod interpreter;
\ No newline at end of file
+pub mod interpreter;
+pub mod module;
+pub mod doc;
+pub mod lsp;
+pub mod stdlib;
\ No newline at end of file
@@ -0,0 +1,944 @@
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+
+use std::rc::Rc;
+
+use super::error::Response::Wrong;
+use super::lexer::*;
+use super::parser::*;
+use super::visitor::*;
+use super::interpreter::*;
+use super::stdlib;
+
+
+
+// names of a module's globals are qualified by its path, the entry program's are left bare
+pub fn global_name(module: &str, name: &str) -> String {
+  if module.is_empty() {
+    name.to_string()
+  } else {
+    format!("{}::{}", module, name)
+  }
+}
+
+
+
+// a module is run once when first imported, its globals are all that's left of it
+pub struct Module {
+  pub path:    String,
+  pub exports: Vec<(String, Type)>,
+}
+
+
+
+pub struct ModuleLoader {
+  pub search_path: Vec<PathBuf>,
+  pub checking:    bool, // imported files are only checked, never compiled or run
+  pub dialect:     LexerBuilder, // what the entry program and every file it imports are written in
+
+  modules: HashMap<PathBuf, Rc<Module>>,
+  loading: Vec<PathBuf>,
+}
+
+impl ModuleLoader {
+  pub fn new(search_path: Vec<PathBuf>) -> Self {
+    ModuleLoader {
+      search_path,
+      checking: false,
+      dialect:  LexerBuilder::new(),
+
+      modules: HashMap::new(),
+      loading: Vec::new(),
+    }
+  }
+
+
+
+  // relative to the importing file first, then along the search path
+  pub fn resolve(&self, path: &str, from: &Source) -> Option<PathBuf> {
+    let mut file = PathBuf::from(path);
+
+    if file.extension().is_none() {
+      file.set_extension("snek");
+    }
+
+    let origin = Path::new(&from.file.0).parent().map(|parent| parent.to_path_buf()).unwrap_or_default();
+
+    for base in Some(origin).into_iter().chain(self.search_path.iter().cloned()) {
+      let candidate = base.join(&file);
+
+      if candidate.is_file() {
+        return candidate.canonicalize().ok()
+      }
+    }
+
+    None
+  }
+
+
+
+  // checks, compiles and runs an entry program, the globals the host defined on `vm` known to it by their types
+  pub fn run(&mut self, vm: &mut VirtualMachine, source: &Source) -> Result<(), ()> {
+    let ast = Parser::parse_dialect(source, &self.dialect)?;
+
+    let mut visitor = Visitor::new(source, &ast);
+
+    for &(ref name, ref t) in vm.host_globals() {
+      visitor.declare_global(name, t.clone())?
+    }
+
+    visitor.imports = self.load_imports(vm, &ast, source, "")?;
+    visitor.visit()?;
+
+    let block = {
+      let mut compiler = Compiler::new(vm, source);
+
+      compiler.declare_globals(&visitor.globals());
+      compiler.declare_literals(visitor.literals());
+      compiler.declare_builtins(visitor.builtins());
+      compiler.compile_main(&ast, "entry")?
+    };
+
+    vm.execute(&block)
+  }
+
+
+
+  // loads every module imported by `ast` and links its exports into the globals of `module`
+  pub fn load_imports(&mut self, vm: &mut VirtualMachine, ast: &Vec<Statement>, source: &Source, module: &str) -> Result<HashMap<String, Rc<Module>>, ()> {
+    let mut imports = HashMap::new();
+
+    for statement in ast {
+      if let StatementNode::Import(ref path, ref alias) = statement.node {
+        // standard modules take precedence, local files can still be imported as e.g. `./math`
+        let resolved = match self.load_native(vm, path) {
+          Some(imported) => {
+            self.link(vm, &imported, alias, module);
+            imports.insert(path.clone(), imported);
+
+            continue
+          },
+
+          None => self.resolve(path, source),
+        };
+
+        let resolved = match resolved {
+          Some(resolved) => resolved,
+          None           => return Err(
+            response!(
+              Wrong(format!("can't find module `{}`", path)),
+              source.file,
+              source.resolve(statement.pos)
+            )
+          )
+        };
+
+        if let Some(start) = self.loading.iter().position(|loading| *loading == resolved) {
+          let chain = self.loading[start ..].iter()
+            .chain(Some(&resolved))
+            .map(|path| path.display().to_string())
+            .collect::<Vec<String>>();
+
+          return Err(
+            response!(
+              Wrong(format!("import cycle: {}", chain.join(" -> "))),
+              source.file,
+              source.resolve(statement.pos)
+            )
+          )
+        }
+
+        let imported = self.load(vm, resolved)?;
+
+        self.link(vm, &imported, alias, module);
+        imports.insert(path.clone(), imported);
+      }
+    }
+
+    Ok(imports)
+  }
+
+
+
+  fn link(&self, vm: &mut VirtualMachine, imported: &Module, alias: &str, module: &str) {
+    if self.checking {
+      return
+    }
+
+    for &(ref name, _) in imported.exports.iter() {
+      let index = vm.fetch_global(&global_name(&imported.path, name)).unwrap();
+
+      vm.alias_global(&global_name(module, &format!("{}.{}", alias, name)), index)
+    }
+  }
+
+  fn load_native(&mut self, vm: &mut VirtualMachine, name: &str) -> Option<Rc<Module>> {
+    let key = PathBuf::from(name);
+
+    if let Some(module) = self.modules.get(&key) {
+      return Some(module.clone())
+    }
+
+    let mut exports = Vec::new();
+
+    for (export, t, value) in stdlib::module(vm, name)? {
+      vm.set_global(&global_name(name, &export), value);
+      exports.push((export, t))
+    }
+
+    let module = Rc::new(
+      Module {
+        path: name.to_string(),
+        exports,
+      }
+    );
+
+    self.modules.insert(key, module.clone());
+
+    Some(module)
+  }
+
+  pub fn load(&mut self, vm: &mut VirtualMachine, path: PathBuf) -> Result<Rc<Module>, ()> {
+    if let Some(module) = self.modules.get(&path) {
+      return Ok(module.clone())
+    }
+
+    let name   = path.display().to_string();
+    let source = Source::new(name.clone());
+    let ast    = Parser::parse_dialect(&source, &self.dialect)?;
+
+    self.loading.push(path.clone());
+
+    let imports = self.load_imports(vm, &ast, &source, &name);
+
+    self.loading.pop();
+
+    let mut visitor = Visitor::new(&source, &ast);
+
+    visitor.imports = imports?;
+    visitor.visit()?;
+
+    let exports = visitor.exports()?;
+
+    if self.checking {
+      let module = Rc::new(
+        Module {
+          path: name,
+          exports,
+        }
+      );
+
+      self.modules.insert(path, module.clone());
+
+      return Ok(module)
+    }
+
+    let block = {
+      let mut compiler = Compiler::module(vm, &source, &name);
+
+      compiler.declare_globals(&visitor.globals());
+      compiler.declare_literals(visitor.literals());
+      compiler.declare_builtins(visitor.builtins());
+      compiler.compile_main(&ast, &name)?
+    };
+
+    // running the module once initializes its globals, leaving nothing on the stack
+    let depth = vm.stack.len();
+
+    vm.execute(&block)?;
+    vm.stack.truncate(depth);
+
+    // `io.exit` while initializing a module ends the whole program
+    if vm.exit_code.is_some() {
+      return Err(())
+    }
+
+    let module = Rc::new(
+      Module {
+        path: name,
+        exports,
+      }
+    );
+
+    self.modules.insert(path, module.clone());
+
+    Ok(module)
+  }
+}
+
+
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use super::super::error;
+
+  use std::cell::RefCell;
+  use std::io::{ self, Write };
+
+  // checks, compiles and runs a program, giving the messages of whatever went wrong
+  pub fn run(vm: &mut VirtualMachine, text: &str) -> (Result<(), ()>, Vec<String>) {
+    run_dialect(vm, LexerBuilder::new(), text)
+  }
+
+  // runs a program like `run`, giving what it printed too
+  pub fn output(vm: &mut VirtualMachine, text: &str) -> (Result<(), ()>, Vec<String>, String) {
+    let printed = Rc::new(RefCell::new(Vec::new()));
+
+    vm.set_output(Box::new(Printed(printed.clone())));
+
+    let (result, messages) = run(vm, text);
+    let printed            = String::from_utf8(printed.borrow().clone()).unwrap();
+
+    (result, messages, printed)
+  }
+
+  struct Printed(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Printed {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  fn run_dialect(vm: &mut VirtualMachine, dialect: LexerBuilder, text: &str) -> (Result<(), ()>, Vec<String>) {
+    let source = Source::from("<test>", text.lines().map(|line| line.to_string()).collect());
+
+    let mut loader = ModuleLoader::new(Vec::new());
+
+    loader.dialect = dialect;
+
+    let (result, diagnostics) = error::collect(|| loader.run(vm, &source));
+
+    (result, diagnostics.into_iter().map(|diagnostic| diagnostic.message).collect())
+  }
+
+  // a fresh directory, for programs to import the files written to it by their absolute paths
+  fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("snek-{}-{}", name, std::process::id()));
+
+    std::fs::create_dir_all(&directory).unwrap();
+
+    directory.canonicalize().unwrap()
+  }
+
+  // `5s` lexes to the int 5000, milliseconds
+  struct DurationMatcher;
+
+  impl<'t> Matcher<'t> for DurationMatcher {
+    fn try_match(&self, tokenizer: &mut Tokenizer<'t>) -> Result<Option<Token<'t>>, ()> {
+      let seconds = tokenizer.collect_while(|c| c.is_ascii_digit());
+
+      if seconds.is_empty() || tokenizer.peek() != Some('s') {
+        return Ok(None)
+      }
+
+      tokenizer.advance();
+
+      let source: &'t Source = tokenizer.source;
+      let pos                = tokenizer.last_position();
+
+      let millis = format!("{}000", seconds);
+
+      Ok(Some(Token::new(TokenType::Int, (pos.0, &source.lines[pos.0 - 1]), (pos.1 + 1, pos.1 + seconds.len() + 1), &millis)))
+    }
+  }
+
+  #[test]
+  fn host_globals_are_read_and_written_by_programs() {
+    let mut vm = VirtualMachine::new();
+
+    vm.define_global("limit", Type::from(TypeNode::Int), Value::Int(3));
+
+    let (result, messages) = run(&mut vm, "doubled := limit * 2\nlimit = limit + 1");
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("doubled"), Some(Value::Int(6)));
+    assert_eq!(vm.get_global("limit"), Some(Value::Int(4)));
+  }
+
+  #[test]
+  fn host_globals_are_checked_as_their_type() {
+    let mut vm = VirtualMachine::new();
+
+    vm.define_global("verbose", Type::from(TypeNode::Bool), Value::Bool(true));
+
+    let (result, messages) = run(&mut vm, "x: int = verbose");
+
+    assert!(result.is_err());
+    assert!(messages.iter().any(|message| message.contains("mismatched")), "{:?}", messages);
+  }
+
+  #[test]
+  fn undefined_globals_are_rejected_before_running() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "limit * 2");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("no such value `limit` in this scope".to_string()));
+  }
+
+  #[test]
+  fn globals_are_shared_by_every_function_frame() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"step :: 3
+count := 0
+bump := fun() { count = count + step }
+twice := fun() { bump()
+bump() }
+twice()
+bump()"#;
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("count"), Some(Value::Int(9)));
+  }
+
+  #[test]
+  fn values_are_printed_to_the_host_writer() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"print("a", 1)
+println(" b")
+println(format("{} is {}", "x", 2.5))
+println([1, 2, 3])
+println(["k": 'c'])
+println((1, "s", yes))
+f := fun(x: int) -> int { x }
+println(f)"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "a 1 b\nx is 2.5\n[1, 2, 3]\n[\"k\": 'c']\n(1, \"s\", yes)\n<fun f>\n");
+  }
+
+  #[test]
+  fn strings_interpolate_the_expressions_in_their_braces() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"name := "snek"
+age := 3
+xs := [1, 2]
+println("hello {name}, you are {age + 1}, {xs} {"in" ++ name}")
+println("\{name} {{}} {}")
+println(format("{{}} {}", 1))"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "hello snek, you are 4, [1, 2] insnek\n{name} {{}} {}\n{} 1\n");
+  }
+
+  #[test]
+  fn escapes_multi_line_and_raw_strings_are_read() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r##"a := "\u{1F40D} \x41\0!"
+b := """
+    first
+      second
+    """
+c := r#"raw "quoted" \n"#
+d := r"back\slash"
+println(a)
+println(b)
+println(c, d)"##;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "\u{1F40D} A\0!\nfirst\n  second\nraw \"quoted\" \\n back\\slash\n");
+  }
+
+  #[test]
+  fn only_printable_values_are_interpolated() {
+    let (result, messages) = run(&mut VirtualMachine::new(), "f := fun() {}\nprintln(\"{f}\")");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("can't interpolate `() nil` into a string".to_string()));
+  }
+
+  #[test]
+  fn format_takes_an_argument_for_each_placeholder() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages, printed) = output(&mut vm, "println(\"before\")\nprintln(format(\"{}%{}\", 1))");
+
+    assert!(result.is_err());
+    assert_eq!(printed, "");
+    assert_eq!(messages, vec!("format string has 2 placeholder(s) but 1 argument(s) were given".to_string()));
+  }
+
+  #[test]
+  fn top_level_functions_can_not_be_redeclared() {
+    let mut vm = VirtualMachine::new();
+
+    let program = "f := fun(n: int) -> int { n }\nprintln(f(1))\nf := fun() -> string { \"second\" }";
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("can't redeclare top-level function `f`".to_string()));
+  }
+
+  #[test]
+  fn top_level_functions_recur_on_themselves_and_each_other() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"fib := fun(n: int) -> int { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+even := fun(n: int) -> bool { if n == 0 { yes } else { odd(n - 1) } }
+odd := fun(n: int) -> bool { if n == 0 { no } else { even(n - 1) } }
+a := fib(15)
+b := even(10)
+c := odd(7)"#;
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("a"), Some(Value::Int(610)));
+    assert_eq!(vm.get_global("b"), Some(Value::Bool(true)));
+    assert_eq!(vm.get_global("c"), Some(Value::Bool(true)));
+  }
+
+  #[test]
+  fn top_level_functions_are_called_before_their_declaration() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "n := twice(4)\ntwice := fun(x: int) -> int { x * 2 }");
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("n"), Some(Value::Int(8)));
+  }
+
+  #[test]
+  fn hoisted_functions_can_not_read_globals_before_they_are_initialized() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "n := f()\na := 5\nf := fun() -> int { a }");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("`a` is read before it's initialized".to_string()));
+
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "a := 5\nn := f()\nf := fun() -> int { a }");
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("n"), Some(Value::Int(5)));
+  }
+
+  #[test]
+  fn functions_only_refer_to_globals_declared_before_them() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "double := fun(x: int) -> int { x * 2 + secret }\nsecret :: 1");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("no such value `secret` in this scope".to_string()));
+  }
+
+  #[test]
+  fn top_level_bindings_shadow_builtins() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "len := fun(x: int) -> int { x * 2 }\nn := len(5)");
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("n"), Some(Value::Int(10)));
+
+    let (result, messages) = run(&mut vm, "println := fun(s: string) -> int { 1 }\nx := println(\"hi\")");
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("x"), Some(Value::Int(1)));
+  }
+
+  #[test]
+  fn builtins_are_called_before_a_binding_shadows_them() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "n := len([1, 2, 3])\nlen := 4\nm := len");
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("n"), Some(Value::Int(3)));
+    assert_eq!(vm.get_global("m"), Some(Value::Int(4)));
+  }
+
+  #[test]
+  fn literals_passed_inside_operands_take_their_sized_type() {
+    let mut vm = VirtualMachine::new();
+
+    let program = "x: u8 = 250\nf := fun(a: u8) -> u8 { a }\nleft := x + f(1)\nright := f(1) + x";
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("left"), Some(Value::Sized(251, IntKind::U8)));
+    assert_eq!(vm.get_global("right"), Some(Value::Sized(251, IntKind::U8)));
+  }
+
+  #[test]
+  fn arguments_inside_operands_and_conditions_are_checked() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "f := fun(a: u8) -> u8 { a }\nn := 1 + f(\"s\")");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("mismatched argument, expected `(u8) u8` got `string`".to_string()));
+
+    let (result, messages) = run(&mut vm, "f := fun(a: u8) -> u8 { a }\nn := if no { 1 } elif f(\"s\") > 2 { 2 } else { 3 }");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("mismatched argument, expected `(u8) u8` got `string`".to_string()));
+  }
+
+  #[test]
+  fn numbers_and_chars_are_ordered() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "a := 1 < 2\nb := 'b' <= 'a'\nc := 2.5 > 1.0\nd: u8 = 3\ne := d >= 4");
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("a"), Some(Value::Bool(true)));
+    assert_eq!(vm.get_global("b"), Some(Value::Bool(false)));
+    assert_eq!(vm.get_global("c"), Some(Value::Bool(true)));
+    assert_eq!(vm.get_global("e"), Some(Value::Bool(false)));
+  }
+
+  #[test]
+  fn only_numbers_and_chars_are_ordered() {
+    for &(program, message) in &[
+      ("x := \"a\" < \"b\"", "can't perform operation `string < string`"),
+      ("x := yes < no",         "can't perform operation `bool < bool`"),
+      ("x := [1] < [2]",        "can't perform operation `[int] < [int]`"),
+    ] {
+      let mut vm = VirtualMachine::new();
+
+      let (result, messages) = run(&mut vm, program);
+
+      assert!(result.is_err());
+      assert_eq!(messages, vec!(message.to_string()));
+    }
+  }
+
+  #[test]
+  fn map_keys_are_iterated_in_their_own_scope() {
+    let mut vm = VirtualMachine::new();
+
+    let program = "m := [\"a\": 1, \"b\": 2]\ntotal := 0\nfor k in m {\n  total = total + m[k]\n}\nx := 1\ny := {\n  x := 5\n  x * 2\n}";
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("total"), Some(Value::Int(3)));
+    assert_eq!(vm.get_global("x"), Some(Value::Int(1)));
+    assert_eq!(vm.get_global("y"), Some(Value::Int(10)));
+  }
+
+  #[test]
+  fn raising_fits_where_any_value_is_expected() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"x := try { raise("a") } catch e { 2 }
+positive := fun(n: int) -> int { if n > 0 { n } else { raise("not positive") } }
+checked := fun(n: int) -> int {
+  if n > 0 {
+    return n
+  }
+  raise("not positive")
+}
+never := fun() -> string { raise("never") }
+y := try { positive(0) } catch e { checked(3) }
+z := try { never() } catch e { e }"#;
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("x"), Some(Value::Int(2)));
+    assert_eq!(vm.get_global("y"), Some(Value::Int(3)));
+    assert_eq!(vm.get_global("z").map(|z| z.to_string()), Some("never".to_string()));
+  }
+
+  #[test]
+  fn errors_unwind_frames_to_the_innermost_handler_left() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"inner := fun(n: int) -> int { if n > 2 { raise("too big") } else { n } }
+outer := fun(n: int) -> int { inner(n) + 1 }
+a := try { outer(5) } catch e { println("caught", e)
+0 }
+b := try { try { outer(9) } catch e { raise(format("again: {}", e)) } } catch e { println(e)
+1 }
+c := try { outer(1) } catch e { 0 }
+d := outer(7)
+println("unreached")"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("too big".to_string()));
+    assert_eq!(printed, "caught too big\nagain: too big\n");
+
+    assert_eq!(vm.get_global("a"), Some(Value::Int(0)));
+    assert_eq!(vm.get_global("b"), Some(Value::Int(1)));
+    assert_eq!(vm.get_global("c"), Some(Value::Int(2)));
+  }
+
+  #[test]
+  fn returns_leave_nested_expressions_loops_and_handlers() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"find := fun(x: int) -> int {
+  for i in [1, 2, 3] {
+    if i == x {
+      return i * 10
+    }
+  }
+  0
+}
+early := fun(x: int) -> int {
+  y := 1 + {
+    if x > 0 {
+      return 99
+    }
+    2
+  }
+  y
+}
+handled := fun(x: int) -> int {
+  try {
+    if x > 0 {
+      return 5
+    }
+    raise("no")
+  } catch e {
+    return 6
+  }
+}
+bare := fun() {
+  return
+}
+a := [find(2), find(9), early(1), early(0), handled(1), handled(0)]
+b := bare()"#;
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("a").map(|a| a.to_string()), Some("[20, 0, 99, 3, 5, 6]".to_string()));
+    assert_eq!(vm.get_global("b"), Some(Value::Nil));
+  }
+
+  #[test]
+  fn type_parameters_are_bound_by_the_arguments() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"id := fun<T>(x: T) -> T { x }
+first := fun<T>(xs: [T]) -> T { xs[0] }
+same := fun<T>(a: T, b: T) -> bool { a == b }
+n := id(4) + 1
+s := id("s") ++ "!"
+println(n, s, first(['a', 'b']), same(1, 1), same("a", "b"))"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "5 s! a yes no\n");
+
+    let (result, messages) = run(&mut vm, "same := fun<T>(a: T, b: T) -> bool { a == b }\nx := same(1, \"a\")");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("mismatched argument, expected `int` got `string`".to_string()));
+
+    let (result, messages) = run(&mut vm, "id := fun<T>(x: T) -> T { x }\nx: string = id(1)");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("mismatched types, expected type `string` got `int`".to_string()));
+  }
+
+  #[test]
+  fn sized_ints_overflow_unless_wrapped_or_saturated() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"a: u8 = 200
+b := wrapping_add(a, 100)
+c := saturating_add(a, 100)
+d := saturating_sub(a, 250)
+e: i8 = 100
+f := wrapping_mul(e, 3)
+g := a as i64 + 1000"#;
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("b"), Some(Value::Sized(44, IntKind::U8)));
+    assert_eq!(vm.get_global("c"), Some(Value::Sized(255, IntKind::U8)));
+    assert_eq!(vm.get_global("d"), Some(Value::Sized(0, IntKind::U8)));
+    assert_eq!(vm.get_global("f"), Some(Value::Sized(44, IntKind::I8)));
+    assert_eq!(vm.get_global("g"), Some(Value::Sized(1200, IntKind::I64)));
+
+    let (_, messages) = run(&mut VirtualMachine::new(), "a: u8 = 200\nb := a + 100");
+
+    assert_eq!(messages, vec!("`u8` overflowed in `200 + 100`".to_string()));
+
+    let (_, messages) = run(&mut VirtualMachine::new(), "a: i64 = 1200\nb := a as u8");
+
+    assert_eq!(messages, vec!("`1200` doesn't fit in `u8`".to_string()));
+  }
+
+  #[test]
+  fn sized_literals_are_checked_against_their_range() {
+    let (_, messages) = run(&mut VirtualMachine::new(), "a: u8 = 256");
+
+    assert_eq!(messages, vec!("literal `256` doesn't fit in `u8`".to_string()));
+
+    let (_, messages) = run(&mut VirtualMachine::new(), "a := 300 as u8");
+
+    assert_eq!(messages, vec!("literal `300` doesn't fit in `u8`".to_string()));
+  }
+
+  #[test]
+  fn arithmetic_takes_numbers_of_one_type() {
+    for &(program, message) in &[
+      ("id := fun<T>(x: T) -> T { x + 1 }", "can't perform operation `T + int`"),
+      ("x := 1 + \"a\"",                   "can't perform operation `int + string`"),
+      ("x := 2.5 * 2",                     "can't perform operation `float * int`"),
+      ("x := yes - no",                    "can't perform operation `bool - bool`"),
+    ] {
+      let mut vm = VirtualMachine::new();
+
+      let (result, messages) = run(&mut vm, program);
+
+      assert!(result.is_err());
+      assert_eq!(messages, vec!(message.to_string()));
+    }
+  }
+
+  #[test]
+  fn argument_counts_fit_in_a_byte() {
+    let mut vm = VirtualMachine::new();
+
+    let args = vec!("1"; 255).join(", ");
+
+    let (result, messages) = run(&mut vm, &format!("s := format(\"{}\", {})", "{}".repeat(254), &args[3 ..]));
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("s").map(|s| s.to_string()), Some("1".repeat(254)));
+
+    let (result, messages) = run(&mut vm, &format!("println({}, 1)", args));
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("too many arguments, a call takes at most 255".to_string()));
+
+    let (result, messages) = run(&mut vm, "s := format()");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("`format` takes a format string".to_string()));
+  }
+
+  #[test]
+  fn modules_are_run_once_and_expose_their_exports() {
+    let directory = directory("exports");
+
+    std::fs::write(directory.join("util.snek"), "println(\"loading util\")\nexport double := fun(x: int) -> int { x * 2 }\nexport base := 20\nhidden := 1").unwrap();
+    std::fs::write(directory.join("middle.snek"), format!("import \"{}/util\"\nexport value := util.double(util.base)", directory.display())).unwrap();
+
+    let mut vm = VirtualMachine::new();
+
+    let program = format!("import \"{0}/util\" as u\nimport \"{0}/middle\"\na := u.double(u.base) + middle.value", directory.display());
+
+    let (result, messages, printed) = output(&mut vm, &program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "loading util\n");
+    assert_eq!(vm.get_global("a"), Some(Value::Int(80)));
+
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages, _) = output(&mut vm, &format!("import \"{}/util\"\na := util.hidden", directory.display()));
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("no such value `util.hidden` in this scope".to_string()));
+  }
+
+  #[test]
+  fn import_cycles_are_reported() {
+    let directory = directory("cycle");
+
+    std::fs::write(directory.join("a.snek"), format!("import \"{}/b\"\nexport x := 1", directory.display())).unwrap();
+    std::fs::write(directory.join("b.snek"), format!("import \"{}/a\"\nexport y := 2", directory.display())).unwrap();
+
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, &format!("import \"{}/a\"", directory.display()));
+
+    let (a, b) = (directory.join("a.snek"), directory.join("b.snek"));
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!(format!("import cycle: {} -> {} -> {}", a.display(), b.display(), a.display())));
+  }
+
+  #[test]
+  fn missing_modules_are_reported() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "import \"no/such/module\"");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("can't find module `no/such/module`".to_string()));
+  }
+
+  #[test]
+  fn dialect_matchers_lex_their_literals() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run_dialect(&mut vm, LexerBuilder::new().matcher(DurationMatcher), "timeout := 5s * 2\nretries := 5");
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("timeout"), Some(Value::Int(10000)));
+    assert_eq!(vm.get_global("retries"), Some(Value::Int(5)));
+  }
+
+  #[test]
+  fn dialect_operators_call_their_function() {
+    let mut vm = VirtualMachine::new();
+
+    let dialect = LexerBuilder::new().operator("|>", 1, "at_least");
+    let program = "at_least := fun(x: int, floor: int) -> int { if x < floor { floor } else { x } }\nn := 2 + 1 |> 5";
+
+    let (result, messages) = run_dialect(&mut vm, dialect, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(vm.get_global("n"), Some(Value::Int(5)));
+  }
+
+  #[test]
+  fn dialect_operators_are_unknown_to_other_parsers() {
+    let mut vm = VirtualMachine::new();
+
+    let dialect = LexerBuilder::new().operator("|>", 1, "at_least");
+    let program = "at_least := fun(x: int, floor: int) -> int { x }\nn := 1 |> 5";
+
+    let (result, _) = run_dialect(&mut vm, dialect, program);
+
+    assert!(result.is_ok());
+
+    let (result, _) = run(&mut vm, program);
+
+    assert!(result.is_err());
+  }
+}
@@ -1,69 +1,136 @@
-use std::rc::Rc;
+use std::sync::Arc;
 use std::fmt;
 
 use super::*;
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum StatementNode<'s> {
-  Expression(Expression<'s>),
-  Variable(Type, Expression<'s>, Option<Expression<'s>>),
-  Constant(Type, Expression<'s>, Expression<'s>),
-  Assignment(Expression<'s>, Expression<'s>),
+pub enum StatementNode {
+  Expression(Expression),
+  Variable(Type, Expression, Option<Expression>),
+  Constant(Type, Expression, Expression),
+  Assignment(Expression, Expression),
+  For(Expression, Expression, Expression),
+  Import(String, String),
+  Export(Arc<Statement>),
 
   Break,
   Continue,
-  Return(Option<Rc<Expression<'s>>>),
+  Return(Option<Arc<Expression>>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Statement<'s> {
-  pub node: StatementNode<'s>,
-  pub pos:  TokenElement<'s>,
+pub struct Statement {
+  pub node: StatementNode,
+  pub pos:  Span,
 }
 
-impl<'s> Statement<'s> {
-  pub fn new(node: StatementNode<'s>, pos: TokenElement<'s>) -> Self {
+impl Statement {
+  pub fn new(node: StatementNode, pos: Span) -> Self {
     Statement {
       node,
       pos,
     }
   }
+
+  // the same statement with every span in it passed through `f`, as when moving it into another source
+  pub fn map_spans(&self, f: &Fn(Span) -> Span) -> Statement {
+    use self::StatementNode::*;
+
+    let node = match self.node {
+      Expression(ref expression)            => Expression(expression.map_spans(f)),
+      Variable(ref t, ref left, ref right)  => Variable(t.clone(), left.map_spans(f), right.as_ref().map(|right| right.map_spans(f))),
+      Constant(ref t, ref left, ref right)  => Constant(t.clone(), left.map_spans(f), right.map_spans(f)),
+      Assignment(ref left, ref right)       => Assignment(left.map_spans(f), right.map_spans(f)),
+      For(ref name, ref iterable, ref body) => For(name.map_spans(f), iterable.map_spans(f), body.map_spans(f)),
+      Import(ref path, ref alias)           => Import(path.clone(), alias.clone()),
+      Export(ref statement)                 => Export(Arc::new(statement.map_spans(f))),
+      Break                                 => Break,
+      Continue                              => Continue,
+      Return(ref value)                     => Return(value.as_ref().map(|value| Arc::new(value.map_spans(f)))),
+    };
+
+    Statement::new(node, f(self.pos))
+  }
 }
 
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum ExpressionNode<'e> {
+pub enum ExpressionNode {
   Int(u128),
   Double(f64),
   Str(String),
+  Interpolation(Vec<Expression>), // string segments and the expressions between them
   Char(char),
   Bool(bool),
   Identifier(String),
-  Binary(Rc<Expression<'e>>, Operator, Rc<Expression<'e>>),
-  Block(Vec<Statement<'e>>),
-  Set(Vec<Expression<'e>>),
-  Cast(Rc<Expression<'e>>, Type),
-  Array(Vec<Expression<'e>>),
-  Index(Rc<Expression<'e>>, Rc<Expression<'e>>),
-  Function(Vec<Statement<'e>>, Type, Rc<Expression<'e>>),
-  Call(Rc<Expression<'e>>, Vec<Expression<'e>>),
-  If(Rc<Expression<'e>>, Rc<Expression<'e>>, Option<Vec<(Option<Expression<'e>>, Expression<'e>, TokenElement<'e>)>>),
+  Binary(Arc<Expression>, Operator, Arc<Expression>),
+  Block(Vec<Statement>),
+  Set(Vec<Expression>),
+  Cast(Arc<Expression>, Type),
+  Array(Vec<Expression>),
+  Map(Vec<(Expression, Expression)>),
+  Index(Arc<Expression>, Arc<Expression>),
+  Function(Vec<String>, Vec<Statement>, Type, Arc<Expression>),
+  Call(Arc<Expression>, Vec<Expression>),
+  If(Arc<Expression>, Arc<Expression>, Option<Vec<(Option<Expression>, Expression, Span)>>),
+  Try(Arc<Expression>, String, Arc<Expression>),
   EOF,
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Expression<'e> {
-  pub node: ExpressionNode<'e>,
-  pub pos:  TokenElement<'e>,
+pub struct Expression {
+  pub node: ExpressionNode,
+  pub pos:  Span,
 }
 
-impl<'e> Expression<'e> {
-  pub fn new(node: ExpressionNode<'e>, pos: TokenElement<'e>) -> Self {
+impl Expression {
+  pub fn new(node: ExpressionNode, pos: Span) -> Self {
     Expression {
       node,
       pos,
     }
   }
+
+  pub fn map_spans(&self, f: &Fn(Span) -> Span) -> Expression {
+    use self::ExpressionNode::*;
+
+    let all   = |expressions: &Vec<Expression>| expressions.iter().map(|expression| expression.map_spans(f)).collect::<Vec<_>>();
+    let inner = |expression: &Arc<Expression>| Arc::new(expression.map_spans(f));
+
+    let node = match self.node {
+      Int(n)                               => Int(n),
+      Double(n)                            => Double(n),
+      Str(ref s)                           => Str(s.clone()),
+      Interpolation(ref parts)             => Interpolation(all(parts)),
+      Char(c)                              => Char(c),
+      Bool(b)                              => Bool(b),
+      Identifier(ref name)                 => Identifier(name.clone()),
+      Binary(ref a, ref op, ref b)         => Binary(inner(a), op.clone(), inner(b)),
+      Block(ref body)                      => Block(body.iter().map(|statement| statement.map_spans(f)).collect()),
+      Set(ref content)                     => Set(all(content)),
+      Cast(ref a, ref t)                   => Cast(inner(a), t.clone()),
+      Array(ref content)                   => Array(all(content)),
+      Map(ref content)                     => Map(content.iter().map(|&(ref key, ref value)| (key.map_spans(f), value.map_spans(f))).collect()),
+      Index(ref a, ref index)              => Index(inner(a), inner(index)),
+      Call(ref callee, ref args)           => Call(inner(callee), all(args)),
+      Try(ref body, ref name, ref handler) => Try(inner(body), name.clone(), inner(handler)),
+      EOF                                  => EOF,
+
+      Function(ref generics, ref params, ref t, ref body) => Function(
+        generics.clone(), params.iter().map(|param| param.map_spans(f)).collect(), t.clone(), inner(body)
+      ),
+
+      If(ref condition, ref body, ref elses) => If(
+        inner(condition),
+        inner(body),
+        elses.as_ref().map(|elses| elses.iter().map(|&(ref condition, ref body, pos)| {
+          (condition.as_ref().map(|condition| condition.map_spans(f)), body.map_spans(f), f(pos))
+        }).collect())
+      ),
+    };
+
+    Expression::new(node, f(self.pos))
+  }
 }
 
 
@@ -71,6 +138,40 @@ impl<'e> Expression<'e> {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
   Add, Sub, Mul, Div, Mod, Pow, Concat, Eq, Lt, Gt, NEq, LtEq, GtEq,
+
+  Custom(String), // one a dialect registered, parsed to a call of its function
+}
+
+// the operators a parser knows beyond snek's own, each with its precedence and the function it calls
+#[derive(Debug, Clone, Default)]
+pub struct Operators {
+  custom: Vec<(String, u8, String)>,
+}
+
+impl Operators {
+  // `left operator right` will parse to `function(left, right)`, replacing what was registered for it before
+  pub fn register(&mut self, operator: &str, precedence: u8, function: &str) {
+    self.custom.retain(|&(ref registered, _, _)| registered != operator);
+    self.custom.push((operator.to_string(), precedence, function.to_string()))
+  }
+
+  pub fn get(&self, operator: &str) -> Option<(Operator, u8)> {
+    match self.custom.iter().find(|&&(ref registered, _, _)| registered == operator) {
+      Some(&(_, precedence, _)) => Some((Operator::Custom(operator.to_string()), precedence)),
+      None                      => Operator::from_str(operator),
+    }
+  }
+
+  // the function a registered operator calls
+  pub fn function(&self, operator: &Operator) -> Option<String> {
+    match *operator {
+      Operator::Custom(ref operator) => {
+        self.custom.iter().find(|&&(ref registered, _, _)| registered == operator).map(|&(_, _, ref function)| function.clone())
+      },
+
+      _ => None,
+    }
+  }
 }
 
 impl Operator {
@@ -114,6 +215,8 @@ impl Operator {
       NEq    => "!=",
       LtEq   => "<=",
       GtEq   => ">=",
+
+      Custom(ref operator) => operator,
     }
   }
 }
@@ -1,7 +1,7 @@
 pub mod ast;
 pub mod parser;
 
-use super::lexer::{ TokenElement, Token, TokenType, };
+use super::lexer::{ LexerBuilder, TokenElement, Token, TokenType, Number, parse_number, };
 use super::source::*;
 use super::visitor::*;
 
@@ -1,12 +1,19 @@
 use super::*;
 use super::super::error::Response::Wrong;
+use super::super::interpreter::IntKind;
 
+use std::sync::Arc;
 use std::rc::Rc;
+use std::path::Path;
+
+// a statement with the first and last line of the tokens it was parsed from
+pub type LinedStatement = (Statement, Option<(usize, usize)>);
 
 pub struct Parser<'p> {
-  index:  usize,
-  tokens: Vec<&'p Token<'p>>,
-  source: &'p Source,
+  index:     usize,
+  tokens:    Vec<&'p Token<'p>>,
+  source:    &'p Source,
+  operators: Rc<Operators>,
 }
 
 impl<'p> Parser<'p> {
@@ -14,13 +21,40 @@ impl<'p> Parser<'p> {
     Parser {
       tokens,
       source,
-      index: 0,
+      index:     0,
+      operators: Rc::new(Operators::default()),
     }
   }
 
+  // parses the operators of a dialect too, as `LexerBuilder::build` gave them along with its lexer
+  pub fn with_operators(mut self, operators: Operators) -> Self {
+    self.operators = Rc::new(operators);
+    self
+  }
+
+
+
+  // lexes and parses a whole source, the statements only hold spans so the tokens needn't outlive this
+  pub fn parse_source(source: &Source) -> Result<Vec<Statement>, ()> {
+    Self::parse_dialect(source, &LexerBuilder::new())
+  }
+
+  // like `parse_source`, for a source written in a dialect of snek
+  pub fn parse_dialect(source: &Source, dialect: &LexerBuilder) -> Result<Vec<Statement>, ()> {
+    let content = format!("{}\n", source.lines.join("\n"));
+
+    let (lexer, operators) = dialect.build(content.chars().collect(), source);
+
+    let mut tokens = Vec::new();
 
+    for token_result in lexer {
+      tokens.push(token_result?)
+    }
+
+    Parser::new(tokens.iter().collect(), source).with_operators(operators).parse()
+  }
 
-  pub fn parse(&mut self) -> Result<Vec<Statement<'p>>, ()> {
+  pub fn parse(&mut self) -> Result<Vec<Statement>, ()> {
     let mut ast = Vec::new();
 
     while self.remaining() > 0 {
@@ -30,7 +64,31 @@ impl<'p> Parser<'p> {
     Ok(ast)
   }
 
-  fn parse_statement(&mut self) -> Result<Statement<'p>, ()> {
+  // like `parse`, along with the first and last line of the tokens each statement was parsed from,
+  // none for the `EOF` of trailing blank lines
+  pub fn parse_lines(&mut self) -> Result<Vec<LinedStatement>, ()> {
+    let mut ast = Vec::new();
+
+    while self.remaining() > 0 {
+      let start     = self.index;
+      let statement = self.parse_statement()?;
+
+      let consumed = self.tokens[start .. self.index.min(self.tokens.len())].iter()
+        .filter(|token| token.token_type != TokenType::EOL)
+        .map(|token| (token.line.0, token.line.0 + token.lexeme.matches('\n').count()));
+
+      let lines = consumed.fold(None, |lines, (first, last)| match lines {
+        Some((a, b)) => Some((first.min(a), last.max(b))),
+        None         => Some((first, last)),
+      });
+
+      ast.push((statement, lines))
+    }
+
+    Ok(ast)
+  }
+
+  fn parse_statement(&mut self) -> Result<Statement, ()> {
     use self::TokenType::*;
 
     while self.current_type() == &EOL && self.remaining() != 0 {
@@ -60,17 +118,85 @@ impl<'p> Parser<'p> {
             )
           },
 
+          "for" => {
+            self.next()?;
+
+            let name_position = self.current_position();
+
+            let name = Expression::new(
+              ExpressionNode::Identifier(self.eat_type(&TokenType::Identifier)?),
+              name_position
+            );
+
+            self.eat_lexeme("in")?;
+
+            let iterable      = self.parse_expression()?;
+            let body_position = self.current_position();
+            let body          = Expression::new(
+              ExpressionNode::Block(self.parse_block_of(("{", "}"), &Self::_parse_statement)?),
+              body_position
+            );
+
+            Statement::new(
+              StatementNode::For(name, iterable, body),
+              position
+            )
+          },
+
+          "import" => {
+            self.next()?;
+
+            let path = self.eat_type(&TokenType::Str)?;
+
+            let alias = if self.remaining() > 0 && self.current_lexeme() == "as" {
+              self.next()?;
+
+              self.eat_type(&TokenType::Identifier)?
+            } else {
+              match Path::new(&path).file_stem().and_then(|stem| stem.to_str()) {
+                Some(stem) => stem.to_string(),
+                None       => return Err(
+                  response!(
+                    Wrong(format!("can't name module `{}`, use `as`", path)),
+                    self.source.file,
+                    self.source.resolve(position)
+                  )
+                )
+              }
+            };
+
+            Statement::new(
+              StatementNode::Import(path, alias),
+              position
+            )
+          },
+
+          // the exported declaration consumes its own new line
+          "export" => {
+            self.next()?;
+
+            let statement = self.parse_statement()?;
+
+            return Ok(
+              Statement::new(
+                StatementNode::Export(Arc::new(statement)),
+                position
+              )
+            )
+          },
+
           "return" => {
             self.next()?;
 
-            if self.current_lexeme() == "\n" {
+            // nothing follows at the end of a line or a block
+            if self.remaining() == 0 || self.current_lexeme() == "\n" || self.current_lexeme() == "}" {
               Statement::new(
                 StatementNode::Return(None),
                 position
               )
             } else {
               Statement::new(
-                StatementNode::Return(Some(Rc::new(self.parse_expression()?))),
+                StatementNode::Return(Some(Arc::new(self.parse_expression()?))),
                 position
               )
             }
@@ -99,7 +225,7 @@ impl<'p> Parser<'p> {
                   "="   => {
                     self.next()?;
 
-                    let position = self.span_from(expression.pos.clone());
+                    let position = self.span_from(expression.pos);
 
                     Statement::new(
                       StatementNode::Assignment(expression, self.parse_expression()?),
@@ -117,7 +243,7 @@ impl<'p> Parser<'p> {
 
                 statement
               } else {
-                let position = expression.pos.clone();
+                let position = expression.pos;
 
                 Statement::new(
                   StatementNode::Expression(expression),
@@ -125,7 +251,7 @@ impl<'p> Parser<'p> {
                 )
               }
             } else {
-              let position = expression.pos.clone();
+              let position = expression.pos;
 
               Statement::new(
                 StatementNode::Expression(expression),
@@ -134,8 +260,19 @@ impl<'p> Parser<'p> {
             }
           },
 
+          Index(..) if self.remaining() > 0 && self.current_lexeme() == "=" => {
+            self.next()?;
+
+            let position = self.span_from(expression.pos);
+
+            Statement::new(
+              StatementNode::Assignment(expression, self.parse_expression()?),
+              position
+            )
+          },
+
           _ => {
-            let position = expression.pos.clone();
+            let position = expression.pos;
 
             Statement::new(
               StatementNode::Expression(expression),
@@ -151,7 +288,7 @@ impl<'p> Parser<'p> {
     Ok(statement)
   }
 
-  fn parse_expression(&mut self) -> Result<Expression<'p>, ()> {
+  fn parse_expression(&mut self) -> Result<Expression, ()> {
     let atom = self.parse_atom()?;
 
     if self.current_type() == &TokenType::Operator {
@@ -161,7 +298,7 @@ impl<'p> Parser<'p> {
     }
   }
 
-  fn parse_atom(&mut self) -> Result<Expression<'p>, ()> {
+  fn parse_atom(&mut self) -> Result<Expression, ()> {
     use self::TokenType::*;
 
     if self.remaining() == 0 {
@@ -176,33 +313,88 @@ impl<'p> Parser<'p> {
       let position   = self.current_position();
 
       let expression = match token_type {
-        Int => Expression::new(
-          ExpressionNode::Int(self.eat()?.parse::<u128>().unwrap()),
-          position
-        ),
+        // the lexer only lets valid literals through
+        Int | Double => match parse_number(&self.eat()?) {
+          Ok(Number::Int(n, None))       => Expression::new(ExpressionNode::Int(n), position),
+          Ok(Number::Int(n, Some(kind))) => Expression::new(
+            ExpressionNode::Cast(
+              Arc::new(Expression::new(ExpressionNode::Int(n), position)),
+              Type::from(TypeNode::Sized(kind))
+            ),
+            position
+          ),
 
-        Double => Expression::new(
-          ExpressionNode::Double(self.eat()?.parse::<f64>().unwrap()),
-          position
-        ),
+          Ok(Number::Float(n)) => Expression::new(ExpressionNode::Double(n), position),
 
-        Char => Expression::new(
-          ExpressionNode::Char(self.eat()?.chars().last().unwrap()),
-          position
-        ),
+          Err(message) => return Err(
+            response!(
+              Wrong(message),
+              self.source.file,
+              self.source.resolve(position)
+            )
+          )
+        },
+
+        // dialect matchers may still hand over an empty one
+        Char => match self.eat()?.chars().last() {
+          Some(c) => Expression::new(ExpressionNode::Char(c), position),
+
+          None => return Err(
+            response!(
+              Wrong("empty character literal"),
+              self.source.file,
+              self.source.resolve(position)
+            )
+          )
+        },
 
         Str => Expression::new(
           ExpressionNode::Str(self.eat()?),
           position
         ),
 
+        // segments and embedded expressions alternate, up to the closing segment
+        StrPart => {
+          let mut parts = Vec::new();
+
+          loop {
+            let part_position = self.current_position();
+
+            match *self.current_type() {
+              StrPart => {
+                parts.push(Expression::new(ExpressionNode::Str(self.eat()?), part_position));
+                parts.push(self.parse_expression()?);
+              },
+
+              StrEnd => {
+                parts.push(Expression::new(ExpressionNode::Str(self.eat()?), part_position));
+
+                break
+              },
+
+              _ => return Err(
+                response!(
+                  Wrong(format!("expected end of interpolation, found `{}`", self.current_lexeme())),
+                  self.source.file,
+                  self.source.resolve(part_position)
+                )
+              )
+            }
+          }
+
+          Expression::new(
+            ExpressionNode::Interpolation(parts),
+            position
+          )
+        },
+
         Identifier => Expression::new(
           ExpressionNode::Identifier(self.eat()?),
           position
         ),
 
         Bool => Expression::new(
-          ExpressionNode::Bool(self.eat()? == "true"),
+          ExpressionNode::Bool(self.eat()? == "yes"),
           position
         ),
 
@@ -212,7 +404,23 @@ impl<'p> Parser<'p> {
 
             let left_position = self.current_position();
 
-            let params = self.parse_block_of(("(", ")"), &Self::_parse_declaration_comma)?;              
+            let mut generics = Vec::new();
+
+            if self.current_lexeme() == "<" {
+              self.next()?;
+
+              while self.current_lexeme() != ">" {
+                generics.push(self.eat_type(&TokenType::Identifier)?);
+
+                if self.current_lexeme() == "," {
+                  self.next()?
+                }
+              }
+
+              self.eat_lexeme(">")?;
+            }
+
+            let params = self.parse_block_of(("(", ")"), &Self::_parse_declaration_comma)?;
 
             let return_type = if self.current_lexeme() == "->" {
               self.next()?;
@@ -229,7 +437,7 @@ impl<'p> Parser<'p> {
             let body = self.parse_expression()?;
 
             Expression::new(
-              ExpressionNode::Function(params, return_type, Rc::new(body)),
+              ExpressionNode::Function(generics, params, return_type, Arc::new(body)),
               position
             )
           }
@@ -237,9 +445,9 @@ impl<'p> Parser<'p> {
           "if" => {
             self.next()?;
 
-            let condition   = Rc::new(self.parse_expression()?);
-            let if_position = self.span_from(position.clone());
-            let body        = Rc::new(
+            let condition   = Arc::new(self.parse_expression()?);
+            let if_position = self.span_from(position);
+            let body        = Arc::new(
               Expression::new(
                 ExpressionNode::Block(self.parse_block_of(("{", "}"), &Self::_parse_statement)?),
                 position
@@ -282,11 +490,38 @@ impl<'p> Parser<'p> {
             }
 
             Expression::new(
-              ExpressionNode::If(condition, body, if elses.len() > 0 { Some(elses) } else { None }),
+              ExpressionNode::If(condition, body, if !elses.is_empty() { Some(elses) } else { None }),
               if_position
             )
           },
 
+          "try" => {
+            self.next()?;
+
+            let body = Arc::new(
+              Expression::new(
+                ExpressionNode::Block(self.parse_block_of(("{", "}"), &Self::_parse_statement)?),
+                position
+              )
+            );
+
+            self.eat_lexeme("catch")?;
+
+            let name             = self.eat_type(&TokenType::Identifier)?;
+            let handler_position = self.current_position();
+            let handler          = Arc::new(
+              Expression::new(
+                ExpressionNode::Block(self.parse_block_of(("{", "}"), &Self::_parse_statement)?),
+                handler_position
+              )
+            );
+
+            Expression::new(
+              ExpressionNode::Try(body, name, handler),
+              position
+            )
+          },
+
           ref c => return Err(
             response!(
               Wrong(format!("unexpected keyword `{}`", c)),
@@ -302,40 +537,40 @@ impl<'p> Parser<'p> {
             position
           ),
 
-          "[" => Expression::new(
-            ExpressionNode::Array(self.parse_block_of(("[", "]"), &Self::_parse_expression_comma)?),
-            self.span_from(position)
-          ),
-
-          "(" => {
-            let backup_index = self.index;
-
+          "[" => if self.peek_lexeme(1) == Some(":") && self.peek_lexeme(2) == Some("]") {
+            self.next()?;
             self.next()?;
-
-            let mut nested = 1;
-
-            while nested != 0 {
-              if self.current_lexeme() == ")" {
-                nested -= 1
-              } else if self.current_lexeme() == "(" {
-                nested += 1
-              }
-
-              if nested == 0 {
-                break
-              }
-
-              self.next()?
-            }
-
             self.next()?;
 
-            if self.current_lexeme() != "->" && self.remaining() > 0 && self.current_lexeme() != "\n" {
-              self.parse_type();
-            }
+            Expression::new(
+              ExpressionNode::Map(Vec::new()),
+              self.span_from(position)
+            )
+          } else {
+            let content = self.parse_block_of(("[", "]"), &Self::_parse_entry_comma)?;
 
-            self.index = backup_index;
+            if content.iter().all(|&(_, ref value)| value.is_none()) {
+              Expression::new(
+                ExpressionNode::Array(content.into_iter().map(|(element, _)| element).collect()),
+                self.span_from(position)
+              )
+            } else if content.iter().all(|&(_, ref value)| value.is_some()) {
+              Expression::new(
+                ExpressionNode::Map(content.into_iter().map(|(key, value)| (key, value.unwrap())).collect()),
+                self.span_from(position)
+              )
+            } else {
+              return Err(
+                response!(
+                  Wrong("mixed array elements and map entries"),
+                  self.source.file,
+                  self.source.resolve(position)
+                )
+              )
+            }
+          },
 
+          "(" => {
             let content = self.parse_block_of(("(", ")"), &Self::_parse_expression_comma)?;
 
             if content.len() == 1 {
@@ -382,16 +617,16 @@ impl<'p> Parser<'p> {
   }
 
 
-  fn parse_postfix(&mut self, expression: Expression<'p>) -> Result<Expression<'p>, ()> {
+  fn parse_postfix(&mut self, expression: Expression) -> Result<Expression, ()> {
     match *self.current_type() {
       TokenType::Symbol => match self.current_lexeme().as_str() {
         "(" => {
           let args = self.parse_block_of(("(", ")"), &Self::_parse_expression_comma)?;
 
-          let position = expression.pos.clone();
+          let position = expression.pos;
 
           let call = Expression::new(
-            ExpressionNode::Call(Rc::new(expression), args),
+            ExpressionNode::Call(Arc::new(expression), args),
             self.span_from(position)
           );
 
@@ -405,16 +640,33 @@ impl<'p> Parser<'p> {
 
           self.eat_lexeme("]")?;
 
-          let position = expression.pos.clone();
+          let position = expression.pos;
 
           let index = Expression::new(
-            ExpressionNode::Index(Rc::new(expression), Rc::new(expr)),
+            ExpressionNode::Index(Arc::new(expression), Arc::new(expr)),
             self.span_from(position)
           );
 
           self.parse_postfix(index)
         },
 
+        // members of imported modules are plain names, e.g. `lib.foo`
+        "." => if let ExpressionNode::Identifier(ref module) = expression.node {
+          self.next()?;
+
+          let name     = self.eat_type(&TokenType::Identifier)?;
+          let position = expression.pos;
+
+          self.parse_postfix(
+            Expression::new(
+              ExpressionNode::Identifier(format!("{}.{}", module, name)),
+              self.span_from(position)
+            )
+          )
+        } else {
+          Ok(expression)
+        },
+
         _ => Ok(expression)
       },
 
@@ -423,11 +675,11 @@ impl<'p> Parser<'p> {
           self.next()?;
 
           let t        = self.parse_type()?;
-          let position = expression.pos.clone();
+          let position = expression.pos;
 
           self.parse_postfix(
             Expression::new(
-              ExpressionNode::Cast(Rc::new(expression), t),
+              ExpressionNode::Cast(Arc::new(expression), t),
               position
             )
           )
@@ -440,29 +692,47 @@ impl<'p> Parser<'p> {
     }
   }
 
+  // operators of dialects are calls of the function they were registered with
+  fn binary(&self, left: Expression, operator: Operator, right: Expression, position: Span) -> Expression {
+    let node = match self.operators.function(&operator) {
+      Some(function) => ExpressionNode::Call(
+        Arc::new(Expression::new(ExpressionNode::Identifier(function), position)),
+        vec!(left, right)
+      ),
+
+      None => ExpressionNode::Binary(Arc::new(left), operator, Arc::new(right)),
+    };
+
+    Expression::new(node, position)
+  }
+
+  // operator tokens are only lexed from what snek or the dialect knows, so each has a precedence
+  fn eat_operator(&mut self) -> Result<(Operator, u8), ()> {
+    let lexeme = self.eat()?;
+
+    Ok(self.operators.get(&lexeme).unwrap())
+  }
+
   // basic precedence climbing
-  fn parse_binary(&mut self, left: Expression<'p>) -> Result<Expression<'p>, ()> {
-    let left_position = left.pos.clone();
+  fn parse_binary(&mut self, left: Expression) -> Result<Expression, ()> {
+    let left_position = left.pos;
 
     let mut expression_stack = vec!(left);
-    let mut operator_stack   = vec!(Operator::from_str(&self.eat()?).unwrap());
+    let mut operator_stack   = vec!(self.eat_operator()?);
 
     expression_stack.push(self.parse_atom()?);
 
-    while operator_stack.len() > 0 {
+    while !operator_stack.is_empty() {
       while self.current_type() == &TokenType::Operator {
         let position               = self.current_position();
-        let (operator, precedence) = Operator::from_str(&self.eat()?).unwrap();
+        let (operator, precedence) = self.eat_operator()?;
 
         if precedence < operator_stack.last().unwrap().1 {
           let right = expression_stack.pop().unwrap();
           let left  = expression_stack.pop().unwrap();
 
           expression_stack.push(
-            Expression::new(
-              ExpressionNode::Binary(Rc::new(left), operator_stack.pop().unwrap().0, Rc::new(right)),
-              self.current_position(),
-            )
+            self.binary(left, operator_stack.pop().unwrap().0, right, self.current_position())
           );
 
           if self.remaining() > 0 {
@@ -473,7 +743,7 @@ impl<'p> Parser<'p> {
               response!(
                 Wrong("reached EOF in operation"),
                 self.source.file,
-                position
+                self.source.resolve(position)
               )
             )
           }
@@ -487,10 +757,7 @@ impl<'p> Parser<'p> {
       let left  = expression_stack.pop().unwrap();
 
       expression_stack.push(
-        Expression::new(
-          ExpressionNode::Binary(Rc::new(left), operator_stack.pop().unwrap().0, Rc::new(right)),
-          self.current_position(),
-        )
+        self.binary(left, operator_stack.pop().unwrap().0, right, self.current_position())
       );
     }
 
@@ -504,12 +771,12 @@ impl<'p> Parser<'p> {
     )
   }
 
-  fn parse_declaration(&mut self, left: Expression<'p>) -> Result<Statement<'p>, ()> {
+  fn parse_declaration(&mut self, left: Expression) -> Result<Statement, ()> {
     match self.current_lexeme().as_str() {
       ":" => {
         self.next()?;
 
-        let position = left.pos.clone();
+        let position = left.pos;
 
         match self.current_lexeme().as_str() {
           ":" => {
@@ -534,7 +801,7 @@ impl<'p> Parser<'p> {
             self.next()?;
 
             let right    = Some(self.parse_expression()?);
-            let position = left.pos.clone();
+            let position = left.pos;
 
             Ok(
               Statement::new(
@@ -575,7 +842,7 @@ impl<'p> Parser<'p> {
                 self.next()?;
 
                 let right    = Some(self.parse_expression()?);
-                let position = left.pos.clone();
+                let position = left.pos;
 
                 Ok(
                   Statement::new(
@@ -610,26 +877,38 @@ impl<'p> Parser<'p> {
         response!(
           Wrong("invalid declaration without `:`"),
           self.source.file,
-          self.current_position()
+          self.source.resolve(self.current_position())
         )
       )
     }
   }
 
+  // the builtin types besides the sized ints, by the names they're written as
+  pub const BUILTIN_TYPES: &'static [(&'static str, TypeNode)] = &[
+    ("string", TypeNode::Str),
+    ("char",   TypeNode::Char),
+    ("int",    TypeNode::Int),
+    ("float",  TypeNode::Double),
+    ("bool",   TypeNode::Bool),
+  ];
+
+  // the type a name stands for, names that aren't builtin are left to be resolved later
+  pub fn named_type(name: &str) -> Type {
+    if let Some(&(_, ref node)) = Self::BUILTIN_TYPES.iter().find(|&&(builtin, _)| builtin == name) {
+      return Type::from(node.clone())
+    }
+
+    match IntKind::from_name(name) {
+      Some(kind) => Type::from(TypeNode::Sized(kind)),
+      None       => Type::id(name),
+    }
+  }
+
   fn parse_type(&mut self) -> Result<Type, ()> {
     use self::TokenType::*;
 
     let t = match *self.current_type() {
-      Identifier => match self.eat()?.as_str() {
-        "string" => Type::from(TypeNode::Str),
-        "char"   => Type::from(TypeNode::Char),
-
-        "int"    => Type::from(TypeNode::Int),
-        "float"  => Type::from(TypeNode::Double),
-
-        "bool"   => Type::from(TypeNode::Bool),
-        id       => Type::id(id),
-      },
+      Identifier => Self::named_type(&self.eat()?),
 
       Symbol => match self.current_lexeme().as_str() {
         "(" => {
@@ -650,13 +929,27 @@ impl<'p> Parser<'p> {
           self.eat_lexeme("]")?;
 
           Type::array(t)
-        }
+        },
+
+        "{" => {
+          self.next()?;
+
+          let key = self.parse_type()?;
+
+          self.eat_lexeme(":")?;
+
+          let value = self.parse_type()?;
+
+          self.eat_lexeme("}")?;
+
+          Type::map(key, value)
+        },
 
         _   => return Err(
           response!(
             Wrong(format!("unexpected symbol `{}` in type", self.current_lexeme())),
             self.source.file,
-            self.current_position()
+            self.source.resolve(self.current_position())
           )
         )
       }
@@ -665,7 +958,7 @@ impl<'p> Parser<'p> {
         response!(
           Wrong(format!("expected type found `{}`", self.current_lexeme())),
           self.source.file,
-          self.current_position()
+          self.source.resolve(self.current_position())
         )
       )
     };
@@ -698,7 +991,7 @@ impl<'p> Parser<'p> {
     self.eat_lexeme(delimeters.1)?;
 
     if !block_tokens.is_empty() {
-      let mut parser = Parser::new(block_tokens, self.source);
+      let mut parser = Parser { operators: self.operators.clone(), ..Parser::new(block_tokens, self.source) };
       let mut block  = Vec::new();
 
       while let Some(element) = parse_with(&mut parser)? {
@@ -713,7 +1006,7 @@ impl<'p> Parser<'p> {
 
 
 
-  fn _parse_statement(self: &mut Self) -> Result<Option<Statement<'p>>, ()> {
+  fn _parse_statement(self: &mut Self) -> Result<Option<Statement>, ()> {
     if self.remaining() > 0 {
       Ok(Some(self.parse_statement()?))
     } else {
@@ -721,7 +1014,7 @@ impl<'p> Parser<'p> {
     }
   }
 
-  fn _parse_expression(self: &mut Self) -> Result<Option<Expression<'p>>, ()> {
+  fn _parse_expression(self: &mut Self) -> Result<Option<Expression>, ()> {
     let expression = self.parse_expression()?;
 
     match expression.node {
@@ -730,7 +1023,7 @@ impl<'p> Parser<'p> {
     }
   }
 
-  fn _parse_expression_comma(self: &mut Self) -> Result<Option<Expression<'p>>, ()> {
+  fn _parse_expression_comma(self: &mut Self) -> Result<Option<Expression>, ()> {
     if self.remaining() > 0 && self.current_lexeme() == "\n" {
       self.next()?
     }
@@ -752,7 +1045,40 @@ impl<'p> Parser<'p> {
     expression
   }
 
-  fn _parse_declaration_comma(self: &mut Self) -> Result<Option<Statement<'p>>, ()> {
+  fn _parse_entry_comma(self: &mut Self) -> Result<Option<(Expression, Option<Expression>)>, ()> {
+    if self.remaining() > 0 && self.current_lexeme() == "\n" {
+      self.next()?
+    }
+
+    let key = match Self::_parse_expression(self)? {
+      Some(key) => key,
+      None      => return Ok(None),
+    };
+
+    let value = if self.remaining() > 0 && self.current_lexeme() == ":" {
+      self.next()?;
+
+      Some(self.parse_expression()?)
+    } else {
+      None
+    };
+
+    if self.remaining() > 0 && self.current_lexeme() == "\n" {
+      self.next()?
+    }
+
+    if self.remaining() > 0 {
+      self.eat_lexeme(",")?;
+
+      if self.remaining() > 0 && self.current_lexeme() == "\n" {
+        self.next()?
+      }
+    }
+
+    Ok(Some((key, value)))
+  }
+
+  fn _parse_declaration_comma(self: &mut Self) -> Result<Option<Statement>, ()> {
     if self.remaining() == 0 {
       Ok(None)
     } else {
@@ -797,7 +1123,7 @@ impl<'p> Parser<'p> {
           response!(
             Wrong(format!("expected new line found: `{}`", self.current_lexeme())),
             self.source.file,
-            self.current_position()
+            self.source.resolve(self.current_position())
           )
         )
       }
@@ -826,32 +1152,41 @@ impl<'p> Parser<'p> {
     self.tokens.len().saturating_sub(self.index)
   }
 
-  fn current_position(&self) -> TokenElement<'p> {
+  fn current_position(&self) -> Span {
     let current = self.current();
 
-    TokenElement::Pos(
-      current.line,
-      current.slice
-    )
+    self.source.span(current.line.0, current.slice)
   }
 
-  fn span_from(&self, left_position: TokenElement<'p>) -> TokenElement<'p> {
-    match left_position {
-      TokenElement::Pos(ref line, ref slice) => if let TokenElement::Pos(_, ref slice2) = self.current_position() {
-        TokenElement::Pos(*line, (slice.0, if slice2.1 < line.1.len() { slice2.1 } else { line.1.len() } ))
-      } else {
-        left_position.clone()
+  fn span_from(&self, left_position: Span) -> Span {
+    match self.source.locate(left_position) {
+      Some((line, slice)) => {
+        let length = self.source.lines.get(line - 1).map_or(0, |line| line.len());
+
+        self.source.span(line, (slice.0, self.current().slice.1.min(length)))
+      },
+
+      None => left_position,
+    }
+  }
+
+  // literals never match, so their content can't be mistaken for a symbol
+  fn peek_lexeme(&self, offset: usize) -> Option<&str> {
+    match self.tokens.get(self.index + offset) {
+      Some(token) => match token.token_type {
+        TokenType::Str | TokenType::StrPart | TokenType::StrEnd | TokenType::Char => None,
+        _                                                                         => Some(token.lexeme.as_str()),
       },
 
-      _ => left_position.clone(),
+      None => None,
     }
   }
 
   fn current(&self) -> &'p Token<'p> {
     if self.index > self.tokens.len() - 1 {
-      &self.tokens[self.tokens.len() - 1]
+      self.tokens[self.tokens.len() - 1]
     } else {
-      &self.tokens[self.index]
+      self.tokens[self.index]
     }
   }
 
@@ -873,7 +1208,7 @@ impl<'p> Parser<'p> {
         response!(
           Wrong(format!("expected `{}`, found `{}`", lexeme, self.current_lexeme())),
           self.source.file,
-          self.current_position()
+          self.source.resolve(self.current_position())
         )
       )
     }
@@ -890,33 +1225,27 @@ impl<'p> Parser<'p> {
         response!(
           Wrong(format!("expected `{}`, found `{}`", token_type, self.current_type())),
           self.source.file,
-          self.current_position()
+          self.source.resolve(self.current_position())
         )
       )
     }
   }
 
+  // literals are quoted, so their content can't be mistaken for a symbol
   fn current_lexeme(&self) -> String {
-    self.current().lexeme.clone()
+    let token = self.current();
+
+    match token.token_type {
+      TokenType::Str | TokenType::StrPart | TokenType::StrEnd => format!("{:?}", token.lexeme),
+      TokenType::Char                                         => format!("{:?}", token.lexeme.chars().last().unwrap_or(' ')),
+      _                                                       => token.lexeme.clone(),
+    }
   }
 
   fn current_type(&self) -> &TokenType {
     &self.current().token_type
   }
 
-  fn expect_type(&self, token_type: TokenType) -> Result<(), ()> {
-    if self.current_type() == &token_type {
-      Ok(())
-    } else {
-      Err(
-        response!(
-          Wrong(format!("expected `{}`, found `{}`", token_type, self.current_type())),
-          self.source.file
-        )
-      )
-    }
-  }
-
   fn expect_lexeme(&self, lexeme: &str) -> Result<(), ()> {
     if self.current_lexeme() == lexeme {
       Ok(())
@@ -932,20 +1261,22 @@ impl<'p> Parser<'p> {
 
 
 
-  pub fn fold_expression<'v>(expression: &Expression<'v>) -> Result<Expression<'v>, ()> {
+  pub fn fold_expression(expression: &Expression) -> Result<Expression, ()> {
     use self::ExpressionNode::*;
     use self::Operator::*;
 
     let node = match expression.node {
       Binary(ref left, ref op, ref right) => {
         let node = match (&Self::fold_expression(&*left)?.node, op, &Self::fold_expression(&*right)?.node) {
-          (&Int(ref a),   &Add, &Int(ref b))     => Int(a + b),
+          // overflowing or negative results are left for the vm to report
+          (&Int(a), &Add, &Int(b)) if a.checked_add(b).is_some() => Int(a + b),
+          (&Int(a), &Sub, &Int(b)) if a >= b                     => Int(a - b),
+          (&Int(a), &Mul, &Int(b)) if a.checked_mul(b).is_some() => Int(a * b),
+          (&Int(a), &Div, &Int(b)) if b != 0                     => Int(a / b),
+
           (&Double(ref a), &Add, &Double(ref b)) => Double(a + b),
-          (&Int(ref a),   &Sub, &Int(ref b))     => Int(a - b),
           (&Double(ref a), &Sub, &Double(ref b)) => Double(a - b),
-          (&Int(ref a),   &Mul, &Int(ref b))     => Int(a * b),
           (&Double(ref a), &Mul, &Double(ref b)) => Double(a * b),
-          (&Int(ref a),   &Div, &Int(ref b))     => Int(a / b),
           (&Double(ref a), &Div, &Double(ref b)) => Double(a / b),
 
           _ => expression.node.clone()
@@ -953,7 +1284,7 @@ impl<'p> Parser<'p> {
 
         Expression::new(
           node,
-          expression.pos.clone()
+          expression.pos
         )
       },
 
@@ -962,4 +1293,61 @@ impl<'p> Parser<'p> {
 
     Ok(node)
   }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::super::error;
+
+  fn parse(text: &str) -> (Result<Vec<Statement>, ()>, Vec<error::Diagnostic>) {
+    let source = Source::from("<test>", text.lines().map(|line| line.to_string()).collect());
+
+    error::collect(|| Parser::parse_dialect(&source, &LexerBuilder::new()))
+  }
+
+  #[test]
+  fn empty_character_literals_are_errors() {
+    let (result, diagnostics) = parse("a := 'x'\ne := ''");
+
+    assert!(result.is_err());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "empty character literal");
+    assert_eq!(diagnostics[0].location, Some((2, (6, 8))));
+  }
+
+  #[test]
+  fn malformed_number_literals_are_errors_at_their_span() {
+    let (result, diagnostics) = parse("a := 1\nb := 0b102 + 1");
+
+    assert!(result.is_err());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].message, "invalid digit `2` in base 2 literal `0b102`");
+    assert_eq!(diagnostics[0].location, Some((2, (6, 10))));
+  }
+
+  #[test]
+  fn escapes_out_of_range_are_errors() {
+    let messages = |text| parse(text).1.into_iter().map(|diagnostic| diagnostic.message).collect::<Vec<String>>();
+
+    assert_eq!(messages("a := \"\\u{D800}\""), vec!("`\\u{D800}` isn't a unicode scalar value"));
+    assert_eq!(messages("a := \"\\u{110000}\""), vec!("`\\u{110000}` isn't a unicode scalar value"));
+    assert_eq!(messages("a := \"\\x80\""), vec!("hex escape `\\x80` is out of range, use `\\u{..}` above `\\x7F`"));
+    assert_eq!(messages("a := \"\\q\""), vec!("unexpected escape character: q"));
+  }
+
+  #[test]
+  fn character_literals_hold_one_codepoint() {
+    let (result, diagnostics) = parse("a := 'x'\nb := '\\n'");
+
+    assert!(result.is_ok(), "{:?}", diagnostics.iter().map(|diagnostic| &diagnostic.message).collect::<Vec<_>>());
+
+    let chars = result.unwrap().into_iter().filter_map(|statement| match statement.node {
+      StatementNode::Variable(_, _, Some(Expression { node: ExpressionNode::Char(c), .. })) => Some(c),
+      _                                                                                   => None,
+    }).collect::<Vec<char>>();
+
+    assert_eq!(chars, vec!('x', '\n'));
+  }
+}
@@ -1,10 +1,13 @@
 use std::fs::File;
 use std::io::prelude::*;
+use std::sync::atomic::{ AtomicUsize, Ordering };
 
 use std::fmt;
 
 use colored::Colorize;
 
+use super::lexer::{ TokenElement, TokenType };
+
 #[derive(Debug)]
 pub struct FilePath(pub String);
 
@@ -16,10 +19,34 @@ impl fmt::Display for FilePath {
 
 
 
+// tells sources apart, 0 is no source at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FileId(pub usize);
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+// where something is in a source, counting chars from the start of its lines joined up as the lexer sees them,
+// plain values so whatever holds one can outlive the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+  pub file:  FileId,
+  pub start: usize,
+  pub end:   usize, // exclusive
+}
+
+// a line, and the columns on it, both from 1, as diagnostics and editors count them
+pub type Location = (usize, (usize, usize));
+
+
+
 #[derive(Debug)]
 pub struct Source {
-  pub file:  FilePath,
-  pub lines: Vec<String>,
+  pub id:      FileId,
+  pub file:    FilePath,
+  pub lines:   Vec<String>,
+  pub content: String, // the text as it was read, line endings and all
+
+  starts: Vec<usize>, // where each line starts, and one past the last
 }
 
 impl Source {
@@ -29,16 +56,73 @@ impl Source {
 
     source.read_to_string(&mut content).unwrap();
 
-    Source {
-      file:  FilePath(path),
-      lines: content.lines().map(|x| x.to_string()).collect()
-    }
+    Self::from_text(&path, content)
+  }
+
+  pub fn from_text(path: &str, content: String) -> Self {
+    let lines = content.lines().map(|x| x.to_string()).collect();
+
+    Self::with(path, lines, content)
   }
 
   pub fn from(path: &str, lines: Vec<String>) -> Self {
+    let content = lines.join("\n");
+
+    Self::with(path, lines, content)
+  }
+
+  fn with(path: &str, lines: Vec<String>, content: String) -> Self {
+    let mut starts = vec!(0);
+
+    for line in &lines {
+      let start = starts[starts.len() - 1];
+
+      starts.push(start + line.chars().count() + 1)
+    }
+
     Source {
+      id:   FileId(NEXT_ID.fetch_add(1, Ordering::Relaxed)),
       file: FilePath(path.into()),
       lines,
+      content,
+      starts,
+    }
+  }
+
+
+
+  // the span of columns of a line, both counted from 1 and the last one included
+  pub fn span(&self, line: usize, slice: (usize, usize)) -> Span {
+    let start = self.starts[line.saturating_sub(1).min(self.starts.len() - 1)];
+
+    Span {
+      file:  self.id,
+      start: start + slice.0.saturating_sub(1),
+      end:   start + slice.1,
+    }
+  }
+
+  // the line and columns a span starts at, none for spans of other sources
+  pub fn locate(&self, span: Span) -> Option<Location> {
+    if span.file != self.id {
+      return None
     }
+
+    let line = match self.starts.binary_search(&span.start) {
+      Ok(line)  => line,
+      Err(line) => line - 1,
+    };
+
+    let start = self.starts[line];
+
+    Some((line + 1, (span.start - start + 1, span.end.max(span.start) - start)))
   }
-}
\ No newline at end of file
+
+  // a span as a position responses can show
+  pub fn resolve(&self, span: Span) -> TokenElement<'_> {
+    match self.locate(span) {
+      Some((line, slice)) => TokenElement::Pos((line, self.lines.get(line - 1).map_or("", |line| line.as_str())), slice),
+      None                => TokenElement::Type(TokenType::EOF),
+    }
+  }
+}
@@ -0,0 +1,154 @@
+use std::env;
+use std::fs;
+
+use super::*;
+
+use self::Value::*;
+
+
+
+fn require(allowed: bool, name: &str, capability: &str) -> Result<(), String> {
+  if allowed {
+    Ok(())
+  } else {
+    Err(format!("`io.{}` needs the `{}` capability, which the host didn't grant", name, capability))
+  }
+}
+
+
+
+pub fn module(vm: &mut VirtualMachine) -> Vec<Export> {
+  let int    = || Type::from(TypeNode::Int);
+  let nil    = || Type::from(TypeNode::Nil);
+  let string = || Type::from(TypeNode::Str);
+
+  vec!(
+    native(vm, "io", "read_file", vec!(string()), string(), |vm, args| {
+      require(vm.capabilities.files, "read_file", "files")?;
+
+      let path = str_of(&args[0])?;
+
+      match fs::read_to_string(path) {
+        Ok(content) => Ok(super::string(vm, content)),
+        Err(error)  => Err(format!("can't read `{}`: {}", path, error)),
+      }
+    }),
+
+    native(vm, "io", "write_file", vec!(string(), string()), nil(), |vm, args| {
+      require(vm.capabilities.files, "write_file", "files")?;
+
+      let path = str_of(&args[0])?;
+
+      match fs::write(path, str_of(&args[1])?) {
+        Ok(_)      => Ok(Nil),
+        Err(error) => Err(format!("can't write `{}`: {}", path, error)),
+      }
+    }),
+
+    native(vm, "io", "read_line", vec!(), string(), |vm, _| {
+      require(vm.capabilities.console, "read_line", "console")?;
+
+      match vm.read_line() {
+        Ok(Some(line)) => Ok(super::string(vm, line)),
+        Ok(None)       => Err("reached end of input".to_string()),
+        Err(error)     => Err(format!("can't read input: {}", error)),
+      }
+    }),
+
+    native(vm, "io", "args", vec!(), Type::array(string()), |vm, _| {
+      require(vm.capabilities.process, "args", "process")?;
+
+      let args = vm.args.clone().into_iter().map(|arg| super::string(vm, arg)).collect();
+
+      Ok(array(vm, args))
+    }),
+
+    native(vm, "io", "env", vec!(string()), string(), |vm, args| {
+      require(vm.capabilities.process, "env", "process")?;
+
+      let name = str_of(&args[0])?;
+
+      match env::var(name) {
+        Ok(value) => Ok(super::string(vm, value)),
+        Err(_)    => Err(format!("environment variable `{}` isn't set", name)),
+      }
+    }),
+
+    // stops the program, leaving it to the host to act on the exit code
+    native(vm, "io", "exit", vec!(int()), nil(), |vm, args| {
+      require(vm.capabilities.process, "exit", "process")?;
+
+      let code = int_of(&args[0])?;
+
+      if code < i32::min_value() as i128 || code > i32::max_value() as i128 {
+        return Err(format!("exit code `{}` out of range", code))
+      }
+
+      vm.exit_code = Some(code as i32);
+
+      Ok(Nil)
+    }),
+  )
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::super::super::module::tests::{ run, output };
+  use super::*;
+
+  #[test]
+  fn io_needs_the_capabilities_the_host_grants() {
+    let (result, messages) = run(&mut VirtualMachine::new(), "import \"io\"\na := io.read_file(\"/\")");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("`io.read_file` needs the `files` capability, which the host didn't grant".to_string()));
+
+    let mut vm = VirtualMachine::new();
+
+    vm.capabilities.files = true;
+
+    let (result, messages) = run(&mut vm, "import \"io\"\na := io.env(\"HOME\")");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("`io.env` needs the `process` capability, which the host didn't grant".to_string()));
+  }
+
+  #[test]
+  fn files_are_written_and_read_back() {
+    let path = env::temp_dir().join(format!("snek-io-{}.txt", std::process::id()));
+
+    let mut vm = VirtualMachine::new();
+
+    vm.capabilities = Capabilities::all();
+
+    let program = format!(
+      "import \"io\"\nio.write_file(\"{0}\", \"hé\\nthere\")\na := io.read_file(\"{0}\")\nb := try {{ io.read_file(\"{0}.missing\") }} catch e {{ \"missing\" }}",
+      path.display()
+    );
+
+    let (result, messages, _) = output(&mut vm, &program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "hé\nthere");
+    assert_eq!(str_of(&vm.get_global("a").unwrap()), Ok("hé\nthere"));
+    assert_eq!(str_of(&vm.get_global("b").unwrap()), Ok("missing"));
+
+    fs::remove_file(&path).unwrap()
+  }
+
+  #[test]
+  fn exiting_stops_the_program_with_its_code() {
+    let mut vm = VirtualMachine::new();
+
+    vm.capabilities = Capabilities::all();
+
+    let (_, messages, printed) = output(&mut vm, "import \"io\"\nprintln(\"before\")\nio.exit(3)\nprintln(\"after\")");
+
+    assert!(messages.is_empty(), "{:?}", messages);
+    assert_eq!(printed, "before\n");
+    assert_eq!(vm.exit_code, Some(3));
+  }
+}
@@ -0,0 +1,128 @@
+use std::f64::consts;
+
+use super::*;
+
+use self::Value::*;
+
+
+
+// float to int, failing when it doesn't fit
+fn to_int(n: f64) -> Result<Value, String> {
+  if n.is_finite() && n >= i128::min_value() as f64 && n < i128::max_value() as f64 {
+    Ok(Int(n as i128))
+  } else {
+    Err(format!("can't convert `{:?}` to int", n))
+  }
+}
+
+fn gcd(a: i128, b: i128) -> Result<i128, String> {
+  if b == 0 {
+    a.checked_abs().ok_or(format!("integer overflow in `gcd({}, 0)`", a))
+  } else {
+    gcd(b, a % b)
+  }
+}
+
+
+
+pub fn module(vm: &mut VirtualMachine) -> Vec<Export> {
+  let int   = || Type::from(TypeNode::Int);
+  let float = || Type::from(TypeNode::Double);
+
+  vec!(
+    constant("pi",  TypeNode::Double, Double(consts::PI)),
+    constant("tau", TypeNode::Double, Double(2.0 * consts::PI)),
+    constant("e",   TypeNode::Double, Double(consts::E)),
+
+    native(vm, "math", "sqrt",  vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.sqrt()))),
+    native(vm, "math", "abs",   vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.abs()))),
+    native(vm, "math", "sin",   vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.sin()))),
+    native(vm, "math", "cos",   vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.cos()))),
+    native(vm, "math", "tan",   vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.tan()))),
+    native(vm, "math", "asin",  vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.asin()))),
+    native(vm, "math", "acos",  vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.acos()))),
+    native(vm, "math", "atan",  vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.atan()))),
+    native(vm, "math", "log",   vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.ln()))),
+    native(vm, "math", "log2",  vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.log2()))),
+    native(vm, "math", "log10", vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.log10()))),
+    native(vm, "math", "exp",   vec!(float()), float(), |_, args| Ok(Double(float_of(&args[0])?.exp()))),
+
+    native(vm, "math", "atan2", vec!(float(), float()), float(), |_, args| Ok(Double(float_of(&args[0])?.atan2(float_of(&args[1])?)))),
+    native(vm, "math", "min",   vec!(float(), float()), float(), |_, args| Ok(Double(float_of(&args[0])?.min(float_of(&args[1])?)))),
+    native(vm, "math", "max",   vec!(float(), float()), float(), |_, args| Ok(Double(float_of(&args[0])?.max(float_of(&args[1])?)))),
+
+    native(vm, "math", "floor", vec!(float()), int(), |_, args| to_int(float_of(&args[0])?.floor())),
+    native(vm, "math", "ceil",  vec!(float()), int(), |_, args| to_int(float_of(&args[0])?.ceil())),
+    native(vm, "math", "round", vec!(float()), int(), |_, args| to_int(float_of(&args[0])?.round())),
+    native(vm, "math", "trunc", vec!(float()), int(), |_, args| to_int(float_of(&args[0])?.trunc())),
+
+    native(vm, "math", "to_float", vec!(int()), float(), |_, args| Ok(Double(int_of(&args[0])? as f64))),
+
+    native(vm, "math", "iabs", vec!(int()), int(), |_, args| {
+      let n = int_of(&args[0])?;
+
+      n.checked_abs().map(Int).ok_or(format!("integer overflow in `iabs({})`", n))
+    }),
+
+    native(vm, "math", "sign", vec!(int()), int(), |_, args| Ok(Int(int_of(&args[0])?.signum()))),
+    native(vm, "math", "imin", vec!(int(), int()), int(), |_, args| Ok(Int(int_of(&args[0])?.min(int_of(&args[1])?)))),
+    native(vm, "math", "imax", vec!(int(), int()), int(), |_, args| Ok(Int(int_of(&args[0])?.max(int_of(&args[1])?)))),
+    native(vm, "math", "gcd",  vec!(int(), int()), int(), |_, args| gcd(int_of(&args[0])?, int_of(&args[1])?).map(Int)),
+  )
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::super::super::module::tests::run;
+  use super::*;
+
+  #[test]
+  fn math_functions_take_and_give_their_types() {
+    let mut vm = VirtualMachine::new();
+
+    let program = "import \"math\"\na := math.sqrt(16.0)\nb := math.floor(2.7)\nc := math.gcd(12, 18)\nd := math.max(math.pi, 3.0)";
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("a"), Some(Double(4.0)));
+    assert_eq!(vm.get_global("b"), Some(Int(2)));
+    assert_eq!(vm.get_global("c"), Some(Int(6)));
+    assert_eq!(vm.get_global("d"), Some(Double(consts::PI)));
+
+    let (result, messages) = run(&mut VirtualMachine::new(), "import \"math\"\na := math.iabs(2.5)");
+
+    assert!(result.is_err(), "{:?}", messages);
+  }
+
+  #[test]
+  fn floats_too_large_for_an_int_fail_to_convert() {
+    let (result, messages) = run(&mut VirtualMachine::new(), "import \"math\"\na := math.floor(1e300)");
+
+    assert!(result.is_err());
+    assert_eq!(messages, vec!("can't convert `1e300` to int".to_string()));
+  }
+
+  #[test]
+  fn powers_are_taken_of_ints_and_floats() {
+    let mut vm = VirtualMachine::new();
+
+    let (result, messages) = run(&mut vm, "a := 2 ^ 10\nb := 2.0 ^ 0.5");
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("a"), Some(Int(1024)));
+    assert_eq!(vm.get_global("b"), Some(Double(2f64.sqrt())));
+
+    let (_, messages) = run(&mut VirtualMachine::new(), "a := 2 ^ 200");
+
+    assert_eq!(messages, vec!("integer overflow in `2 ^ 200`".to_string()));
+
+    let (_, messages) = run(&mut VirtualMachine::new(), "a := 2 ^ (0 - 1)");
+
+    assert_eq!(messages, vec!("negative exponent in `2 ^ -1`".to_string()));
+  }
+}
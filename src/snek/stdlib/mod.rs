@@ -0,0 +1,88 @@
+pub mod math;
+pub mod string;
+pub mod io;
+
+use std::sync::Arc;
+
+use super::visitor::*;
+use super::interpreter::*;
+
+
+
+// a name exported by a native module, with its type and value
+pub type Export = (String, Type, Value);
+
+// the exports of the standard module `name`, if there is one
+pub fn module(vm: &mut VirtualMachine, name: &str) -> Option<Vec<Export>> {
+  match name {
+    "math"   => Some(math::module(vm)),
+    "string" => Some(string::module(vm)),
+    "io"     => Some(io::module(vm)),
+    _        => None,
+  }
+}
+
+
+
+pub fn native(vm: &mut VirtualMachine, module: &str, name: &str, params: Vec<Type>, return_type: Type, function: NativeFunction) -> Export {
+  let value = vm.allocate(
+    HeapValueType::Native(
+      Native {
+        name: format!("{}.{}", module, name),
+        function,
+      }
+    )
+  );
+
+  (name.to_string(), Type::new(TypeNode::Func(params, Arc::new(return_type)), TypeMode::Immutable), value)
+}
+
+pub fn constant(name: &str, t: TypeNode, value: Value) -> Export {
+  (name.to_string(), Type::new(t, TypeMode::Immutable), value)
+}
+
+
+
+pub fn int_of(value: &Value) -> Result<i128, String> {
+  match *value {
+    Value::Int(n) => Ok(n),
+    _             => Err(format!("expected int, found `{}`", value)),
+  }
+}
+
+// int literals are accepted where floats are expected
+pub fn float_of(value: &Value) -> Result<f64, String> {
+  match *value {
+    Value::Double(n) => Ok(n),
+    Value::Int(n)    => Ok(n as f64),
+    _                => Err(format!("expected float, found `{}`", value)),
+  }
+}
+
+pub fn str_of<'a>(value: &Value) -> Result<&'a str, String> {
+  if let Value::HeapValue(pointer) = *value {
+    if let HeapValueType::Str(ref content) = unsafe { &*pointer }.kind {
+      return Ok(content)
+    }
+  }
+
+  Err(format!("expected string, found `{}`", value))
+}
+
+pub fn array_of<'a>(value: &Value) -> Result<&'a Vec<Value>, String> {
+  if let Value::HeapValue(pointer) = *value {
+    if let HeapValueType::Array(ref content) = unsafe { &*pointer }.kind {
+      return Ok(content)
+    }
+  }
+
+  Err(format!("expected array, found `{}`", value))
+}
+
+pub fn string(vm: &mut VirtualMachine, content: String) -> Value {
+  vm.allocate(HeapValueType::Str(content.into_boxed_str()))
+}
+
+pub fn array(vm: &mut VirtualMachine, content: Vec<Value>) -> Value {
+  vm.allocate(HeapValueType::Array(content))
+}
@@ -0,0 +1,158 @@
+use super::*;
+
+use self::Value::*;
+
+
+
+// positions and counts are in chars rather than bytes
+pub fn module(vm: &mut VirtualMachine) -> Vec<Export> {
+  let int     = || Type::from(TypeNode::Int);
+  let float   = || Type::from(TypeNode::Double);
+  let boolean = || Type::from(TypeNode::Bool);
+  let string  = || Type::from(TypeNode::Str);
+
+  vec!(
+    native(vm, "string", "split", vec!(string(), string()), Type::array(string()), |vm, args| {
+      let (content, separator) = (str_of(&args[0])?, str_of(&args[1])?);
+
+      if separator.is_empty() {
+        return Err("can't split on an empty separator".to_string())
+      }
+
+      let parts = content.split(separator).map(|part| super::string(vm, part.to_string())).collect();
+
+      Ok(array(vm, parts))
+    }),
+
+    native(vm, "string", "join", vec!(Type::array(string()), string()), string(), |vm, args| {
+      let separator = str_of(&args[1])?;
+
+      let mut parts = Vec::new();
+
+      for part in array_of(&args[0])? {
+        parts.push(str_of(part)?)
+      }
+
+      Ok(super::string(vm, parts.join(separator)))
+    }),
+
+    native(vm, "string", "trim", vec!(string()), string(), |vm, args| {
+      let trimmed = str_of(&args[0])?.trim().to_string();
+
+      Ok(super::string(vm, trimmed))
+    }),
+
+    native(vm, "string", "starts_with", vec!(string(), string()), boolean(), |_, args| {
+      Ok(Bool(str_of(&args[0])?.starts_with(str_of(&args[1])?)))
+    }),
+
+    native(vm, "string", "ends_with", vec!(string(), string()), boolean(), |_, args| {
+      Ok(Bool(str_of(&args[0])?.ends_with(str_of(&args[1])?)))
+    }),
+
+    // the char index of the first occurrence, or -1
+    native(vm, "string", "find", vec!(string(), string()), int(), |_, args| {
+      let content = str_of(&args[0])?;
+
+      let index = match content.find(str_of(&args[1])?) {
+        Some(offset) => content[.. offset].chars().count() as i128,
+        None         => -1,
+      };
+
+      Ok(Int(index))
+    }),
+
+    native(vm, "string", "replace", vec!(string(), string(), string()), string(), |vm, args| {
+      let (content, from, to) = (str_of(&args[0])?, str_of(&args[1])?, str_of(&args[2])?);
+
+      if from.is_empty() {
+        return Err("can't replace an empty string".to_string())
+      }
+
+      Ok(super::string(vm, content.replace(from, to)))
+    }),
+
+    native(vm, "string", "to_upper", vec!(string()), string(), |vm, args| {
+      let upper = str_of(&args[0])?.to_uppercase();
+
+      Ok(super::string(vm, upper))
+    }),
+
+    native(vm, "string", "to_lower", vec!(string()), string(), |vm, args| {
+      let lower = str_of(&args[0])?.to_lowercase();
+
+      Ok(super::string(vm, lower))
+    }),
+
+    native(vm, "string", "chars", vec!(string()), Type::array(Type::from(TypeNode::Char)), |vm, args| {
+      let chars = str_of(&args[0])?.chars().map(Char).collect();
+
+      Ok(array(vm, chars))
+    }),
+
+    native(vm, "string", "parse_int", vec!(string()), int(), |_, args| {
+      let content = str_of(&args[0])?;
+
+      content.trim().parse::<i128>().map(Int).map_err(|_| format!("can't parse `{}` as int", content))
+    }),
+
+    native(vm, "string", "parse_float", vec!(string()), float(), |_, args| {
+      let content = str_of(&args[0])?;
+
+      content.trim().parse::<f64>().map(Double).map_err(|_| format!("can't parse `{}` as float", content))
+    }),
+
+    native(vm, "string", "repeat", vec!(string(), int()), string(), |vm, args| {
+      let (content, count) = (str_of(&args[0])?, int_of(&args[1])?);
+
+      if count < 0 || count > isize::max_value() as i128 || content.len().checked_mul(count as usize).is_none_or(|len| len > isize::max_value() as usize) {
+        return Err(format!("can't repeat a string {} times", count))
+      }
+
+      Ok(super::string(vm, content.repeat(count as usize)))
+    }),
+  )
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::super::super::module::tests::{ run, output };
+  use super::*;
+
+  #[test]
+  fn strings_are_split_searched_and_rebuilt_by_chars() {
+    let mut vm = VirtualMachine::new();
+
+    let program = r#"import "string"
+println(string.split("a,b,,c", ","), string.join(["x", "y"], "-"), string.trim("  hé  "))
+println(string.find("héllo", "l"), string.find("abc", "z"), string.replace("aXbX", "X", "."))
+println(string.to_upper("straße"), string.chars("hé"), string.repeat("ab", 3))
+println(string.starts_with("hello", "he"), string.ends_with("hello", "he"))"#;
+
+    let (result, messages, printed) = output(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+    assert_eq!(printed, "[\"a\", \"b\", \"\", \"c\"] x-y hé\n2 -1 a.b.\nSTRASSE ['h', 'é'] ababab\nyes no\n");
+  }
+
+  #[test]
+  fn numbers_are_parsed_or_raise_a_catchable_error() {
+    let mut vm = VirtualMachine::new();
+
+    let program = "import \"string\"\na := string.parse_int(\" 42\")\nb := string.parse_float(\"2.5\")\nc := try { string.parse_int(\"x1\") } catch e { 0 - 1 }";
+
+    let (result, messages) = run(&mut vm, program);
+
+    assert!(result.is_ok(), "{:?}", messages);
+
+    assert_eq!(vm.get_global("a"), Some(Int(42)));
+    assert_eq!(vm.get_global("b"), Some(Double(2.5)));
+    assert_eq!(vm.get_global("c"), Some(Int(-1)));
+
+    let (_, messages) = run(&mut VirtualMachine::new(), "import \"string\"\na := string.repeat(\"ab\", 0 - 2)");
+
+    assert_eq!(messages, vec!("can't repeat a string -2 times".to_string()));
+  }
+}
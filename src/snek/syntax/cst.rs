@@ -0,0 +1,332 @@
+use super::super::lexer::*;
+
+use std::fmt::{ self, Display, Formatter };
+use std::mem;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriviaKind {
+  Whitespace,
+  Comment,
+}
+
+// text the parser never sees, kept around so the source can be written back
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+  pub kind: TriviaKind,
+  pub text: String,
+}
+
+impl Trivia {
+  pub fn is_comment(&self) -> bool {
+    self.kind == TriviaKind::Comment
+  }
+
+  // the text of a `--|` or `---| .. ---` doc comment
+  pub fn doc(&self) -> Option<String> {
+    if !self.is_comment() {
+      return None
+    }
+
+    if let Some(text) = self.text.strip_prefix("--|") {
+      let text = text.trim_end();
+
+      return Some(text.strip_prefix(' ').unwrap_or(text).to_string())
+    }
+
+    let body  = self.text.strip_prefix("---|")?.trim_end();
+    let body  = body.strip_suffix("---").unwrap_or(body);
+    let lines = body.lines().collect::<Vec<&str>>();
+
+    // lines after the first are indented along with the comment
+    let indent = lines.iter().skip(1)
+      .filter(|line| !line.trim().is_empty())
+      .map(|line| line.len() - line.trim_start().len())
+      .min()
+      .unwrap_or(0);
+
+    let text = lines.iter().enumerate().map(|(i, line)| if i == 0 {
+      line.trim()
+    } else {
+      line.get(indent ..).unwrap_or("").trim_end()
+    }).collect::<Vec<&str>>();
+
+    Some(text.join("\n").trim().to_string())
+  }
+}
+
+
+
+#[derive(Debug, Clone)]
+pub struct SyntaxToken<'t> {
+  pub token:    Token<'t>,
+  pub text:     String, // exactly as written, quotes and escapes included
+  pub leading:  Vec<Trivia>,
+  pub trailing: Vec<Trivia>,
+}
+
+impl<'t> SyntaxToken<'t> {
+  pub fn is(&self, token_type: TokenType, text: &str) -> bool {
+    self.token.token_type == token_type && self.text == text
+  }
+
+  pub fn opens(&self) -> bool {
+    self.is_symbol(&["(", "[", "{"]) || self.token.token_type == TokenType::StrPart && !self.resumes_string()
+  }
+
+  pub fn closes(&self) -> bool {
+    self.is_symbol(&[")", "]", "}"]) || self.token.token_type == TokenType::StrEnd
+  }
+
+  pub fn is_symbol(&self, symbols: &[&str]) -> bool {
+    self.token.token_type == TokenType::Symbol && symbols.contains(&self.text.as_str())
+  }
+
+  // a segment of an interpolated string following an embedded expression
+  pub fn resumes_string(&self) -> bool {
+    [TokenType::StrPart, TokenType::StrEnd].contains(&self.token.token_type) && self.text.starts_with('}')
+  }
+}
+
+impl<'t> Display for SyntaxToken<'t> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    for trivia in &self.leading {
+      write!(f, "{}", trivia.text)?
+    }
+
+    write!(f, "{}", self.text)?;
+
+    for trivia in &self.trailing {
+      write!(f, "{}", trivia.text)?
+    }
+
+    Ok(())
+  }
+}
+
+
+
+// tokens between a pair of brackets, or the segments and expressions of an interpolated string
+#[derive(Debug, Clone)]
+pub struct Group<'t> {
+  pub open:     SyntaxToken<'t>,
+  pub children: Vec<SyntaxNode<'t>>,
+  pub close:    Option<SyntaxToken<'t>>, // missing when the source is unbalanced
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxNode<'t> {
+  Token(SyntaxToken<'t>),
+  Group(Group<'t>),
+}
+
+impl<'t> Display for SyntaxNode<'t> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    match *self {
+      SyntaxNode::Token(ref token) => write!(f, "{}", token),
+
+      SyntaxNode::Group(ref group) => {
+        write!(f, "{}", group.open)?;
+
+        for child in &group.children {
+          write!(f, "{}", child)?
+        }
+
+        match group.close {
+          Some(ref close) => write!(f, "{}", close),
+          None            => Ok(()),
+        }
+      },
+    }
+  }
+}
+
+
+
+// a lossless tree of the source, writing it out gives back the text it was built from
+#[derive(Debug, Clone)]
+pub struct Cst<'t> {
+  pub nodes: Vec<SyntaxNode<'t>>,
+  pub end:   Vec<Trivia>, // trivia after the last token
+}
+
+impl<'t> Cst<'t> {
+  pub fn new(source: &'t Source) -> Result<Self, ()> {
+    let mut lexer = Lexer::default(source.content.chars().collect(), source);
+
+    let mut tokens  = Vec::new();
+    let mut leading = Vec::new();
+
+    while let Some(result) = lexer.next_lossless() {
+      let (token, text) = result?;
+
+      let kind = match token.token_type {
+        TokenType::Whitespace => TriviaKind::Whitespace,
+        TokenType::Comment    => TriviaKind::Comment,
+
+        _ => {
+          tokens.push(
+            SyntaxToken {
+              token,
+              text,
+              leading:  mem::take(&mut leading),
+              trailing: Vec::new(),
+            }
+          );
+
+          continue
+        },
+      };
+
+      let trivia = Trivia { kind, text };
+
+      // trivia on the line of a token belongs to it, the rest to whatever comes next
+      match tokens.last_mut() {
+        Some(ref mut last) if leading.is_empty() && last.token.token_type != TokenType::EOL => last.trailing.push(trivia),
+        _                                                                                     => leading.push(trivia),
+      }
+    }
+
+    let mut tokens = tokens.into_iter();
+    let nodes      = Self::group(&mut tokens, None);
+
+    Ok(
+      Cst {
+        nodes,
+        end: leading,
+      }
+    )
+  }
+
+  // every token in source order, the brackets of groups included
+  pub fn tokens(&self) -> Vec<&SyntaxToken<'t>> {
+    fn flatten<'a, 't>(nodes: &'a [SyntaxNode<'t>], tokens: &mut Vec<&'a SyntaxToken<'t>>) {
+      for node in nodes {
+        match *node {
+          SyntaxNode::Token(ref token) => tokens.push(token),
+
+          SyntaxNode::Group(ref group) => {
+            tokens.push(&group.open);
+            flatten(&group.children, tokens);
+
+            if let Some(ref close) = group.close {
+              tokens.push(close)
+            }
+          },
+        }
+      }
+    }
+
+    let mut tokens = Vec::new();
+
+    flatten(&self.nodes, &mut tokens);
+
+    tokens
+  }
+
+  // nests tokens into groups, until the token closing the current one
+  fn group<I: Iterator<Item = SyntaxToken<'t>>>(tokens: &mut I, closing: Option<(TokenType, &str)>) -> Vec<SyntaxNode<'t>> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = tokens.next() {
+      if let Some((ref token_type, text)) = closing {
+        if token.token.token_type == *token_type && (text.is_empty() || token.text == text) {
+          nodes.push(SyntaxNode::Token(token));
+
+          return nodes
+        }
+      }
+
+      let closing = match (&token.token.token_type, token.text.as_str()) {
+        (&TokenType::Symbol,  "(")                          => Some((TokenType::Symbol, ")")),
+        (&TokenType::Symbol,  "[")                          => Some((TokenType::Symbol, "]")),
+        (&TokenType::Symbol,  "{")                          => Some((TokenType::Symbol, "}")),
+        (&TokenType::StrPart, _) if !token.resumes_string() => Some((TokenType::StrEnd, "")),
+
+        _ => None,
+      };
+
+      match closing {
+        Some(closing) => {
+          let mut children = Self::group(tokens, Some(closing.clone()));
+
+          let close = match children.last() {
+            Some(&SyntaxNode::Token(ref last)) => last.token.token_type == closing.0 && (closing.1.is_empty() || last.text == closing.1),
+            _                                  => false,
+          };
+
+          let close = if close {
+            match children.pop() {
+              Some(SyntaxNode::Token(token)) => Some(token),
+              _                              => None,
+            }
+          } else {
+            None
+          };
+
+          nodes.push(
+            SyntaxNode::Group(
+              Group {
+                open: token,
+                children,
+                close,
+              }
+            )
+          )
+        },
+
+        None => nodes.push(SyntaxNode::Token(token)),
+      }
+    }
+
+    nodes
+  }
+}
+
+impl<'t> Display for Cst<'t> {
+  fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    for node in &self.nodes {
+      write!(f, "{}", node)?
+    }
+
+    for trivia in &self.end {
+      write!(f, "{}", trivia.text)?
+    }
+
+    Ok(())
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TEXT: &str = "--| doubles it\ndouble := fun(x: int) -> int {  -- on its own line\n  x * 2\t\n}\n\n---\nblock comment\n---\nname := \"snek\"\ngreeting := \"hi {name}, {double(2) + 1}!\"\nxs := [1, 2,\n  3]  \n";
+
+  fn round_trip(text: &str) -> String {
+    let source = Source::from_text("<test>", text.to_string());
+
+    Cst::new(&source).map(|cst| cst.to_string()).unwrap_or_default()
+  }
+
+  #[test]
+  fn writes_back_the_text_it_was_built_from() {
+    assert_eq!(round_trip(TEXT), TEXT);
+    assert_eq!(round_trip(TEXT.trim_end()), TEXT.trim_end());
+  }
+
+  #[test]
+  fn keeps_crlf_line_endings() {
+    let text = TEXT.replace('\n', "\r\n");
+
+    assert_eq!(round_trip(&text), text);
+  }
+
+  #[test]
+  fn keeps_unbalanced_brackets() {
+    let text = "f := fun(x: int) -> int {\n  (x + [1\n";
+
+    assert_eq!(round_trip(text), text);
+  }
+}
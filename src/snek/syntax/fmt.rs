@@ -0,0 +1,476 @@
+use super::*;
+use super::super::lexer::*;
+use super::super::parser::*;
+use super::super::error::Response::Wrong;
+
+use std::sync::Arc;
+
+const INDENT: &str  = "  ";
+const WIDTH:  usize = 100;
+
+// formats a whole file, refusing to when the result wouldn't parse to the same program
+pub fn format_source(source: &Source) -> Result<String, ()> {
+  let before    = shape(source)?;
+  let formatted = Formatter::new().format(&Cst::new(source)?);
+  let output    = Source::from_text(&source.file.0, formatted.clone());
+
+  match shape(&output) {
+    Ok(ref after) if *after == before => Ok(formatted),
+
+    _ => Err(
+      response!(
+        Wrong("formatting would change the meaning of this file, leaving it as is"),
+        source.file
+      )
+    ),
+  }
+}
+
+// the parsed program with every position left out, so layout doesn't count
+pub fn shape(source: &Source) -> Result<Vec<Statement>, ()> {
+  Ok(erase_all(&Parser::parse_source(source)?))
+}
+
+// blank lines at the end of a block parse to an `EOF`, which isn't part of the program
+pub fn erase_all(statements: &[Statement]) -> Vec<Statement> {
+  statements.iter().filter(|statement| match statement.node {
+    StatementNode::Expression(Expression { node: ExpressionNode::EOF, .. }) => false,
+    _                                                                      => true,
+  }).map(erase_statement).collect()
+}
+
+fn nowhere() -> Span {
+  Span::default()
+}
+
+fn erase_statement(statement: &Statement) -> Statement {
+  use self::StatementNode::*;
+
+  let node = match statement.node {
+    Expression(ref expression)            => Expression(erase(expression)),
+    Variable(ref t, ref left, ref right)  => Variable(t.clone(), erase(left), right.as_ref().map(erase)),
+    Constant(ref t, ref left, ref right)  => Constant(t.clone(), erase(left), erase(right)),
+    Assignment(ref left, ref right)       => Assignment(erase(left), erase(right)),
+    For(ref name, ref iterable, ref body) => For(erase(name), erase(iterable), erase(body)),
+    Import(ref path, ref alias)           => Import(path.clone(), alias.clone()),
+    Export(ref statement)                 => Export(Arc::new(erase_statement(statement))),
+    Break                                 => Break,
+    Continue                              => Continue,
+    Return(ref value)                     => Return(value.as_ref().map(|value| Arc::new(erase(value)))),
+  };
+
+  Statement::new(node, nowhere())
+}
+
+fn erase(expression: &Expression) -> Expression {
+  use self::ExpressionNode::*;
+
+  let all = |expressions: &Vec<Expression>| expressions.iter().map(erase).collect::<Vec<_>>();
+
+  let node = match expression.node {
+    Int(n)                               => Int(n),
+    Double(n)                            => Double(n),
+    Str(ref s)                           => Str(s.clone()),
+    Interpolation(ref parts)             => Interpolation(all(parts)),
+    Char(c)                              => Char(c),
+    Bool(b)                              => Bool(b),
+    Identifier(ref name)                 => Identifier(name.clone()),
+    Binary(ref a, ref op, ref b)         => Binary(Arc::new(erase(a)), op.clone(), Arc::new(erase(b))),
+    Block(ref body)                      => Block(erase_all(body)),
+    Set(ref content)                     => Set(all(content)),
+    Cast(ref a, ref t)                   => Cast(Arc::new(erase(a)), t.clone()),
+    Array(ref content)                   => Array(all(content)),
+    Map(ref content)                     => Map(content.iter().map(|&(ref key, ref value)| (erase(key), erase(value))).collect()),
+    Index(ref a, ref index)              => Index(Arc::new(erase(a)), Arc::new(erase(index))),
+    Call(ref callee, ref args)           => Call(Arc::new(erase(callee)), all(args)),
+    Try(ref body, ref name, ref handler) => Try(Arc::new(erase(body)), name.clone(), Arc::new(erase(handler))),
+    EOF                                  => EOF,
+
+    Function(ref generics, ref params, ref t, ref body) => Function(
+      generics.clone(), params.iter().map(erase_statement).collect(), t.clone(), Arc::new(erase(body))
+    ),
+
+    If(ref condition, ref body, ref elses) => If(
+      Arc::new(erase(condition)),
+      Arc::new(erase(body)),
+      elses.as_ref().map(|elses| elses.iter().map(|&(ref condition, ref body, _)| (condition.as_ref().map(erase), erase(body), nowhere())).collect())
+    ),
+  };
+
+  Expression::new(node, nowhere())
+}
+
+
+
+// whether a `(` or `[` right after the token calls or indexes it
+fn callable(token: &SyntaxToken) -> bool {
+  use self::TokenType::*;
+
+  match token.token.token_type {
+    Identifier | Str | StrEnd | Char => true,
+    Keyword                          => token.text == "fun",
+    _                                => token.is_symbol(&[")", "]", "}"]),
+  }
+}
+
+// block comments of a CRLF file end their lines like the rest of the output
+fn comments(trivia: &[Trivia]) -> Vec<String> {
+  trivia.iter().filter(|trivia| trivia.is_comment()).map(|trivia| trivia.text.trim_end().replace("\r\n", "\n")).collect()
+}
+
+
+
+pub struct Formatter {
+  output: String,
+  groups: Vec<usize>, // indentation of the line each open group started on
+  blank:  bool,       // a blank line is waiting for the next line
+  opened: bool,       // the last line ended inside of a new group
+}
+
+impl Formatter {
+  pub fn new() -> Self {
+    Formatter {
+      output: String::new(),
+      groups: Vec::new(),
+      blank:  false,
+      opened: false,
+    }
+  }
+
+  pub fn format(mut self, cst: &Cst) -> String {
+    let eol = SyntaxToken {
+      token:    Token::new(TokenType::EOL, (0, ""), (0, 0), "\n"),
+      text:     "\n".into(),
+      leading:  Vec::new(),
+      trailing: Vec::new(),
+    };
+
+    let mut line = Vec::new();
+
+    for token in Self::break_blocks(cst.tokens(), &eol) {
+      let eol = token.token.token_type == TokenType::EOL;
+
+      line.push(token);
+
+      if eol {
+        self.line(&line);
+        line.clear()
+      }
+    }
+
+    if !line.is_empty() {
+      self.line(&line)
+    }
+
+    for comment in comments(&cst.end) {
+      self.output.push_str(&comment);
+      self.output.push('\n')
+    }
+
+    self.output
+  }
+
+  // a block spread over several lines gets its braces on lines of their own
+  fn break_blocks<'a, 't>(tokens: Vec<&'a SyntaxToken<'t>>, eol: &'a SyntaxToken<'t>) -> Vec<&'a SyntaxToken<'t>> {
+    let mut broken = vec![false; tokens.len()];
+    let mut groups = Vec::new(); // where each open group starts, and whether it has a line break of its own
+
+    for (i, token) in tokens.iter().enumerate() {
+      if token.opens() {
+        groups.push((i, false))
+      } else if token.closes() {
+        if let Some((open, true)) = groups.pop() {
+          if tokens[open].is_symbol(&["{"]) {
+            broken[open] = true;
+            broken[i]    = true
+          }
+        }
+      } else if token.token.token_type == TokenType::EOL {
+        if let Some(group) = groups.last_mut() {
+          group.1 = true
+        }
+      }
+    }
+
+    let mut result: Vec<&SyntaxToken> = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+      let after_break = result.last().is_none_or(|last| last.token.token_type == TokenType::EOL);
+
+      if broken[i] && token.closes() && !after_break {
+        result.push(eol)
+      }
+
+      result.push(token);
+
+      if broken[i] && token.opens() && tokens.get(i + 1).is_some_and(|next| next.token.token_type != TokenType::EOL) {
+        result.push(eol)
+      }
+    }
+
+    result
+  }
+
+  fn line(&mut self, line: &[&SyntaxToken]) {
+    let content = match line.last() {
+      Some(last) if last.token.token_type == TokenType::EOL => &line[.. line.len() - 1],
+      _                                                     => line,
+    };
+
+    // blank lines are squashed into one, and dropped at the edges of a group
+    if content.is_empty() && line.iter().all(|token| comments(&token.leading).is_empty()) {
+      self.blank = !self.output.is_empty();
+
+      return
+    }
+
+    let closing = content.first().is_some_and(|token| token.closes());
+
+    if self.blank && !self.opened && !closing {
+      self.output.push('\n')
+    }
+
+    self.blank = false;
+
+    let level = match self.groups.last() {
+      Some(&level) if closing => level,
+      Some(&level)            => level + 1,
+      None                    => 0,
+    };
+
+    if content.is_empty() {
+      let text = comments(&line[0].leading).join(" ");
+
+      self.push(level, &text)
+    } else {
+      self.layout(content, level)
+    }
+
+    for token in content {
+      if token.opens() {
+        self.groups.push(level)
+      } else if token.closes() {
+        self.groups.pop();
+      }
+    }
+
+    self.opened = content.last().is_some_and(|token| token.opens())
+  }
+
+  // puts the tokens on one line, or spreads the arguments of a call over several if it gets too long
+  fn layout(&mut self, tokens: &[&SyntaxToken], level: usize) {
+    let text = Self::render(tokens);
+
+    if INDENT.len() * level + text.chars().count() <= WIDTH {
+      return self.push(level, &text)
+    }
+
+    match Self::arguments(tokens) {
+      Some((open, close, commas)) => {
+        self.layout(&tokens[..= open], level);
+
+        let mut start = open + 1;
+
+        for end in commas.into_iter().map(|comma| comma + 1).chain(Some(close)) {
+          if start < end {
+            self.layout(&tokens[start .. end], level + 1)
+          }
+
+          start = end
+        }
+
+        self.layout(&tokens[close ..], level)
+      },
+
+      None => self.push(level, &text),
+    }
+  }
+
+  fn push(&mut self, level: usize, text: &str) {
+    for _ in 0 .. level {
+      self.output.push_str(INDENT)
+    }
+
+    self.output.push_str(text.trim_end());
+    self.output.push('\n')
+  }
+
+  // the first call on the line with more than one argument, as the position of its parens and commas
+  fn arguments(tokens: &[&SyntaxToken]) -> Option<(usize, usize, Vec<usize>)> {
+    let mut strings = 0isize;
+
+    for (open, token) in tokens.iter().enumerate() {
+      match token.token.token_type {
+        TokenType::StrPart if !token.resumes_string() => strings += 1,
+        TokenType::StrEnd                             => strings -= 1,
+        _                                             => (),
+      }
+
+      // parameters and interpolated expressions can't be broken up
+      if strings > 0 || open == 0 || !token.is_symbol(&["("]) || !callable(tokens[open - 1]) || tokens[open - 1].text == "fun" {
+        continue
+      }
+
+      let mut depth  = 0;
+      let mut commas = Vec::new();
+
+      for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.opens() {
+          depth += 1
+        } else if token.closes() {
+          depth -= 1;
+
+          if depth == 0 {
+            if !commas.is_empty() {
+              return Some((open, i, commas))
+            }
+
+            break
+          }
+        } else if depth == 1 && token.is_symbol(&[","]) {
+          commas.push(i)
+        }
+      }
+    }
+
+    None
+  }
+
+  fn render(tokens: &[&SyntaxToken]) -> String {
+    let mut text = String::new();
+
+    let mut generics = 0; // inside of `fun<..>`, and 2 right after it
+    let mut typed    = vec![false]; // a `name: type` was seen in the current group, so another `:` starts a value
+
+    for (i, token) in tokens.iter().enumerate() {
+      if i == 0 {
+        for comment in comments(&token.leading) {
+          text.push_str(&comment);
+          text.push(' ')
+        }
+      } else if Self::spaced(tokens[i - 1], token, tokens.get(i + 1).cloned(), generics, *typed.last().unwrap_or(&false)) {
+        text.push(' ')
+      }
+
+      text.push_str(&Self::text(token));
+
+      generics = match generics {
+        0 if token.text == "<" && i > 0 && tokens[i - 1].is(TokenType::Keyword, "fun") => 1,
+        1 if token.text == ">" => 2,
+        1                      => 1,
+        _                      => 0,
+      };
+
+      if token.opens() {
+        typed.push(false)
+      } else if token.closes() {
+        typed.pop();
+      } else if token.is_symbol(&[","]) {
+        if let Some(typed) = typed.last_mut() {
+          *typed = false
+        }
+      } else if token.is_symbol(&[":"]) && i > 0 && !tokens[i - 1].is_symbol(&[":"]) && !tokens.get(i + 1).is_some_and(|next| next.is_symbol(&[":", "="])) {
+        if let Some(typed) = typed.last_mut() {
+          *typed = true
+        }
+      }
+
+      for comment in comments(&token.trailing) {
+        text.push(' ');
+        text.push_str(&comment)
+      }
+    }
+
+    text
+  }
+
+  fn text(token: &SyntaxToken) -> String {
+    match token.token.token_type {
+      TokenType::EOL      => String::new(),
+      TokenType::Operator => Operator::from_str(&token.text).map_or(token.text.clone(), |(op, _)| op.as_str().to_string()),
+      _                   => token.text.clone(),
+    }
+  }
+
+  // whether a space goes between two tokens on the same line
+  fn spaced(prev: &SyntaxToken, token: &SyntaxToken, next: Option<&SyntaxToken>, generics: u8, typed: bool) -> bool {
+    // `:=` and `::`
+    let compound = |token: &SyntaxToken| token.is_symbol(&[":", "="]);
+
+    if !comments(&prev.trailing).is_empty() {
+      return true
+    }
+
+    if prev.token.token_type == TokenType::StrPart || token.resumes_string() {
+      return false
+    }
+
+    if token.is_symbol(&[","]) {
+      return false
+    }
+
+    if prev.is_symbol(&[","]) {
+      return true
+    }
+
+    if prev.is_symbol(&["."]) || token.is_symbol(&["."]) || token.is_symbol(&[")", "]"]) || prev.is_symbol(&["(", "["]) {
+      return false
+    }
+
+    if token.is_symbol(&["}"]) {
+      return !prev.is_symbol(&["{"])
+    }
+
+    if prev.is_symbol(&["{"]) {
+      return true
+    }
+
+    if token.is_symbol(&[":"]) {
+      return !prev.is_symbol(&[":"]) && (next.is_some_and(compound) || typed)
+    }
+
+    if prev.is_symbol(&[":"]) {
+      return !compound(token)
+    }
+
+    // `fun<T, U>(..)` is glued together
+    if prev.is(TokenType::Keyword, "fun") && token.text == "<" || generics == 1 || generics == 2 && token.is_symbol(&["("]) {
+      return false
+    }
+
+    if token.is_symbol(&["(", "["]) {
+      return !callable(prev)
+    }
+
+    true
+  }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::super::error;
+
+  const TEXT: &str = "--| doubles it\ndouble:=fun(x:int) -> int{\n    x*2   -- twice\n}\n\n\n\n---\nblock comment\n---\nname := \"snek\"\ngreeting := \"hi {name}, {double(2)+1}!\"\nxs := [1,2,\n3]\nif double(1) > 1 { println(\"more\") } else { println(\"less\") }\nfor x in xs {\n        println(x)\n}\n";
+
+  fn format(text: &str) -> String {
+    let source = Source::from_text("<test>", text.to_string());
+
+    error::collect(|| format_source(&source)).0.expect("formatting failed")
+  }
+
+  #[test]
+  fn formatting_twice_changes_nothing() {
+    let once = format(TEXT);
+
+    assert_ne!(once, TEXT);
+    assert_eq!(format(&once), once);
+  }
+
+  #[test]
+  fn crlf_formats_like_lf() {
+    assert_eq!(format(&TEXT.replace('\n', "\r\n")), format(TEXT));
+  }
+}
@@ -0,0 +1,239 @@
+use std::collections::{ HashMap, HashSet };
+
+use super::super::lexer::*;
+use super::super::parser::Parser;
+use super::super::visitor::{ Type, TypeNode };
+use super::super::interpreter::IntKind;
+use super::super::lsp::json::Json;
+
+
+
+// what a run of source is, for editors to color it by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+  Keyword,
+  Bool,
+  Number,
+  Str,
+  Char,
+  Operator,
+  Symbol,
+  Type,
+  Function,
+  Value,
+  Comment,
+}
+
+// a run of source on a single line, and what it is
+#[derive(Debug, Clone)]
+pub struct Highlight {
+  pub location: Location,
+  pub class:    Class,
+}
+
+
+
+// classifies every token of a source, with `types` knowing the types of names where they're declared or used,
+// stops at the first token that won't lex, which is reported as usual
+pub fn highlight(source: &Source, types: &HashMap<Location, Type>) -> Vec<Highlight> {
+  let mut lexer      = Lexer::default(source.content.chars().collect(), source);
+  let mut highlights = Vec::new();
+
+  let mut previous: Option<(TokenType, String)> = None;
+  let mut generics: HashSet<String>             = HashSet::new();
+  let mut in_generics                           = false;
+
+  while let Some(Ok((token, text))) = lexer.next_lossless() {
+    let class = match token.token_type {
+      TokenType::Whitespace | TokenType::EOL | TokenType::EOF => continue,
+
+      TokenType::Comment                                       => Class::Comment,
+      TokenType::Keyword                                       => Class::Keyword,
+      TokenType::Bool                                          => Class::Bool,
+      TokenType::Int | TokenType::Double                       => Class::Number,
+      TokenType::Str | TokenType::StrPart | TokenType::StrEnd => Class::Str,
+      TokenType::Char                                          => Class::Char,
+      TokenType::Operator                                      => Class::Operator,
+      TokenType::Symbol                                        => Class::Symbol,
+
+      TokenType::Identifier => {
+        let end = token.slice.0 + text.chars().count() - 1;
+
+        // type parameters, as in `fun<T>`
+        if in_generics {
+          generics.insert(text.clone());
+        }
+
+        match types.get(&(token.line.0, (token.slice.0, end))) {
+          Some(t) if t.node.is_function() => Class::Function,
+          Some(_)                         => Class::Value,
+
+          None => match Parser::named_type(&text).node {
+            TypeNode::Id(ref name) if !generics.contains(name) => Class::Value,
+            _                                                   => Class::Type,
+          },
+        }
+      },
+    };
+
+    if token.token_type != TokenType::Comment {
+      let keyword = |text: &str| previous.as_ref().is_some_and(|&(ref token_type, ref previous)| *token_type == TokenType::Keyword && previous == text);
+
+      if token.token_type == TokenType::Operator && text == "<" && keyword("fun") {
+        in_generics = true
+      } else if token.token_type == TokenType::Operator && text == ">" {
+        in_generics = false
+      }
+
+      previous = Some((token.token_type.clone(), text.clone()))
+    }
+
+    // tokens running over several lines are cut up at the line breaks
+    let (mut line, mut start) = (token.line.0, token.slice.0);
+    let mut length            = 0;
+
+    for c in text.chars().chain(Some('\n')) {
+      match c {
+        '\n' => {
+          if length > 0 {
+            highlights.push(
+              Highlight {
+                location: (line, (start, start + length - 1)),
+                class,
+              }
+            )
+          }
+
+          line  += 1;
+          start  = 1;
+          length = 0
+        },
+
+        '\r' => (),
+        _    => length += 1,
+      }
+    }
+  }
+
+  highlights
+}
+
+
+
+// a TextMate grammar matching what the lexer does, from the very lists it matches with
+pub fn textmate_grammar() -> String {
+  let name_char = "[[:alnum:]_!?]";
+
+  let words = |words: &[&str]| {
+    let mut words = words.iter().map(|word| escape_regex(word)).collect::<Vec<String>>();
+
+    // longer words first, so none is cut short by another it starts with
+    words.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    words.join("|")
+  };
+
+  let alphabetic = KEYWORDS.iter().cloned().filter(|keyword| keyword.chars().all(char::is_alphabetic)).collect::<Vec<&str>>();
+  let symbolic   = KEYWORDS.iter().cloned().filter(|keyword| !keyword.chars().all(char::is_alphabetic)).collect::<Vec<&str>>();
+
+  let sizes = IntKind::ALL.iter().map(|kind| kind.to_string()).collect::<Vec<String>>();
+  let types = Parser::BUILTIN_TYPES.iter().map(|&(name, _)| name.to_string()).chain(sizes.iter().cloned())
+    .filter(|name| match Parser::named_type(name).node {
+      TypeNode::Id(_) => false,
+      _               => true,
+    })
+    .collect::<Vec<String>>();
+
+  let sizes = sizes.iter().map(|size| size.as_str()).collect::<Vec<&str>>();
+  let types = types.iter().map(|name| name.as_str()).collect::<Vec<&str>>();
+
+  let symbols = SYMBOLS.iter().map(|symbol| escape_regex(&symbol.to_string())).collect::<String>();
+
+  let rule = |name: &str, pattern: String| Json::object(vec!(
+    ("name",  Json::str(name)),
+    ("match", Json::Str(pattern)),
+  ));
+
+  let region = |name: &str, begin: &str, end: &str, patterns: Vec<Json>| Json::object(vec!(
+    ("name",     Json::str(name)),
+    ("begin",    Json::str(begin)),
+    ("end",      Json::str(end)),
+    ("patterns", Json::Array(patterns)),
+  ));
+
+  let include = |name: &str| Json::object(vec!(("include", Json::Str(format!("#{}", name)))));
+
+  let escapes       = rule("constant.character.escape.snek", r"\\(?:u\{[0-9a-fA-F]{1,6}\}|x[0-9a-fA-F]{2}|.)".to_string());
+  let interpolation = Json::object(vec!(
+    ("name",          Json::str("meta.interpolation.snek")),
+    ("begin",         Json::str(r"\{")),
+    ("end",           Json::str(r"\}")),
+    ("beginCaptures", Json::object(vec!(("0", Json::object(vec!(("name", Json::str("punctuation.section.interpolation.begin.snek")))))))),
+    ("endCaptures",   Json::object(vec!(("0", Json::object(vec!(("name", Json::str("punctuation.section.interpolation.end.snek")))))))),
+    ("patterns",      Json::Array(vec!(Json::object(vec!(("include", Json::str("$self"))))))),
+  ));
+
+  let repository = Json::object(vec!(
+    ("comments", Json::object(vec!(("patterns", Json::Array(vec!(
+      region("comment.block.documentation.snek", r"---\|", "---", Vec::new()),
+      region("comment.block.snek",               "---",    "---", Vec::new()),
+      rule("comment.line.documentation.snek", r"--\|.*$".to_string()),
+      rule("comment.line.double-dash.snek",   "--.*$".to_string()),
+    )))))),
+
+    ("strings", Json::object(vec!(("patterns", Json::Array(vec!(
+      region("string.quoted.raw.snek",    &format!(r#"(?<!{})r(#*)""#, name_char), r#""\1"#, Vec::new()),
+      region("string.quoted.triple.snek", r#"""""#, r#"""""#, vec!(escapes.clone(), interpolation.clone())),
+      region("string.quoted.double.snek", r#"""#,   r#"""#,   vec!(escapes.clone(), interpolation)),
+      rule("string.quoted.single.snek", r"'(?:[^'\\]|\\(?:u\{[0-9a-fA-F]{1,6}\}|x[0-9a-fA-F]{2}|.))'".to_string()),
+    )))))),
+
+    ("numbers", Json::object(vec!(("patterns", Json::Array(vec!(
+      rule(
+        "constant.numeric.snek",
+        format!(
+          r"(?<!{0})(?:0x[0-9a-fA-F_]+|0b[01_]+|0o[0-7_]+|(?:[0-9][0-9_]*)?\.?[0-9][0-9_]*(?:[eE][+-]?[0-9]+)?)(?:{1})?(?!{0})",
+          name_char, words(&sizes)
+        )
+      ),
+    )))))),
+
+    ("keywords", Json::object(vec!(("patterns", Json::Array(vec!(
+      rule("keyword.control.snek",  format!("(?<!{0})(?:{1})(?!{0})", name_char, words(&alphabetic))),
+      rule("keyword.operator.snek", format!("(?:{})", words(&symbolic))),
+      rule("constant.language.boolean.snek", format!("(?<!{0})(?:{1})(?!{0})", name_char, words(BOOLS))),
+    )))))),
+
+    ("types", Json::object(vec!(("patterns", Json::Array(vec!(
+      rule("support.type.snek", format!("(?<!{0})(?:{1})(?!{0})", name_char, words(&types))),
+    )))))),
+
+    ("functions", Json::object(vec!(("patterns", Json::Array(vec!(
+      Json::object(vec!(
+        ("match",    Json::Str(format!(r"(?<!{0})([[:alpha:]_]{0}*)\s*(?=\()", name_char))),
+        ("captures", Json::object(vec!(("1", Json::object(vec!(("name", Json::str("entity.name.function.snek")))))))),
+      )),
+    )))))),
+
+    ("operators", Json::object(vec!(("patterns", Json::Array(vec!(
+      rule("keyword.operator.snek", format!("(?:{})", words(OPERATORS))),
+      rule("punctuation.snek",      format!("[{}]", symbols)),
+    )))))),
+  ));
+
+  let grammar = Json::object(vec!(
+    ("$schema",   Json::str("https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json")),
+    ("name",      Json::str("snek")),
+    ("scopeName", Json::str("source.snek")),
+    ("fileTypes", Json::Array(vec!(Json::str("snek")))),
+    ("patterns",  Json::Array(
+      ["comments", "strings", "numbers", "keywords", "types", "functions", "operators"].iter().map(|name| include(name)).collect()
+    )),
+    ("repository", repository),
+  ));
+
+  format!("{}\n", grammar)
+}
+
+fn escape_regex(text: &str) -> String {
+  text.chars().map(|c| if r"\^$.|?*+()[]{}-/".contains(c) { format!("\\{}", c) } else { c.to_string() }).collect()
+}
@@ -0,0 +1,395 @@
+use std::ops::Range;
+
+use super::super::lexer::*;
+use super::super::parser::*;
+use super::super::error::{ self, Diagnostic };
+
+
+
+// a run of whole lines lexed and parsed on their own, holding one or more top-level statements,
+// positions in it count lines from its first one
+pub struct Chunk {
+  statements: Vec<Statement>,
+  source:     Source, // what the spans of its statements point into
+
+  starts:      Vec<usize>, // lines, from 0, where statements not sharing a line with the one before start
+  diagnostics: Vec<Diagnostic>,
+  parsed:      bool,
+
+  pub first: usize, // the line of the document it starts at, from 0
+}
+
+impl Chunk {
+  fn new(path: &str, lines: Vec<String>, first: usize) -> Self {
+    let source  = Source::from(path, lines);
+    let content = format!("{}\n", source.lines.join("\n"));
+
+    let (tokens, mut diagnostics) = error::collect(|| {
+      let mut tokens = Vec::new();
+
+      for token_result in Lexer::default(content.chars().collect(), &source) {
+        tokens.push(token_result?)
+      }
+
+      Ok(tokens)
+    });
+
+    let parsed = tokens.is_ok();
+    let tokens = tokens.unwrap_or_else(|()| Vec::new());
+
+    let mut statements = Vec::new();
+    let mut starts     = vec!(0);
+
+    let parsed = parsed && (tokens.is_empty() || {
+      let (ast, parse_diagnostics) = error::collect(|| Parser::new(tokens.iter().collect(), &source).parse_lines());
+
+      diagnostics.extend(parse_diagnostics);
+
+      let mut last = 0;
+
+      for &(_, lines) in ast.iter().flat_map(|ast| ast.iter()) {
+        if let Some((first, end)) = lines {
+          if first > last && last > 0 {
+            starts.push(first - 1)
+          }
+
+          last = last.max(end)
+        }
+      }
+
+      let parsed = ast.is_ok();
+
+      statements = ast.map(|ast| ast.into_iter().map(|(statement, _)| statement).collect()).unwrap_or_default();
+
+      parsed
+    });
+
+    // a chunk that failed to lex or parse is kept as text, with whatever went wrong
+    if !parsed {
+      statements.clear();
+      starts.truncate(1);
+
+      if diagnostics.iter().all(|diagnostic| diagnostic.severity != 1) {
+        diagnostics.push(
+          Diagnostic {
+            message:  "couldn't parse this".to_string(),
+            severity: 1,
+            file:     Some(path.to_string()),
+            location: None,
+          }
+        )
+      }
+    }
+
+    Chunk {
+      statements,
+      source,
+      starts,
+      diagnostics,
+      parsed,
+      first,
+    }
+  }
+
+  pub fn statements(&self) -> &[Statement] {
+    &self.statements
+  }
+
+  pub fn lines(&self) -> &[String] {
+    &self.source.lines
+  }
+
+  pub fn parses(&self) -> bool {
+    self.parsed
+  }
+
+  // whether it ends inside of a block comment, which would go on over whatever follows
+  fn open(&self) -> bool {
+    self.parsed && ends_in_comment(&format!("{}\n", self.source.lines.join("\n")), &self.source)
+  }
+
+  // what went wrong lexing or parsing it, at lines of the document, or at its start when nothing better is known
+  pub fn diagnostics(&self) -> Vec<Diagnostic> {
+    self.diagnostics.iter().cloned().map(|mut diagnostic| {
+      let (line, slice)   = diagnostic.location.unwrap_or((1, (1, 0)));
+      diagnostic.location = Some((line + self.first, slice));
+      diagnostic
+    }).collect()
+  }
+}
+
+
+
+// a text kept as chunks of top-level statements, so an edit only lexes and parses the ones it touches
+pub struct Document {
+  pub path: String,
+  chunks:   Vec<Chunk>,
+}
+
+impl Document {
+  pub fn new(path: &str, text: &str) -> Self {
+    let lines  = text.split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect();
+    let chunks = build(path, lines, 0);
+
+    Document {
+      path: path.to_string(),
+      chunks,
+    }
+  }
+
+  // replaces the text between two positions, as lines and chars from 0, giving the chunks it parsed again
+  pub fn edit(&mut self, start: (usize, usize), end: (usize, usize), text: &str) -> Range<usize> {
+    let (start, end) = if end < start { (end, start) } else { (start, end) };
+
+    let (a, b) = (self.chunk_at(start.0), self.chunk_at(end.0));
+
+    let mut lines = self.chunks[a ..= b].iter().flat_map(|chunk| chunk.lines().iter().cloned()).collect::<Vec<String>>();
+    let offset    = self.chunks[a].first;
+
+    // clamped to the text there is
+    let clamp = |(line, column): (usize, usize), lines: &[String]| {
+      let line = line.saturating_sub(offset);
+
+      if line >= lines.len() {
+        return (lines.len() - 1, lines[lines.len() - 1].len())
+      }
+
+      (line, lines[line].char_indices().nth(column).map_or(lines[line].len(), |(i, _)| i))
+    };
+
+    let (start, end) = (clamp(start, &lines), clamp(end, &lines));
+
+    let edited = format!("{}{}{}", &lines[start.0][.. start.1], text, &lines[end.0][end.1 ..]);
+    let edited = edited.split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect::<Vec<String>>();
+
+    lines.splice(start.0 ..= end.0, edited);
+
+    let old_length = self.chunks[a ..= b].iter().map(|chunk| chunk.lines().len()).sum::<usize>();
+    let delta      = lines.len() as isize - old_length as isize;
+
+    for chunk in &mut self.chunks[b + 1 ..] {
+      chunk.first = (chunk.first as isize + delta) as usize
+    }
+
+    // the statement before might go on into the edited lines, as an `else` does, and so might the edited ones into the next
+    let before = if a > 0 { Some(a - 1) } else { None };
+    let after  = if b + 1 < self.chunks.len() { Some(b + 1) } else { None };
+
+    let mut attempts = vec!((before.unwrap_or(a), b), (before.unwrap_or(a), after.unwrap_or(b)));
+
+    // a string or comment left open by an edit that failed before may be closed by this one, or close what this one opens,
+    // so the region takes in the nearest chunk that failed on either side, and everything up to it, until none is left
+    let (mut from, mut to) = attempts[1];
+
+    loop {
+      let earlier = self.chunks[.. from].iter().rposition(|chunk| !chunk.parses());
+      let later   = self.chunks[to + 1 ..].iter().position(|chunk| !chunk.parses()).map(|i| to + 1 + i);
+
+      if earlier.is_none() && later.is_none() {
+        break
+      }
+
+      from = earlier.unwrap_or(from);
+      to   = later.unwrap_or(to);
+
+      attempts.push((from, to))
+    }
+
+    let mut i = 0;
+
+    while i < attempts.len() {
+      let (from, to) = attempts[i];
+
+      i += 1;
+
+      if i > 1 && attempts[i - 2] == (from, to) {
+        continue
+      }
+
+      let mut region = self.chunks[from .. a].iter().flat_map(|chunk| chunk.lines().iter().cloned()).collect::<Vec<String>>();
+
+      region.extend(lines.iter().cloned());
+      region.extend(self.chunks[b + 1 ..= to].iter().flat_map(|chunk| chunk.lines().iter().cloned()));
+
+      let chunks = build(&self.path, region, self.chunks[from].first);
+
+      // chunks end outside of comments, but for the last one, so a comment left open takes in the rest of the text
+      if chunks.last().is_some_and(Chunk::open) && to + 1 < self.chunks.len() {
+        attempts.push((from, self.chunks.len() - 1));
+
+        continue
+      }
+
+      if chunks.iter().all(Chunk::parses) {
+        let count = chunks.len();
+
+        self.chunks.splice(from ..= to, chunks);
+
+        return from .. from + count
+      }
+    }
+
+    // the statements around stay as they were, only the edited lines are left to wait for a fix
+    let mut chunk = Chunk::new(&self.path, lines, offset);
+
+    if chunk.open() && b + 1 < self.chunks.len() {
+      chunk.parsed = false;
+
+      chunk.diagnostics.push(
+        Diagnostic {
+          message:  "unterminated block comment".to_string(),
+          severity: 1,
+          file:     Some(self.path.clone()),
+          location: None,
+        }
+      )
+    }
+
+    self.chunks.splice(a ..= b, Some(chunk));
+
+    a .. a + 1
+  }
+
+  // the chunk holding a line, the last one for lines past the end
+  fn chunk_at(&self, line: usize) -> usize {
+    match self.chunks.binary_search_by(|chunk| chunk.first.cmp(&line)) {
+      Ok(index)  => index,
+      Err(index) => index.saturating_sub(1),
+    }
+  }
+
+  pub fn chunks(&self) -> &[Chunk] {
+    &self.chunks
+  }
+
+  pub fn lines(&self) -> Vec<&str> {
+    self.chunks.iter().flat_map(|chunk| chunk.lines().iter().map(|line| line.as_str())).collect()
+  }
+
+  pub fn text(&self) -> String {
+    self.lines().join("\n")
+  }
+
+  pub fn parses(&self) -> bool {
+    self.chunks.iter().all(Chunk::parses)
+  }
+
+  pub fn diagnostics(&self) -> Vec<Diagnostic> {
+    self.chunks.iter().flat_map(Chunk::diagnostics).collect()
+  }
+}
+
+
+
+// whether lexing a text ends inside of a block comment, which runs on to the end of whatever it's lexed along with
+fn ends_in_comment(content: &str, source: &Source) -> bool {
+  let mut lexer = Lexer::default(content.chars().collect(), source);
+  let mut open  = false;
+
+  // the text always ends in a line break, which only an unterminated comment swallows
+  while let Some(Ok((token, text))) = lexer.next_lossless() {
+    open = token.token_type == TokenType::Comment && text.starts_with("---") && !text.ends_with("---")
+  }
+
+  open
+}
+
+// chunks for a run of lines, one for each line a top-level statement starts on
+fn build(path: &str, lines: Vec<String>, first: usize) -> Vec<Chunk> {
+  let whole = Chunk::new(path, lines, first);
+
+  if whole.starts.len() < 2 {
+    return vec!(whole)
+  }
+
+  let ends = whole.starts[1 ..].iter().cloned().chain(Some(whole.lines().len()));
+
+  let mut chunks  = Vec::new();
+  let mut pending = 0;
+
+  // a piece that won't parse alone, as when a block comment ends on the line the next statement starts on, joins the next
+  for end in ends {
+    let chunk = Chunk::new(path, whole.lines()[pending .. end].to_vec(), first + pending);
+
+    if chunk.parses() {
+      chunks.push(chunk);
+      pending = end
+    }
+  }
+
+  if pending < whole.lines().len() {
+    return vec!(whole)
+  }
+
+  chunks
+}
+
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::fmt::{ shape, erase_all };
+
+  const TEXT: &str = "a := 1\nb := 2\nc := a + b\nd := c * 2\n\ne := d - a\nf := \"six\"\ng := e\nh := 5\nk := fun(x: int) -> int {\n  x * 2\n}\n";
+
+  fn matches_whole(document: &Document) -> bool {
+    let parsed = document.chunks().iter().flat_map(|chunk| erase_all(chunk.statements())).collect::<Vec<Statement>>();
+    let whole  = error::collect(|| shape(&Source::from_text("<test>", document.text()))).0;
+
+    document.parses() && whole == Ok(parsed)
+  }
+
+  #[test]
+  fn edits_parse_like_the_whole_file() {
+    let mut document = Document::new("<test>", TEXT);
+
+    assert!(matches_whole(&document));
+
+    document.edit((10, 4), (10, 5), "+");
+    document.edit((8, 5), (8, 6), "5 * 3");
+    document.edit((0, 0), (0, 0), "-- first\n");
+
+    assert!(matches_whole(&document), "{:?}", document.text());
+  }
+
+  #[test]
+  fn a_failed_edit_is_mended_by_a_later_one() {
+    let mut document = Document::new("<test>", TEXT);
+
+    document.edit((1, 0), (1, 0), "s := \"\"\"\n");
+
+    assert!(!document.parses());
+
+    document.edit((8, 0), (8, 0), "\"\"\"\n");
+
+    assert!(matches_whole(&document), "{:?}", document.text());
+  }
+
+  #[test]
+  fn an_opened_block_comment_takes_in_the_chunks_after() {
+    let mut document = Document::new("<test>", "a := 1\nb := 2\nc := 3\nd := 4 ---\ne := 5\n");
+
+    document.edit((0, 0), (0, 0), "--- ");
+
+    assert!(matches_whole(&document), "{:?}", document.text());
+    let declarations = document.chunks().iter()
+      .flat_map(|chunk| chunk.statements())
+      .filter(|statement| match statement.node { StatementNode::Variable(..) => true, _ => false })
+      .count();
+
+    assert_eq!(declarations, 1);
+
+    document.edit((0, 0), (0, 4), "");
+
+    assert!(matches_whole(&document), "{:?}", document.text());
+  }
+
+  #[test]
+  fn crlf_lines_parse_like_lf_ones() {
+    let document = Document::new("<test>", &TEXT.replace('\n', "\r\n"));
+
+    assert_eq!(document.text(), TEXT);
+    assert!(matches_whole(&document));
+  }
+}
@@ -0,0 +1,9 @@
+pub mod cst;
+pub mod fmt;
+pub mod highlight;
+pub mod incremental;
+
+pub use self::cst::*;
+pub use self::fmt::*;
+pub use self::highlight::*;
+pub use self::incremental::*;
@@ -3,12 +3,16 @@ use std::collections::HashMap;
 
 use std::rc::Rc;
 
+use super::super::source::Location;
+
 
 
 #[derive(Clone, Debug)]
 pub struct SymTab {
   pub parent: Option<Rc<SymTab>>,
   pub names:  RefCell<HashMap<String, usize>>,
+
+  pub locations: RefCell<HashMap<String, Location>>, // where names were declared, when known
 }
 
 impl SymTab {
@@ -22,6 +26,8 @@ impl SymTab {
     SymTab {
       parent:  Some(parent),
       names:   RefCell::new(hash_names),
+
+      locations: RefCell::new(HashMap::new()),
     }
   }
 
@@ -29,6 +35,8 @@ impl SymTab {
     SymTab {
       parent:  None,
       names:   RefCell::new(HashMap::new()),
+
+      locations: RefCell::new(HashMap::new()),
     }
   }
 
@@ -39,6 +47,24 @@ impl SymTab {
     new_index
   }
 
+  pub fn locate(&self, name: &str, location: Option<Location>) {
+    if let Some(location) = location {
+      self.locations.borrow_mut().insert(name.to_string(), location);
+    }
+  }
+
+  // where the name in scope was declared
+  pub fn get_location(&self, name: &str) -> Option<Location> {
+    if self.names.borrow().contains_key(name) {
+      return self.locations.borrow().get(name).cloned()
+    }
+
+    match self.parent {
+      Some(ref parent) => parent.get_location(name),
+      None => None,
+    }
+  }
+
   pub fn get_name(&self, name: &str) -> Option<(usize, usize)> {
     self.get_name_internal(name, 0)
   }
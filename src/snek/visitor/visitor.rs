@@ -2,16 +2,22 @@ use super::*;
 use super::super::parser::Parser;
 use super::super::error::Response::Wrong;
 
-use std::fmt::{ self, Formatter, Write, Display };
+use std::fmt::{ self, Formatter, Display };
 
 use std::rc::Rc;
-use std::mem;
+use std::sync::Arc;
+
+use std::collections::{ HashMap, HashSet };
+
+use super::super::module::Module;
+use super::super::interpreter::{ format, IntKind };
 
 
 
 #[derive(Debug, Clone)]
 pub enum TypeNode {
   Int,
+  Sized(IntKind),
   Double,
   Bool,
   Str,
@@ -19,18 +25,36 @@ pub enum TypeNode {
   Nil,
   Id(String),
   Set(Vec<Type>),
-  Array(Rc<Type>),
-  Func(Vec<Type>, Rc<Type>),
+  Array(Arc<Type>),
+  Map(Arc<Type>, Arc<Type>),
+  Func(Vec<Type>, Arc<Type>),
+  Generic(Vec<String>, Arc<Type>), // type parameters of a function type
+  Never,                            // of what never gives a value, as `raise`, so it fits any type
 }
 
 impl TypeNode {
+  pub fn is_sized(&self) -> bool {
+    if let TypeNode::Sized(_) = *self { true } else { false }
+  }
+
+  pub fn is_never(&self) -> bool {
+    if let TypeNode::Never = *self { true } else { false }
+  }
+
+  pub fn is_function(&self) -> bool {
+    match *self {
+      TypeNode::Func(..) | TypeNode::Generic(..) => true,
+      _                                          => false,
+    }
+  }
+
   pub fn check_expression(&self, other: &ExpressionNode) -> bool {
     use self::TypeNode::*;
 
     match *other {
       ExpressionNode::Int(_) => match *self {
-        Int | Double => true,
-        _           => false,
+        Int | Double | Sized(_) => true,
+        _                       => false,
       },
 
       ExpressionNode::Array(ref content) => {
@@ -45,6 +69,11 @@ impl TypeNode {
         true
       },
 
+      ExpressionNode::Map(ref content) => match *self {
+        Map(..) => content.is_empty(),
+        _       => false,
+      },
+
       _ => false
     }
   }
@@ -55,7 +84,10 @@ impl PartialEq for TypeNode {
     use self::TypeNode::*;
 
     match (self, other) {
+      (&Never, _) | (_, &Never) => true,
+
       (&Int,   &Int)   => true,
+      (&Sized(a), &Sized(b)) => a == b,
       (&Double, &Double) => true,
 
       (&Bool, &Bool) => true,
@@ -64,9 +96,13 @@ impl PartialEq for TypeNode {
       (&Nil,  &Nil)  => true,
 
       (&Array(ref a), &Array(ref b)) => a == b,
+      (&Map(ref a, ref b), &Map(ref c, ref d)) => a == c && b == d,
       (&Id(ref a), &Id(ref b))       => a == b,
       (&Set(ref a), &Set(ref b))     => a == b,
 
+      (&Func(ref a, ref b), &Func(ref c, ref d))       => a == c && b == d,
+      (&Generic(ref a, ref b), &Generic(ref c, ref d)) => a == c && b == d,
+
       _                              => false,
     }
   }
@@ -88,15 +124,17 @@ impl Display for TypeNode {
 
     match *self {
       Int              => write!(f, "int"),
-      Double           => write!(f, "double"),
+      Sized(ref kind)  => write!(f, "{}", kind),
+      Double           => write!(f, "float"),
       Bool             => write!(f, "bool"),
       Str              => write!(f, "string"),
       Char             => write!(f, "char"),
       Nil              => write!(f, "nil"),
       Array(ref n)     => write!(f, "[{}]", n),
+      Map(ref k, ref v) => write!(f, "{{{}: {}}}", k, v),
       Id(ref n)        => write!(f, "{}", n),
       Set(ref content) => {
-        write!(f, "(");
+        write!(f, "(")?;
 
         for (index, element) in content.iter().enumerate() {
           if index < content.len() - 1 {
@@ -109,7 +147,7 @@ impl Display for TypeNode {
         write!(f, ")")
       },
       Func(ref params, ref return_type) => {
-        write!(f, "(");
+        write!(f, "(")?;
 
         for (index, element) in params.iter().enumerate() {
           if index < params.len() - 1 {
@@ -121,6 +159,8 @@ impl Display for TypeNode {
 
         write!(f, ") {}", return_type)
       },
+      Generic(ref generics, ref t) => write!(f, "<{}>{}", generics.join(", "), t),
+      Never                        => write!(f, "never"),
     }
   }
 }
@@ -199,11 +239,97 @@ impl Type {
   }
 
   pub fn array(t: Type) -> Type {
-    Type::new(TypeNode::Array(Rc::new(t)), TypeMode::Regular)
+    Type::new(TypeNode::Array(Arc::new(t)), TypeMode::Regular)
+  }
+
+  pub fn map(key: Type, value: Type) -> Type {
+    Type::new(TypeNode::Map(Arc::new(key), Arc::new(value)), TypeMode::Regular)
   }
 
   pub fn function(params: Vec<Type>, return_type: Type) -> Type {
-    Type::new(TypeNode::Func(params, Rc::new(return_type)), TypeMode::Regular)
+    Type::new(TypeNode::Func(params, Arc::new(return_type)), TypeMode::Regular)
+  }
+}
+
+impl Type {
+  // binds type parameters against `other`, failing on conflicting bindings
+  pub fn unify(&self, other: &Type, generics: &[String], bindings: &mut HashMap<String, Type>) -> bool {
+    use self::TypeNode::*;
+
+    match (&self.node, &other.node) {
+      (&Id(ref name), _) if generics.contains(name) => {
+        if let Some(bound) = bindings.get(name) {
+          return bound.node == other.node
+        }
+
+        bindings.insert(name.clone(), Type::from(other.node.clone()));
+
+        true
+      },
+
+      (&Array(ref a), &Array(ref b))           => a.unify(b, generics, bindings),
+      (&Map(ref a, ref b), &Map(ref c, ref d)) => a.unify(c, generics, bindings) && b.unify(d, generics, bindings),
+
+      (&Set(ref a), &Set(ref b)) | (&Func(ref a, _), &Func(ref b, _)) if a.len() == b.len() => {
+        if let (&Func(_, ref a), &Func(_, ref b)) = (&self.node, &other.node) {
+          if !a.unify(b, generics, bindings) {
+            return false
+          }
+        }
+
+        a.iter().zip(b.iter()).all(|(a, b)| a.unify(b, generics, bindings))
+      },
+
+      _ => self.node == other.node,
+    }
+  }
+
+  pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+    use self::TypeNode::*;
+
+    let node = match self.node {
+      Id(ref name) => match bindings.get(name) {
+        Some(t) => t.node.clone(),
+        None    => Id(name.clone()),
+      },
+
+      Array(ref t)              => Array(Arc::new(t.substitute(bindings))),
+      Map(ref key, ref value)   => Map(Arc::new(key.substitute(bindings)), Arc::new(value.substitute(bindings))),
+      Set(ref content)          => Set(content.iter().map(|t| t.substitute(bindings)).collect()),
+      Func(ref params, ref ret) => Func(params.iter().map(|t| t.substitute(bindings)).collect(), Arc::new(ret.substitute(bindings))),
+
+      ref node => node.clone(),
+    };
+
+    Type::new(node, self.mode.clone())
+  }
+
+  pub fn mentions(&self, name: &str) -> bool {
+    use self::TypeNode::*;
+
+    match self.node {
+      Id(ref id)                => id == name,
+      Array(ref t)              => t.mentions(name),
+      Map(ref key, ref value)   => key.mentions(name) || value.mentions(name),
+      Set(ref content)          => content.iter().any(|t| t.mentions(name)),
+      Func(ref params, ref ret) => params.iter().any(|t| t.mentions(name)) || ret.mentions(name),
+      Generic(_, ref t)         => t.mentions(name),
+      _                         => false,
+    }
+  }
+
+  // whether `name` keys a map somewhere in it
+  pub fn keys_with(&self, name: &str) -> bool {
+    use self::TypeNode::*;
+
+    match self.node {
+      Array(ref t)              => t.keys_with(name),
+      Map(ref key, ref value)   => key.node == Id(name.to_string()) || key.keys_with(name) || value.keys_with(name),
+      Set(ref content)          => content.iter().any(|t| t.keys_with(name)),
+      Func(ref params, ref ret) => params.iter().any(|t| t.keys_with(name)) || ret.keys_with(name),
+      Generic(_, ref t)         => t.keys_with(name),
+      _                         => false,
+    }
   }
 }
 
@@ -224,354 +350,406 @@ pub enum FlagContext {
 
 
 
+// what checking learned about the source, for editors asking about it
+#[derive(Debug, Default)]
+pub struct Trace {
+  pub types:       HashMap<Location, Type>,      // of every expression typed
+  pub definitions: HashMap<Location, Location>,  // where each name used was declared
+  pub scopes:      Vec<((usize, usize), usize)>, // lines spanned by the statements of a scope, and its frame
+
+  statements: Vec<(usize, usize)>, // lines of statements in scopes not yet closed, with their depth
+}
+
+impl Trace {
+  // a top-level statement checked before rather than visited, its line still counts toward the outermost scope
+  pub fn top_level(&mut self, line: usize) {
+    self.statements.push((line, 1))
+  }
+}
+
+
+
 pub struct Visitor<'v> {
   pub tabs:       Vec<(SymTab, TypeTab)>,
   pub tab_frames: Vec<(SymTab, TypeTab)>,
 
   pub source:  &'v Source,
-  pub ast:     &'v Vec<Statement<'v>>,
+  pub ast:     &'v Vec<Statement>,
+  pub imports: HashMap<String, Rc<Module>>,
 
-  pub flag: Option<FlagContext>,
+  pub flag:  Option<FlagContext>,
+  pub trace: Option<Trace>, // only kept when asked for
+
+  literals: HashMap<Span, IntKind>,  // int literals given a sized type, by where they are
+  builtins: HashSet<Span>,           // names called as built-in operations, where they weren't shadowed
+  keys:     Vec<String>,              // type parameters keying maps in the signatures being checked, so hashable wherever they're bound
 }
 
 impl<'v> Visitor<'v> {
-  pub fn new(source: &'v Source, ast: &'v Vec<Statement<'v>>) -> Self {
+  pub fn new(source: &'v Source, ast: &'v Vec<Statement>) -> Self {
     Visitor {
       tabs:       vec!((SymTab::global(), TypeTab::global())),
       tab_frames: Vec::new(), // very intelligent hack
 
       source,
       ast,
+      imports: HashMap::new(),
+
+      flag:  None,
+      trace: None,
 
-      flag: None,
+      literals: HashMap::new(),
+      builtins: HashSet::new(),
+      keys:     Vec::new(),
     }
   }
 
   pub fn visit(&mut self) -> Result<(), ()> {
+    self.declare_functions()?;
+
     for statement in self.ast {
-      self.visit_statement(&statement)?
+      self.visit_statement(statement)?
     }
 
-    self.tab_frames.push(self.tabs.last().unwrap().clone());
+    self.close();
 
     Ok(())
   }
 
-  pub fn visit_statement(&mut self, statement: &'v Statement<'v>) -> Result<(), ()> {
-    use self::StatementNode::*;
-
-    match statement.node {
-      Expression(ref expression) => self.visit_expression(expression),
-
-      Variable(_, ref left, _) => match left.node {
-        ExpressionNode::Identifier(_) | ExpressionNode::Set(_) => {
-          self.visit_variable(&statement.node)
-        },
-        _ => Ok(())
-      },
+  // top-level functions are declared up front, so they can refer to themselves and each other
+  pub fn declare_functions(&mut self) -> Result<(), ()> {
+    let mut hoisted = HashSet::new();
 
-      Constant(_, ref left, _) => match left.node {
-        ExpressionNode::Identifier(_) | ExpressionNode::Set(_) => self.visit_constant(&statement.node),
-        _ => Ok(())
-      },
+    for statement in self.ast {
+      if let Some(name) = self.declare_function(statement)? {
+        hoisted.insert(name);
+      }
+    }
 
-      Assignment(ref left, ref right) => {
-        let left_type  = self.type_expression(left)?;
-        let right_type = self.type_expression(right)?;
+    // a hoisted function is bound before anything runs, so nothing else may take its name
+    let mut declared = HashSet::new();
 
-        if !left_type.node.check_expression(&Parser::fold_expression(right)?.node) && left_type.node != right_type.node {
-          return Err(
-            response!(
-              Wrong(format!("mismatched types, expected type `{}` got `{}`", left_type.node, right_type)),
-              self.source.file,
-              right.pos
+    for statement in self.ast {
+      for left in Self::declared_names(statement) {
+        if let ExpressionNode::Identifier(ref name) = left.node {
+          if hoisted.contains(name.as_str()) && !declared.insert(name.as_str()) {
+            return Err(
+              response!(
+                Wrong(format!("can't redeclare top-level function `{}`", name)),
+                self.source.file,
+                self.source.resolve(left.pos)
+              )
             )
-          )
+          }
         }
+      }
+    }
 
-        Ok(())
+    Ok(())
+  }
+
+  fn declared_names(statement: &'v Statement) -> Vec<&'v Expression> {
+    use self::StatementNode::*;
+
+    match statement.node {
+      Variable(_, ref left, _) | Constant(_, ref left, _) => match left.node {
+        ExpressionNode::Set(ref names) => names.iter().collect(),
+        _                              => vec!(left),
       },
 
-      _ => Ok(())
+      Export(ref statement) => Self::declared_names(statement),
+
+      _ => Vec::new(),
     }
   }
 
-  fn ensure_no_implicit(&self, expression: &'v Expression<'v>) -> Result<(), ()> {
-    use self::ExpressionNode::*;
+  // every top-level statement is checked, the outermost scope is kept as the last frame
+  pub fn close(&mut self) {
+    self.trace_scope();
+    self.tab_frames.push(self.tabs.last().unwrap().clone());
+  }
 
-    match expression.node {
-      Block(ref statements) => if let Some(statement) = statements.last() {
-        if let StatementNode::Expression(ref expression) = statement.node {
-          match expression.node {
+  fn declare_function(&mut self, statement: &'v Statement) -> Result<Option<&'v str>, ()> {
+    use self::StatementNode::*;
 
-            Call(..)   => (),
-            Block(..)  => { self.ensure_no_implicit(expression)?; }
+    let (left, right) = match statement.node {
+      Variable(_, ref left, Some(ref right)) | Constant(_, ref left, ref right) => (left, right),
+      Export(ref statement)                                                     => return self.declare_function(statement),
+      _                                                                         => return Ok(None),
+    };
 
-            If(_, ref expr, _) => self.ensure_no_implicit(&*expr)?,
+    if let (&ExpressionNode::Identifier(ref name), &ExpressionNode::Function(..)) = (&left.node, &right.node) {
+      let function_type = self.type_expression(right)?;
 
-            _ => return Err(
-              response!(
-                Wrong("unexpected expression without context"),
-                self.source.file,
-                expression.pos
-              )
-            )
-          }
-        }
+      let index = self.current_tab().0.add_name(name);
 
-        ()
-      } else {
-        ()
-      },
+      let location = self.source.locate(left.pos);
 
-      Call(..)   => (),
 
-      If(_, ref expr, _) => self.ensure_no_implicit(&*expr)?,
+      self.current_tab().0.locate(name, location);
+      self.current_tab().1.grow();
+      self.current_tab().1.set_type(index, 0, function_type)?;
 
-      _ => return Err(
-        response!(
-          Wrong("unexpected expression without context"),
-          self.source.file,
-          expression.pos
-        )
-      )
+      return Ok(Some(name))
     }
 
-    Ok(())
+    Ok(None)
   }
 
-  fn visit_expression(&mut self, expression: &'v Expression<'v>) -> Result<(), ()> {
-    use self::ExpressionNode::*;
-
-    match expression.node {
-      Identifier(ref name) => if self.current_tab().0.get_name(name).is_none() {
-        Err(
-          response!(
-            Wrong(format!("no such value `{}` in this scope", name)),
-            self.source.file,
-            expression.pos
-          )
-        )
-      } else {
-        Ok(())
-      },
-
-      Set(ref content) => {
-        for expression in content {
-          self.visit_expression(expression)?
-        }
+  // makes a global provided by the host known to the program
+  pub fn declare_global(&mut self, name: &str, t: Type) -> Result<(), ()> {
+    let index = self.tabs[0].0.add_name(name);
 
-        Ok(())
-      },
+    self.tabs[0].1.grow();
+    self.tabs[0].1.set_type(index, 0, t)
+  }
 
-      Block(ref statements) => {
-        self.push_scope();
+  // every name of the outermost scope, which the compiler turns into globals
+  pub fn globals(&self) -> Vec<String> {
+    self.tabs[0].0.names.borrow().keys().cloned().collect()
+  }
 
-        for (i, statement) in statements.iter().enumerate() {
-          if i < statements.len() - 1 {
-            if let StatementNode::Expression(ref expression) = statement.node {
-              self.ensure_no_implicit(expression)?
-            }
-          }
+  // the compiler loads these literals as values of their sized type
+  pub fn literals(&self) -> HashMap<Span, IntKind> {
+    self.literals.clone()
+  }
 
-          self.visit_statement(statement)?
-        }
+  // the compiler emits built-in operations for these calls, and calls whatever the name is bound to otherwise
+  pub fn builtins(&self) -> HashSet<Span> {
+    self.builtins.clone()
+  }
 
-        self.pop_scope();
+  // the type a name of the outermost scope ended up with
+  pub fn global_type(&self, name: &str) -> Result<Type, ()> {
+    match self.tabs[0].0.get_name(name) {
+      Some((index, env_index)) => self.tabs[0].1.get_type(index, env_index),
+      None                     => Err(
+        response!(
+          Wrong(format!("no such value `{}` in this scope", name)),
+          self.source.file
+        )
+      ),
+    }
+  }
 
-        Ok(())
-      },
+  // the names and types a module exposes to its importers
+  pub fn exports(&mut self) -> Result<Vec<(String, Type)>, ()> {
+    let mut exports = Vec::new();
 
-      If(ref condition, ref body, ref elses) => {
-        self.visit_expression(&*condition)?;
+    for statement in self.ast {
+      if let StatementNode::Export(ref statement) = statement.node {
+        let left = match statement.node {
+          StatementNode::Variable(_, ref left, _) | StatementNode::Constant(_, ref left, _) => left,
+          _                                                                                 => continue,
+        };
 
-        let condition_type = self.type_expression(&*condition)?.node;
+        let names = match left.node {
+          ExpressionNode::Set(ref names) => names.iter().collect(),
+          _                              => vec!(left),
+        };
 
-        if condition_type == TypeNode::Bool {
+        for name in names {
+          if let ExpressionNode::Identifier(ref name) = name.node {
+            exports.push((name.clone(), self.global_type(name)?))
+          }
+        }
+      }
+    }
 
-          self.push_scope();
+    Ok(exports)
+  }
 
-          self.visit_expression(body)?;
-          let body_type = self.type_expression(body)?;
+  pub fn visit_statement(&mut self, statement: &'v Statement) -> Result<(), ()> {
+    use self::StatementNode::*;
 
-          self.pop_scope();
+    let depth = self.tabs.len();
 
-          if let &Some(ref elses) = elses {
-            for &(ref maybe_condition, ref body, _) in elses {
-              if let Some(ref condition) = *maybe_condition {
-                let condition_type = self.type_expression(condition)?.node;
+    if let (Some(trace), Some((line, _))) = (self.trace.as_mut(), self.source.locate(statement.pos)) {
+      trace.statements.push((line, depth))
+    }
 
-                if condition_type != TypeNode::Bool {
-                  return Err(
-                    response!(
-                      Wrong(format!("mismatched condition, must be `bool` got `{}`", condition_type)),
-                      self.source.file,
-                      condition.pos
-                    )
-                  )
-                }
-              }
+    match statement.node {
+      Import(ref path, ref alias) => {
+        let module = match self.imports.get(path) {
+          Some(module) => module.clone(),
+          None         => return Err(
+            response!(
+              Wrong(format!("unresolved module `{}`", path)),
+              self.source.file,
+              self.source.resolve(statement.pos)
+            )
+          )
+        };
 
-              self.push_scope();
+        for &(ref name, ref t) in module.exports.iter() {
+          let index = self.current_tab().0.add_name(&format!("{}.{}", alias, name));
 
-              self.visit_expression(body)?;
-              let else_body_type = self.type_expression(body)?;
+          let location = self.source.locate(statement.pos);
 
-              self.pop_scope();
 
-              if body_type != else_body_type {
-                return Err(
-                  response!(
-                    Wrong(format!("mismatched types, expected `{}` got `{}`", body_type, else_body_type)),
-                    self.source.file,
-                    body.pos
-                  )
-                )
-              }
-            }
-          }
+          self.current_tab().0.locate(&format!("{}.{}", alias, name), location);
+          self.current_tab().1.grow();
+          self.current_tab().1.set_type(index, 0, t.clone())?;
+        }
 
-          Ok(())
+        Ok(())
+      },
 
-        } else {
+      Export(ref exported) => {
+        if self.tabs.len() > 1 {
           return Err(
             response!(
-              Wrong(format!("mismatched condition, must be `bool` got `{}`", condition_type)),
+              Wrong("can only export top-level declarations"),
               self.source.file,
-              expression.pos
+              self.source.resolve(statement.pos)
             )
           )
         }
-      },
-
-      Call(ref expression, ref args) => {
-        self.visit_expression(expression)?;
 
-        let expression_type = self.type_expression(expression)?.node;
+        match exported.node {
+          Variable(..) | Constant(..) => self.visit_statement(exported),
 
-        if let TypeNode::Func(ref params, ..) = expression_type {
-          for (index, param) in params.iter().enumerate() {
-            let arg_type = self.type_expression(&args[index])?;
-
-            if !param.node.check_expression(&args[index].node) && param != &arg_type {
-              return Err(
-                response!(
-                  Wrong(format!("mismatched argument, expected `{}` got `{}`", expression_type, arg_type)),
-                  self.source.file,
-                  expression.pos
-                )
-              )
-            }
-          }
-        } else {
-          return Err(
+          _ => Err(
             response!(
-              Wrong(format!("expected function, found `{}`", expression_type)),
+              Wrong("can only export declarations"),
               self.source.file,
-              expression.pos
+              self.source.resolve(statement.pos)
             )
           )
         }
-
-        Ok(())
       },
 
-      Function(ref params, ref return_type, ref body) => {
-        use self::ExpressionNode::*;
-        use self::StatementNode::*;
+      Expression(ref expression) => self.visit_expression(expression),
 
-        let mut param_names = Vec::new();
-        let mut param_types = Vec::new();
+      // returns unwind to the enclosing function, so they're checked against its return type
+      Return(ref value) => {
+        let return_type = match *value {
+          Some(ref value) => {
+            self.visit_expression(value)?;
+            self.type_expression(value)?
+          },
 
-        for param in params {
-          match param.node {
-            Constant(ref t, ref name, _) | Variable(ref t, ref name, _) => if let Identifier(ref name) = name.node {
-              param_names.push(name.clone());
+          None => Type::from(TypeNode::Nil),
+        };
 
-              param_types.push(t.clone());
-            } else {
-              return Err(
-                response!(
-                  Wrong("set parameters are work-in-progress"),
-                  self.source.file,
-                  param.pos
-                )
-              )
-            },
+        if let Some(FlagContext::Block(Some(expected))) = self.flag.clone() {
+          let compatible = match *value {
+            Some(ref value) => self.check_literal(&expected.node, value)?,
+            None            => false,
+          };
 
-            _ => unreachable!()
+          if !compatible && expected != return_type {
+            return Err(
+              response!(
+                Wrong(format!("mismatched return type, expected `{}` got `{}`", expected, return_type)),
+                self.source.file,
+                self.source.resolve(statement.pos)
+              )
+            )
           }
         }
 
-        let parent = self.current_tab().clone();
+        Ok(())
+      },
 
-        self.tabs.push(
-          (
-            SymTab::new(Rc::new(parent.0), &param_names),
-            TypeTab::new(Rc::new(parent.1), &param_types)
-          )
-        );
+      Variable(ref t, ref left, _) => match left.node {
+        ExpressionNode::Identifier(_) | ExpressionNode::Set(_) => {
+          self.visit_type(t, statement.pos)?;
+          self.visit_variable(&statement.node)
+        },
+        _ => Ok(())
+      },
 
-        self.visit_expression(body)?;
-        let body_type = self.type_expression(body)?;
+      Constant(ref t, ref left, _) => match left.node {
+        ExpressionNode::Identifier(_) | ExpressionNode::Set(_) => {
+          self.visit_type(t, statement.pos)?;
+          self.visit_constant(&statement.node)
+        },
+        _ => Ok(())
+      },
 
-        self.pop_scope();
+      For(ref name, ref iterable, ref body) => {
+        self.visit_expression(iterable)?;
 
-        if return_type != &body_type {
-          Err(
+        let iterable_type = self.type_expression(iterable)?;
+
+        let element_type = match iterable_type.node {
+          TypeNode::Array(ref t)    => (**t).clone(),
+          TypeNode::Map(ref key, _) => (**key).clone(),
+
+          _ => return Err(
             response!(
-              Wrong(format!("mismatched return type, expected `{}` got `{}`", return_type, body_type)),
+              Wrong(format!("can't iterate over `{}`", iterable_type)),
               self.source.file,
-              expression.pos
+              self.source.resolve(iterable.pos)
             )
           )
-        } else {
-          Ok(())
-        }
-      },
+        };
 
-      Array(ref content) => {
-        let t = self.type_expression(content.first().unwrap())?;
+        self.push_scope();
 
-        for element in content {
-          let element_type = self.type_expression(element)?;
+        if let ExpressionNode::Identifier(ref variable) = name.node {
+          let index = self.current_tab().0.add_name(variable);
 
-          if !t.node.check_expression(&Parser::fold_expression(element)?.node) && t.node != element_type.node {
-            return Err(
-              response!(
-                Wrong(format!("mismatched types in array, expected `{}` got `{}`", t, element_type)),
-                self.source.file,
-                element.pos
-              )
-            )
-          }
+          let location = self.source.locate(name.pos);
+
+
+          self.current_tab().0.locate(variable, location);
+          self.current_tab().1.grow();
+          self.current_tab().1.set_type(index, 0, element_type)?;
         }
 
+        self.visit_expression(body)?;
+
+        self.pop_scope();
+
         Ok(())
       },
 
-      Index(ref left, ref index) => {
-        let left_type = self.type_expression(left)?;
+      Assignment(ref left, ref right) => {
+        self.visit_expression(left)?;
+        self.visit_expression(right)?;
 
-        if let TypeNode::Array(_) = left_type.node {
-          let index_type = self.type_expression(index)?;
+        let targets = match left.node {
+          ExpressionNode::Set(ref names) => names.iter().collect(),
+          _                              => vec!(left),
+        };
+
+        for target in targets {
+          if let ExpressionNode::Identifier(ref name) = target.node {
+            if let TypeMode::Immutable = self.type_expression(target)?.mode {
+              return Err(
+                response!(
+                  Wrong(format!("can't assign to constant `{}`", name)),
+                  self.source.file,
+                  self.source.resolve(target.pos)
+                )
+              )
+            }
+          }
+        }
 
-          if let TypeNode::Func(_, _) = index_type.node {
+        if let ExpressionNode::Index(ref set, _) = left.node {
+          if let TypeNode::Set(_) = self.type_expression(set)?.node {
             return Err(
               response!(
-                Wrong(format!("can't index with `{}`, must be unsigned integer", index_type)),
+                Wrong("can't assign to element of immutable set"),
                 self.source.file,
-                left.pos
+                self.source.resolve(left.pos)
               )
             )
           }
+        }
 
-        } else {
+        let left_type  = self.type_expression(left)?;
+        let right_type = self.type_expression(right)?;
+
+        if !self.check_literal(&left_type.node, right)? && left_type.node != right_type.node {
           return Err(
             response!(
-              Wrong(format!("can't index `{}`", left_type)),
+              Wrong(format!("mismatched types, expected type `{}` got `{}`", left_type.node, right_type)),
               self.source.file,
-              left.pos
+              self.source.resolve(right.pos)
             )
           )
         }
@@ -583,516 +761,1429 @@ impl<'v> Visitor<'v> {
     }
   }
 
-  fn visit_variable(&mut self, variable: &'v StatementNode) -> Result<(), ()> {
+  // whether every path through an expression ends in an explicit `return`
+  // whether control never reaches past an expression, as it returns or raises on every path
+  fn always_returns(&self, expression: &Expression) -> bool {
     use self::ExpressionNode::*;
 
-    if let &StatementNode::Variable(ref variable_type, ref left, ref right) = variable {
-      match left.node {
-        Identifier(ref name) => {
-          let index = if let Some((index, _)) = self.current_tab().0.get_name(name) {
-            index
-          } else {
-            self.current_tab().0.add_name(name)
-          };
+    match expression.node {
+      Block(ref statements) => statements.iter().any(|statement| match statement.node {
+        StatementNode::Return(_)             => true,
+        StatementNode::Expression(ref inner) => self.always_returns(inner),
+        _                                    => false,
+      }),
+
+      If(_, ref body, ref elses) => self.always_returns(body) && match *elses {
+        Some(ref elses) => elses.iter().any(|&(ref condition, ..)| condition.is_none()) && elses.iter().all(|&(_, ref body, _)| self.always_returns(body)),
+        None            => false,
+      },
 
-          self.current_tab().1.grow();
+      Try(ref body, _, ref handler) => self.always_returns(body) && self.always_returns(handler),
 
-          if let &Some(ref right) = right {
-            let right_type = self.type_expression(&right)?;
+      Call(ref called, _) => match called.node {
+        Identifier(ref name) => name == "raise" && self.builtins.contains(&called.pos),
+        _                    => false,
+      },
 
-            match right.node {
-              Function(..) | Block(_) | If(..) => (),
-              _ => self.visit_expression(right)?,
-            }
+      _ => false,
+    }
+  }
 
-            if variable_type.node != TypeNode::Nil {
-              if !variable_type.node.check_expression(&Parser::fold_expression(right)?.node) && variable_type.node != right_type.node {
-                return Err(
-                  response!(
-                    Wrong(format!("mismatched types, expected type `{}` got `{}`", variable_type.node, right_type)),
-                    self.source.file,
-                    right.pos
-                  )
-                )
-              } else {
-                self.current_tab().1.set_type(index, 0, variable_type.to_owned())?;
-              }
+  // whether a block ends in something giving a value, rather than a declaration or an `if` without `else`,
+  // or never gets to its end
+  fn ends_in_value(&self, expression: &Expression) -> bool {
+    use self::ExpressionNode::*;
 
-            } else {
-              self.current_tab().1.set_type(index, 0, right_type)?;
-            }
+    match expression.node {
+      Block(ref statements) => self.always_returns(expression) || match statements.last().map(|statement| &statement.node) {
+        Some(&StatementNode::Return(_))            => true,
+        Some(&StatementNode::Expression(ref tail)) => match tail.node {
+          If(_, _, ref elses) => elses.iter().flat_map(|elses| elses.iter()).any(|&(ref condition, ..)| condition.is_none()),
+          _                   => true,
+        },
 
-            match right.node {
-              Function(..) | Block(_) | If(..) => self.visit_expression(right)?,
-              _ => (),
-            }
+        _ => false,
+      },
 
-          } else {
-            self.current_tab().1.set_type(index, 0, variable_type.to_owned())?;
-          }
-        },
+      _ => true,
+    }
+  }
 
-        Set(ref names) => {
-          if let &Some(ref right) = right {
-            let right_content = match right.node {
-              Set(ref content) => content,
+  fn ensure_no_implicit(&self, expression: &'v Expression) -> Result<(), ()> {
+    use self::ExpressionNode::*;
 
-              _ => return Err(
-                response!(
-                  Wrong("can't assign set to non-set"),
-                  self.source.file,
-                  left.pos
-                )
-              )
-            };
-
-            for (content_index, expression) in names.iter().enumerate() {
-              if let Identifier(ref name) = expression.node {
-                let index = if let Some((index, _)) = self.current_tab().0.get_name(name) {
-                  index
-                } else {
-                  self.current_tab().1.grow();
-                  self.current_tab().0.add_name(name)
-                };
-
-                if let Some(right) = right_content.get(content_index) {
-                  self.visit_expression(&right)?;
-
-                  let right_type = self.type_expression(right)?;
-
-                  if variable_type.node != TypeNode::Nil {
-                    if let TypeNode::Set(ref type_content) = variable_type.node {
-                      if type_content[content_index] != right_type {
-                        return Err(
-                          response!(
-                            Wrong(format!("mismatched types, expected type `{}` got `{}`", type_content[content_index], right_type)),
-                            self.source.file,
-                            right.pos
-                          )
-                        )
-                      } else {
-                        self.current_tab().1.set_type(index, 0, variable_type.to_owned())?;
-                      }
-                    } else {
-                      return Err(
-                        response!(
-                          Wrong(format!("mismatched types of set declaration got `{}`", variable_type.node)),
-                          self.source.file,
-                          left.pos
-                        )
-                      )
-                    }
-                  } else {
-                    self.current_tab().1.set_type(index, 0, right_type)?;
-                  }
-                } else {
-                  return Err(
-                    response!(
-                      Wrong("missing"),
-                      self.source.file,
-                      right.pos
-                    )
-                  )
-                }
-              }
-            }
-          } else {
-            for expression in names {
-              if let Identifier(ref name) = expression.node {
-                let index = if let Some((index, _)) = self.current_tab().0.get_name(name) {
-                  index
-                } else {
-                  self.current_tab().1.grow();
-                  self.current_tab().0.add_name(name)
-                };
+    match expression.node {
+      Block(ref statements) => if let Some(statement) = statements.last() {
+        if let StatementNode::Expression(ref expression) = statement.node {
+          match expression.node {
 
-                self.current_tab().1.set_type(index, 0, variable_type.to_owned())?;
-              }
-            }
+            Call(..)   => (),
+            Block(..)  => { self.ensure_no_implicit(expression)?; }
+
+            If(_, ref expr, _) => self.ensure_no_implicit(&*expr)?,
+            Try(ref body, _, ref handler) => {
+              self.ensure_no_implicit(&*body)?;
+              self.ensure_no_implicit(&*handler)?
+            },
+
+            _ => return Err(
+              response!(
+                Wrong("unexpected expression without context"),
+                self.source.file,
+                self.source.resolve(expression.pos)
+              )
+            )
           }
         }
+      },
 
-        _ => return Err(
+      Call(..)   => (),
+
+      If(_, ref expr, _) => self.ensure_no_implicit(&*expr)?,
+      Try(ref body, _, ref handler) => {
+        self.ensure_no_implicit(&*body)?;
+        self.ensure_no_implicit(&*handler)?
+      },
+
+      _ => return Err(
+        response!(
+          Wrong("unexpected expression without context"),
+          self.source.file,
+          self.source.resolve(expression.pos)
+        )
+      )
+    }
+
+    Ok(())
+  }
+
+  fn visit_expression(&mut self, expression: &'v Expression) -> Result<(), ()> {
+    use self::ExpressionNode::*;
+
+    match expression.node {
+      Identifier(ref name) => if self.current_tab().0.get_name(name).is_none() {
+        Err(
           response!(
-            Wrong("unexpected variable declaration"),
+            Wrong(format!("no such value `{}` in this scope", name)),
             self.source.file,
-            left.pos
+            self.source.resolve(expression.pos)
           )
         )
-      }
+      } else {
+        self.trace_name(name, expression);
 
-      Ok(())
-    } else {
-      unreachable!()
-    }
-  }
+        Ok(())
+      },
 
-  fn visit_constant(&mut self, constant: &'v StatementNode) -> Result<(), ()> {
-    use self::ExpressionNode::*;
+      Set(ref content) => {
+        for expression in content {
+          self.visit_expression(expression)?
+        }
 
-    if let &StatementNode::Constant(ref constant_type, ref left, ref right) = constant {
-      match left.node {
-        Identifier(ref name) => {
-          let index = if let Some((index, _)) = self.current_tab().0.get_name(name) {
-            index
-          } else {
-            self.current_tab().0.add_name(name)
-          };
+        Ok(())
+      },
 
-          self.current_tab().1.grow();
+      // operands are checked before the operation, so what they contain is too
+      Binary(ref left, _, ref right) => {
+        self.visit_expression(left)?;
+        self.visit_expression(right)?;
 
-          match right.node {
-            Function(..) | Block(_) => (),
-            _                       => self.visit_expression(right)?,
-          }
+        self.type_expression(expression)?;
 
-          let right_type = self.type_expression(right)?;
+        Ok(())
+      },
 
-          if constant_type.node != TypeNode::Nil {
-            if !constant_type.node.check_expression(&Parser::fold_expression(right)?.node) && constant_type != &right_type {
-              return Err(
-                response!(
-                  Wrong(format!("mismatched types, expected type `{}` got `{}`", constant_type.node, right_type)),
-                  self.source.file,
-                  right.pos
-                )
-              )
-            } else {
-              self.current_tab().1.set_type(index, 0, constant_type.to_owned())?;
+      Block(ref statements) => {
+        self.push_scope();
+
+        for (i, statement) in statements.iter().enumerate() {
+          if i < statements.len() - 1 {
+            if let StatementNode::Expression(ref expression) = statement.node {
+              self.ensure_no_implicit(expression)?
             }
-          } else {
-            self.current_tab().1.set_type(index, 0, right_type)?;
           }
 
-          match right.node {
-            Function(..) | Block(_) => self.visit_expression(right)?,
-            _                       => (),
-          }
-        },
+          self.visit_statement(statement)?
+        }
 
-        Set(ref names) => {
-          let right_content = match right.node {
-            Set(ref content) => content,
+        self.pop_scope();
 
-            _ => return Err(
-              response!(
-                Wrong("can't assign set to non-set"),
-                self.source.file,
-                left.pos
-              )
-            )
-          };
+        Ok(())
+      },
 
-          for (content_index, expression) in names.iter().enumerate() {
-            if let Identifier(ref name) = expression.node {
-              let index = if let Some((index, _)) = self.current_tab().0.get_name(name) {
-                index
-              } else {
-                self.current_tab().1.grow();
-                self.current_tab().0.add_name(name)
-              };
-
-              if let Some(right) = right_content.get(content_index) {
-                self.visit_expression(&right)?;
-
-                let right_type = self.type_expression(right)?;
-
-                if constant_type.node != TypeNode::Nil {
-                  if let TypeNode::Set(ref type_content) = constant_type.node {
-                    if type_content[content_index] != right_type {
-                      return Err(
-                        response!(
-                          Wrong(format!("mismatched types, expected type `{}` got `{}`", type_content[content_index], right_type)),
-                          self.source.file,
-                          right.pos
-                        )
-                      )
-                    }
-                  } else {
-                    return Err(
-                      response!(
-                        Wrong(format!("mismatched types of set declaration got `{}`", constant_type.node)),
-                        self.source.file,
-                        left.pos
-                      )
+      If(ref condition, ref body, ref elses) => {
+        self.visit_expression(&*condition)?;
+
+        let condition_type = self.type_expression(&*condition)?.node;
+
+        if condition_type == TypeNode::Bool {
+
+          self.push_scope();
+
+          self.visit_expression(body)?;
+          let body_type = self.type_expression(body)?;
+
+          self.pop_scope();
+
+          if let &Some(ref elses) = elses {
+            for &(ref maybe_condition, ref body, _) in elses {
+              if let Some(ref condition) = *maybe_condition {
+                self.visit_expression(condition)?;
+
+                let condition_type = self.type_expression(condition)?.node;
+
+                if condition_type != TypeNode::Bool {
+                  return Err(
+                    response!(
+                      Wrong(format!("mismatched condition, must be `bool` got `{}`", condition_type)),
+                      self.source.file,
+                      self.source.resolve(condition.pos)
                     )
-                  }
-                } else {
-                  self.current_tab().1.set_type(index, 0, right_type)?;
+                  )
                 }
-              } else {
+              }
+
+              self.push_scope();
+
+              self.visit_expression(body)?;
+              let else_body_type = self.type_expression(body)?;
+
+              self.pop_scope();
+
+              if body_type != else_body_type {
                 return Err(
                   response!(
-                    Wrong("missing"),
+                    Wrong(format!("mismatched types, expected `{}` got `{}`", body_type, else_body_type)),
                     self.source.file,
-                    right.pos
+                    self.source.resolve(body.pos)
                   )
                 )
               }
             }
           }
+
+          Ok(())
+
+        } else {
+          Err(
+            response!(
+              Wrong(format!("mismatched condition, must be `bool` got `{}`", condition_type)),
+              self.source.file,
+              self.source.resolve(expression.pos)
+            )
+          )
+        }
+      },
+
+      Try(ref body, ref name, ref handler) => {
+        let body_type    = self.type_expression(body)?;
+        let handler_type = self.type_catch(name, handler)?;
+
+        if body_type != handler_type {
+          return Err(
+            response!(
+              Wrong(format!("mismatched types, expected `{}` got `{}`", body_type, handler_type)),
+              self.source.file,
+              self.source.resolve(handler.pos)
+            )
+          )
+        }
+
+        Ok(())
+      },
+
+      Call(ref expression, ref args) => {
+        // the compiler counts arguments in a byte
+        if args.len() > u8::max_value() as usize {
+          return Err(
+            response!(
+              Wrong(format!("too many arguments, a call takes at most {}", u8::max_value())),
+              self.source.file,
+              self.source.resolve(expression.pos)
+            )
+          )
+        }
+
+        if self.is_builtin(expression) {
+          for arg in args {
+            self.visit_expression(arg)?
+          }
+
+          self.type_builtin(expression, args)?;
+
+          return Ok(())
+        }
+
+        self.visit_expression(expression)?;
+
+        for arg in args {
+          self.visit_expression(arg)?
+        }
+
+        let (params, return_type) = self.type_call(expression, args)?;
+        let expression_type       = Type::function(params.clone(), return_type);
+
+        for (index, param) in params.iter().enumerate() {
+          let arg_type = self.type_expression(&args[index])?;
+
+          if !self.check_literal(&param.node, &args[index])? && param != &arg_type {
+            return Err(
+              response!(
+                Wrong(format!("mismatched argument, expected `{}` got `{}`", expression_type, arg_type)),
+                self.source.file,
+                self.source.resolve(args[index].pos)
+              )
+            )
+          }
+        }
+
+        Ok(())
+      },
+
+      Function(ref generics, ref params, ref return_type, ref body) => {
+        use self::ExpressionNode::*;
+        use self::StatementNode::*;
+
+        let mut param_names     = Vec::new();
+        let mut param_types     = Vec::new();
+        let mut param_locations = Vec::new();
+
+        for param in params {
+          match param.node {
+            Constant(ref t, ref name, _) | Variable(ref t, ref name, _) => if let Identifier(ref identifier) = name.node {
+              param_names.push(identifier.clone());
+              param_locations.push(self.source.locate(name.pos));
+
+              param_types.push(t.clone());
+            } else {
+              return Err(
+                response!(
+                  Wrong("can't destructure a parameter, bind it to a name and unpack that in the body"),
+                  self.source.file,
+                  self.source.resolve(param.pos)
+                )
+              )
+            },
+
+            _ => unreachable!()
+          }
+        }
+
+        let keys = self.keys.len();
+
+        for name in generics {
+          if param_types.iter().chain(Some(return_type)).any(|t| t.keys_with(name)) {
+            self.keys.push(name.clone())
+          }
+        }
+
+        for t in param_types.iter().chain(Some(return_type)) {
+          self.visit_type(t, expression.pos)?
+        }
+
+        let parent = self.current_tab().clone();
+
+        self.tabs.push(
+          (
+            SymTab::new(Rc::new(parent.0), &param_names),
+            TypeTab::new(Rc::new(parent.1), &param_types)
+          )
+        );
+
+        for (name, location) in param_names.iter().zip(param_locations) {
+          self.current_tab().0.locate(name, location)
+        }
+
+        let flag_backup = self.flag.replace(FlagContext::Block(Some(return_type.clone())));
+
+        self.visit_expression(body)?;
+        let body_type = self.type_expression(body)?;
+
+        // a literal in tail position takes the return type like a returned one would
+        let tail_literal = match body.node {
+          Block(ref statements) => match statements.last() {
+            Some(&Statement { node: Expression(ref tail), .. })   => self.check_literal(&return_type.node, tail)?,
+            Some(&Statement { node: Return(Some(ref tail)), .. }) => self.check_literal(&return_type.node, tail)?,
+            _                                                     => false,
+          },
+
+          _ => false,
+        };
+
+        self.flag = flag_backup;
+        self.keys.truncate(keys);
+
+        self.pop_scope();
+
+        // a body that returns or raises on every path has had its returns checked one by one
+        if !tail_literal && return_type != &body_type && !self.always_returns(body) {
+          if !self.ends_in_value(body) {
+            return Err(
+              response!(
+                Wrong(format!("missing return, not every path returns `{}`", return_type)),
+                self.source.file,
+                self.source.resolve(expression.pos)
+              )
+            )
+          }
+
+          Err(
+            response!(
+              Wrong(format!("mismatched return type, expected `{}` got `{}`", return_type, body_type)),
+              self.source.file,
+              self.source.resolve(expression.pos)
+            )
+          )
+        } else {
+          Ok(())
+        }
+      },
+
+      Array(ref content) => {
+        let t = match content.first() {
+          Some(first) => self.type_expression(first)?,
+          None        => return Ok(()),
+        };
+
+        for element in content {
+          self.visit_expression(element)?;
+
+          let element_type = self.type_expression(element)?;
+
+          if !self.check_literal(&t.node, element)? && t.node != element_type.node {
+            return Err(
+              response!(
+                Wrong(format!("mismatched types in array, expected `{}` got `{}`", t, element_type)),
+                self.source.file,
+                self.source.resolve(element.pos)
+              )
+            )
+          }
+        }
+
+        Ok(())
+      },
+
+      Map(ref content) => {
+        let (key_type, value_type) = match content.first() {
+          Some(&(ref key, ref value)) => (self.type_expression(key)?, self.type_expression(value)?),
+          None                        => return Ok(()),
+        };
+
+        self.ensure_hashable(&key_type, content[0].0.pos)?;
+
+        for &(ref key, ref value) in content {
+          self.visit_expression(key)?;
+          self.visit_expression(value)?;
+
+          self.check_argument(&key_type, key)?;
+          self.check_argument(&value_type, value)?;
+        }
+
+        Ok(())
+      },
+
+      Interpolation(ref parts) => {
+        for part in parts {
+          if let Str(_) = part.node {
+            continue
+          }
+
+          self.visit_expression(part)?;
+
+          let part_type = self.type_expression(part)?;
+
+          match part_type.node {
+            TypeNode::Nil | TypeNode::Func(..) | TypeNode::Generic(..) => return Err(
+              response!(
+                Wrong(format!("can't interpolate `{}` into a string", part_type)),
+                self.source.file,
+                self.source.resolve(part.pos)
+              )
+            ),
+
+            _ => (),
+          }
+        }
+
+        Ok(())
+      },
+
+      Cast(ref expression, ref t) => {
+        self.visit_expression(expression)?;
+
+        let expression_type = self.type_expression(expression)?;
+
+        let numeric = |t: &TypeNode| match *t {
+          TypeNode::Int | TypeNode::Double | TypeNode::Sized(_) => true,
+          _                                                     => false,
+        };
+
+        if !numeric(&expression_type.node) || !numeric(&t.node) {
+          return Err(
+            response!(
+              Wrong(format!("can't cast `{}` to `{}`", expression_type, t)),
+              self.source.file,
+              self.source.resolve(expression.pos)
+            )
+          )
+        }
+
+        if t.node.is_sized() {
+          self.check_literal(&t.node, expression)?;
+        }
+
+        Ok(())
+      },
+
+      Index(ref left, ref index) => {
+        self.visit_expression(left)?;
+        self.visit_expression(index)?;
+
+        let left_type = self.type_expression(left)?;
+
+        if let TypeNode::Array(_) = left_type.node {
+          let index_type = self.type_expression(index)?;
+
+          if index_type.node != TypeNode::Int && !index_type.node.is_sized() {
+            return Err(
+              response!(
+                Wrong(format!("can't index with `{}`, must be integer", index_type)),
+                self.source.file,
+                self.source.resolve(index.pos)
+              )
+            )
+          }
+
+        } else if let TypeNode::Set(ref content) = left_type.node {
+          self.set_index(index, content.len())?;
+        } else if let TypeNode::Map(ref key, _) = left_type.node {
+          self.check_argument(key, index)?;
+        } else {
+          return Err(
+            response!(
+              Wrong(format!("can't index `{}`", left_type)),
+              self.source.file,
+              self.source.resolve(left.pos)
+            )
+          )
         }
 
-        _ => return Err(
+        Ok(())
+      },
+
+      _ => Ok(())
+    }
+  }
+
+  fn visit_variable(&mut self, variable: &'v StatementNode) -> Result<(), ()> {
+    use self::ExpressionNode::*;
+
+    if let &StatementNode::Variable(ref variable_type, ref left, ref right) = variable {
+      match left.node {
+        Identifier(ref name) => {
+          let index = if let Some((index, 0)) = self.current_tab().0.get_name(name) {
+            index
+          } else {
+            self.current_tab().0.add_name(name)
+          };
+
+          let location = self.source.locate(left.pos);
+
+
+          self.current_tab().0.locate(name, location);
+          self.current_tab().1.grow();
+
+          if let &Some(ref right) = right {
+            let right_type = self.type_expression(right)?;
+
+            match right.node {
+              Function(..) | Block(_) | If(..) => (),
+              _ => self.visit_expression(right)?,
+            }
+
+            if variable_type.node != TypeNode::Nil {
+              if !self.check_literal(&variable_type.node, right)? && variable_type.node != right_type.node {
+                return Err(
+                  response!(
+                    Wrong(format!("mismatched types, expected type `{}` got `{}`", variable_type.node, right_type)),
+                    self.source.file,
+                    self.source.resolve(right.pos)
+                  )
+                )
+              } else {
+                self.current_tab().1.set_type(index, 0, variable_type.to_owned())?;
+              }
+
+            } else {
+              self.current_tab().1.set_type(index, 0, right_type)?;
+            }
+
+            match right.node {
+              Function(..) | Block(_) | If(..) => self.visit_expression(right)?,
+              _ => (),
+            }
+
+          } else {
+            self.current_tab().1.set_type(index, 0, variable_type.to_owned())?;
+          }
+        },
+
+        Set(ref names) => self.visit_set_declaration(names, variable_type, right.as_ref(), left)?,
+
+        _ => return Err(
+          response!(
+            Wrong("unexpected variable declaration"),
+            self.source.file,
+            self.source.resolve(left.pos)
+          )
+        )
+      }
+
+      Ok(())
+    } else {
+      unreachable!()
+    }
+  }
+
+  fn visit_constant(&mut self, constant: &'v StatementNode) -> Result<(), ()> {
+    use self::ExpressionNode::*;
+
+    if let &StatementNode::Constant(ref constant_type, ref left, ref right) = constant {
+      match left.node {
+        Identifier(ref name) => {
+          let index = if let Some((index, 0)) = self.current_tab().0.get_name(name) {
+            index
+          } else {
+            self.current_tab().0.add_name(name)
+          };
+
+          let location = self.source.locate(left.pos);
+
+
+          self.current_tab().0.locate(name, location);
+          self.current_tab().1.grow();
+
+          match right.node {
+            Function(..) | Block(_) => (),
+            _                       => self.visit_expression(right)?,
+          }
+
+          let right_type = self.type_expression(right)?;
+
+          if constant_type.node != TypeNode::Nil {
+            if !self.check_literal(&constant_type.node, right)? && constant_type != &right_type {
+              return Err(
+                response!(
+                  Wrong(format!("mismatched types, expected type `{}` got `{}`", constant_type.node, right_type)),
+                  self.source.file,
+                  self.source.resolve(right.pos)
+                )
+              )
+            } else {
+              self.current_tab().1.set_type(index, 0, constant_type.to_owned())?;
+            }
+          } else {
+            self.current_tab().1.set_type(index, 0, Type::new(right_type.node, TypeMode::Immutable))?;
+          }
+
+          match right.node {
+            Function(..) | Block(_) => self.visit_expression(right)?,
+            _                       => (),
+          }
+        },
+
+        Set(ref names) => self.visit_set_declaration(names, constant_type, Some(right), left)?,
+
+        _ => return Err(
+          response!(
+            Wrong("unexpected constant declaration"),
+            self.source.file,
+            self.source.resolve(left.pos)
+          )
+        )
+      }
+
+      Ok(())
+    } else {
+      unreachable!()
+    }
+  }
+
+
+
+  fn visit_set_declaration(&mut self, names: &'v [Expression], declared_type: &Type, right: Option<&'v Expression>, left: &'v Expression) -> Result<(), ()> {
+    let set_type = if let Some(right) = right {
+      self.visit_expression(right)?;
+
+      let right_type = self.type_expression(right)?;
+
+      if declared_type.node == TypeNode::Nil {
+        right_type
+      } else {
+        let compatible = match (&declared_type.node, &right.node) {
+          (&TypeNode::Set(ref type_content), &ExpressionNode::Set(ref content)) if type_content.len() == content.len() => {
+            let mut compatible = true;
+
+            for (t, expression) in type_content.iter().zip(content.iter()) {
+              let expression_type = self.type_expression(expression)?;
+
+              if !self.check_literal(&t.node, expression)? && t.node != expression_type.node {
+                compatible = false
+              }
+            }
+
+            compatible
+          },
+
+          _ => declared_type.node == right_type.node,
+        };
+
+        if !compatible {
+          return Err(
+            response!(
+              Wrong(format!("mismatched types, expected type `{}` got `{}`", declared_type.node, right_type)),
+              self.source.file,
+              self.source.resolve(right.pos)
+            )
+          )
+        }
+
+        declared_type.clone()
+      }
+    } else {
+      declared_type.clone()
+    };
+
+    let type_content = match set_type.node {
+      TypeNode::Set(ref content) => content.clone(),
+
+      _ => return Err(
+        response!(
+          Wrong(format!("can't destructure non-set `{}`", set_type)),
+          self.source.file,
+          self.source.resolve(left.pos)
+        )
+      )
+    };
+
+    if type_content.len() != names.len() {
+      return Err(
+        response!(
+          Wrong(format!("mismatched set declaration, expected {} names got {}", type_content.len(), names.len())),
+          self.source.file,
+          self.source.resolve(left.pos)
+        )
+      )
+    }
+
+    for (expression, t) in names.iter().zip(type_content) {
+      if let ExpressionNode::Identifier(ref name) = expression.node {
+        let index = if let Some((index, 0)) = self.current_tab().0.get_name(name) {
+          index
+        } else {
+          self.current_tab().1.grow();
+          self.current_tab().0.add_name(name)
+        };
+
+        let location = self.source.locate(expression.pos);
+
+
+        self.current_tab().0.locate(name, location);
+        self.current_tab().1.set_type(index, 0, Type::new(t.node, declared_type.mode.clone()))?;
+      } else {
+        return Err(
+          response!(
+            Wrong("expected identifier in set declaration"),
+            self.source.file,
+            self.source.resolve(expression.pos)
+          )
+        )
+      }
+    }
+
+    Ok(())
+  }
+
+
+
+  pub fn type_statement(&mut self, statement: &'v Statement) -> Result<Type, ()> {
+    use self::StatementNode::*;
+
+    let t = match statement.node {
+      Expression(ref expression) => self.type_expression(expression)?,
+      Return(ref expression)     => if let Some(ref expression) = *expression {
+        self.type_expression(expression)?
+      } else {
+        Type::from(TypeNode::Nil)
+      }
+      _                          => Type::from(TypeNode::Nil)
+    };
+
+    Ok(t)
+  }
+
+
+
+  pub fn type_expression(&mut self, expression: &'v Expression) -> Result<Type, ()> {
+    use self::ExpressionNode::*;
+
+    let t = match expression.node {
+      Identifier(ref name) => if let Some((index, env_index)) = self.current_tab().0.get_name(name) {
+        self.trace_name(name, expression);
+        self.current_tab().1.get_type(index, env_index)?
+      } else {
+        return Err(
+          response!(
+            Wrong(format!("no such value `{}` in this scope", name)),
+            self.source.file,
+            self.source.resolve(expression.pos)
+          )
+        )
+      },
+
+      Str(_) => Type::from(TypeNode::Str),
+
+      Interpolation(_) => {
+        self.visit_expression(expression)?;
+
+        Type::from(TypeNode::Str)
+      },
+
+      Char(_)   => Type::from(TypeNode::Char),
+      Bool(_)   => Type::from(TypeNode::Bool),
+      Int(n)    => if n > i128::max_value() as u128 {
+        return Err(
+          response!(
+            Wrong(format!("literal `{}` doesn't fit in `int`", n)),
+            self.source.file,
+            self.source.resolve(expression.pos)
+          )
+        )
+      } else {
+        Type::from(TypeNode::Int)
+      },
+      Double(_)  => Type::from(TypeNode::Double),
+
+      Call(ref expression, ref args) => if self.is_builtin(expression) {
+        self.type_builtin(expression, args)?
+      } else {
+        self.type_call(expression, args)?.1
+      },
+
+      Index(ref array, ref index) => {
+        let array_type = self.type_expression(array)?;
+
+        if let TypeNode::Array(ref t) = array_type.node {
+          (**t).clone()
+        } else if let TypeNode::Set(ref content) = array_type.node {
+          content[self.set_index(index, content.len())?].clone()
+        } else if let TypeNode::Map(_, ref value) = array_type.node {
+          (**value).clone()
+        } else {
+          return Err(
+            response!(
+              Wrong(format!("can't index `{}`", array_type)),
+              self.source.file,
+              self.source.resolve(array.pos)
+            )
+          )
+        }
+      },
+
+      // without an `else` a false condition falls through, and the compiler leaves nil
+      // a branch that raises fits any other, which gives the type
+      If(_, ref expression, ref elses) => if elses.iter().flat_map(|elses| elses.iter()).any(|&(ref condition, ..)| condition.is_none()) {
+        let mut t = self.type_expression(expression)?;
+
+        for &(_, ref body, _) in elses.iter().flat_map(|elses| elses.iter()) {
+          if !t.node.is_never() {
+            break
+          }
+
+          t = self.type_expression(body)?
+        }
+
+        t
+      } else {
+        Type::from(TypeNode::Nil)
+      },
+
+      Try(ref expression, ref name, ref handler) => {
+        let t = self.type_expression(expression)?;
+
+        if t.node.is_never() {
+          self.type_catch(name, handler)?
+        } else {
+          t
+        }
+      },
+
+      Array(ref content) => Type::array(
+        match content.first() {
+          Some(first) => self.type_expression(first)?,
+          None        => Type::from(TypeNode::Nil),
+        }
+      ),
+
+      Map(ref content) => match content.first() {
+        Some(&(ref key, ref value)) => Type::map(self.type_expression(key)?, self.type_expression(value)?),
+        None                        => Type::map(Type::from(TypeNode::Nil), Type::from(TypeNode::Nil)),
+      },
+
+      Cast(_, ref t) => Type::from(t.node.clone()),
+
+      Binary(ref left, ref op, ref right) => {
+        use self::Operator::*;
+
+        let (ref a, ref b) = self.type_operands(left, right)?;
+
+        match *op {
+          // numbers of one type, where a type parameter could stand for anything
+          Add | Sub | Mul | Div | Mod | Pow => match (a, b) {
+            (&TypeNode::Int, &TypeNode::Int) | (&TypeNode::Double, &TypeNode::Double) => Type::from(a.clone()),
+            (&TypeNode::Sized(k), &TypeNode::Sized(l)) if k == l                       => Type::from(a.clone()),
+
+            _ => return Err(
+              response!(
+                Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
+                self.source.file,
+                self.source.resolve(expression.pos)
+              )
+            )
+          },
+
+          Concat => if *a == TypeNode::Str {
+            match *b {
+              TypeNode::Func(..) | TypeNode::Array(..) => return Err(
+                response!(
+                  Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
+                  self.source.file,
+                  self.source.resolve(expression.pos)
+                )
+              ),
+
+              _ => Type::from(TypeNode::Str)
+            }
+          } else {
+            return Err(
+              response!(
+                Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
+                self.source.file,
+                self.source.resolve(expression.pos)
+              )
+            )
+          },
+
+          Eq | NEq => if a == b {
+            Type::from(TypeNode::Bool)
+          } else {
+            return Err(
+              response!(
+                Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
+                self.source.file,
+                self.source.resolve(expression.pos)
+              )
+            )
+          },
+
+          // only numbers and chars have an order
+          Lt | Gt | LtEq | GtEq => match (a, b) {
+            (&TypeNode::Int, &TypeNode::Int) | (&TypeNode::Double, &TypeNode::Double) | (&TypeNode::Char, &TypeNode::Char) => Type::from(TypeNode::Bool),
+            (&TypeNode::Sized(k), &TypeNode::Sized(l)) if k == l                                                            => Type::from(TypeNode::Bool),
+
+            _ => return Err(
+              response!(
+                Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
+                self.source.file,
+                self.source.resolve(expression.pos)
+              )
+            )
+          },
+
+          _ => return Err(
+            response!(
+              Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
+              self.source.file,
+              self.source.resolve(expression.pos)
+            )
+          )
+        }
+      },
+
+      Function(ref generics, ref params, ref return_type, _) => {
+        use self::StatementNode::*;
+
+        let mut param_types = Vec::new();
+
+        for param in params {
+          match param.node {
+            Variable(ref t, ..) | Constant(ref t, ..) => param_types.push(t.clone()),
+            _ => unreachable!(),
+          }
+        }
+
+        if generics.is_empty() {
+          Type::function(param_types, return_type.clone())
+        } else {
+          Type::from(TypeNode::Generic(generics.clone(), Arc::new(Type::function(param_types, return_type.clone()))))
+        }
+      },
+
+      Set(ref content) => {
+        let mut type_content = Vec::new();
+
+        for expression in content {
+          type_content.push(self.type_expression(expression)?)
+        }
+
+        Type::set(type_content)
+      },
+
+      // explicit returns are checked as they're visited, the block itself has the type of its last statement
+      Block(ref statements) => match statements.last() {
+        Some(last) => {
+          self.visit_expression(expression)?;
+
+          self.tabs.push(self.tab_frames.last().unwrap().clone());
+
+          let implicit_type = self.type_statement(last)?;
+
+          self.tabs.pop();
+
+          implicit_type
+        },
+
+        None => Type::from(TypeNode::Nil),
+      },
+
+      _ => Type::from(TypeNode::Nil)
+    };
+
+    if let (Some(trace), Some(location)) = (self.trace.as_mut(), self.source.locate(expression.pos)) {
+      trace.types.insert(location, t.clone());
+    }
+
+    Ok(t)
+  }
+
+
+
+  // sets are indexed by constant integers, so each element keeps its own type
+  fn set_index(&self, index: &Expression, len: usize) -> Result<usize, ()> {
+    if let ExpressionNode::Int(i) = Parser::fold_expression(index)?.node {
+      if (i as usize) < len {
+        return Ok(i as usize)
+      }
+
+      Err(
+        response!(
+          Wrong(format!("set index out of bounds: the len is {} but the index is {}", len, i)),
+          self.source.file,
+          self.source.resolve(index.pos)
+        )
+      )
+    } else {
+      Err(
+        response!(
+          Wrong("sets must be indexed by a constant integer"),
+          self.source.file,
+          self.source.resolve(index.pos)
+        )
+      )
+    }
+  }
+
+  // the parameter and return types of a call, with any type parameters inferred from the arguments
+  fn type_call(&mut self, called: &'v Expression, args: &'v [Expression]) -> Result<(Vec<Type>, Type), ()> {
+    let called_type = self.type_expression(called)?;
+
+    let (generics, function_type) = match called_type.node {
+      TypeNode::Generic(ref generics, ref t) => (generics.clone(), (**t).clone()),
+      _                                      => (Vec::new(), called_type.clone()),
+    };
+
+    let (params, return_type) = match function_type.node {
+      TypeNode::Func(ref params, ref return_type) => (params.clone(), (**return_type).clone()),
+
+      _ => return Err(
+        response!(
+          Wrong(format!("expected function, found `{}`", called_type)),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    };
+
+    if params.len() != args.len() {
+      return Err(
+        response!(
+          Wrong(format!("expected {} argument(s) to `{}`, got {}", params.len(), called_type, args.len())),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    }
+
+    if generics.is_empty() {
+      return Ok((params, return_type))
+    }
+
+    let mut bindings = HashMap::new();
+
+    for (param, arg) in params.iter().zip(args.iter()) {
+      let arg_type = self.type_expression(arg)?;
+
+      if !param.unify(&arg_type, &generics, &mut bindings) {
+        return Err(
           response!(
-            Wrong("unexpected constant declaration"),
+            Wrong(format!("mismatched argument, expected `{}` got `{}`", param.substitute(&bindings), arg_type)),
             self.source.file,
-            left.pos
+            self.source.resolve(arg.pos)
           )
         )
       }
-
-      Ok(())
-    } else {
-      unreachable!()
     }
-  }
-
-
-
-  pub fn type_statement(&mut self, statement: &'v Statement<'v>) -> Result<Type, ()> {
-    use self::StatementNode::*;
 
-    let t = match statement.node {
-      Expression(ref expression) => self.type_expression(expression)?,
-      Return(ref expression)     => if let Some(ref expression) = *expression {
-        self.type_expression(expression)?
-      } else {
-        Type::from(TypeNode::Nil)
+    for name in generics.iter() {
+      if !bindings.contains_key(name) && (return_type.mentions(name) || params.iter().any(|t| t.mentions(name))) {
+        return Err(
+          response!(
+            Wrong(format!("can't infer type parameter `{}` of `{}`", name, called_type)),
+            self.source.file,
+            self.source.resolve(called.pos)
+          )
+        )
       }
-      _                          => Type::from(TypeNode::Nil)
-    };
+    }
 
-    Ok(t)
-  }
+    let (params, return_type) = (params.iter().map(|t| t.substitute(&bindings)).collect::<Vec<Type>>(), return_type.substitute(&bindings));
 
+    // type parameters keying maps have to be bound to hashable types
+    for t in params.iter().chain(Some(&return_type)) {
+      self.visit_type(t, called.pos)?
+    }
 
+    Ok((params, return_type))
+  }
 
-  pub fn type_expression(&mut self, expression: &'v Expression<'v>) -> Result<Type, ()> {
-    use self::ExpressionNode::*;
+  // whether `expression` is a literal of the expected type, int literals taking its size if they fit it
+  fn check_literal(&mut self, expected: &TypeNode, expression: &'v Expression) -> Result<bool, ()> {
+    let folded = Parser::fold_expression(expression)?;
 
-    let t = match expression.node {
-      Identifier(ref name) => if let Some((index, env_index)) = self.current_tab().0.get_name(name) {
-        self.current_tab().1.get_type(index, env_index)?
-      } else {
-        return Err(
+    match (expected, &folded.node) {
+      (&TypeNode::Sized(kind), &ExpressionNode::Int(n)) => if n > kind.max() as u128 {
+        Err(
           response!(
-            Wrong(format!("no such value `{}` in this scope", name)),
+            Wrong(format!("literal `{}` doesn't fit in `{}`", n, kind)),
             self.source.file,
-            expression.pos
+            self.source.resolve(expression.pos)
           )
         )
-      },
+      } else {
+        self.literals.insert(expression.pos, kind);
 
-      Str(_) => Type::from(TypeNode::Str),
-      Char(_)   => Type::from(TypeNode::Char),
-      Bool(_)   => Type::from(TypeNode::Bool),
-      Int(_)    => Type::from(TypeNode::Int),
-      Double(_)  => Type::from(TypeNode::Double),
+        Ok(true)
+      },
 
-      Call(ref expression, _) => {
-        if let TypeNode::Func(_, ref return_type) = self.type_expression(expression)?.node {
-          (**return_type).clone()
-        } else {
-          panic!("accident (submit an issue): called {:#?}", self.type_expression(expression)?.node)
+      // elements are checked one by one, so each literal gets sized
+      (&TypeNode::Array(ref t), _) => if let ExpressionNode::Array(ref content) = expression.node {
+        for element in content {
+          if !self.check_literal(&t.node, element)? && t.node != self.type_expression(element)?.node {
+            return Ok(false)
+          }
         }
-      },
 
-      Index(ref array, _) => if let TypeNode::Array(ref t) = self.type_expression(array)?.node {
-        (**t).clone()
+        Ok(true)
       } else {
-        unreachable!()
+        Ok(false)
       },
 
-      If(_, ref expression, _) => self.type_expression(expression)?,
+      (_, node) => Ok(expected.check_expression(node)),
+    }
+  }
 
-      Array(ref content) => Type::array(self.type_expression(content.first().unwrap())?),
+  fn check_argument(&mut self, expected: &Type, argument: &'v Expression) -> Result<(), ()> {
+    let argument_type = self.type_expression(argument)?;
 
-      Cast(_, ref t) => Type::from(t.node.clone()),
+    if !self.check_literal(&expected.node, argument)? && expected.node != argument_type.node {
+      Err(
+        response!(
+          Wrong(format!("mismatched types, expected `{}` got `{}`", expected, argument_type)),
+          self.source.file,
+          self.source.resolve(argument.pos)
+        )
+      )
+    } else {
+      Ok(())
+    }
+  }
 
-      Binary(ref left, ref op, ref right) => {
-        use self::Operator::*;
+  fn ensure_hashable(&self, t: &Type, position: Span) -> Result<(), ()> {
+    match t.node {
+      TypeNode::Int | TypeNode::Char | TypeNode::Bool | TypeNode::Str => Ok(()),
 
-        match (self.type_expression(left)?.node, op, self.type_expression(right)?.node) {
-          (ref a, ref op, ref b) => match **op {
-            Add | Sub | Mul | Div | Pow | Mod => if [a, b] != [&TypeNode::Nil, &TypeNode::Nil] { // real hack here
-              Type::from(if a != &TypeNode::Nil { a.to_owned() } else { b.to_owned() })
-            } else {
-              return Err(
-                response!(
-                  Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
-                  self.source.file,
-                  expression.pos
-                )
-              )
-            },
+      // checked where the function is called, as long as its signature says so
+      TypeNode::Id(ref name) if self.keys.contains(name) => Ok(()),
 
-            Concat => if *a == TypeNode::Str {
-              match *b {
-                TypeNode::Func(..) | TypeNode::Array(..) => return Err(
-                  response!(
-                    Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
-                    self.source.file,
-                    expression.pos
-                  )
-                ),
+      _ => Err(
+        response!(
+          Wrong(format!("can't use `{}` as map key, must be `int`, `char`, `bool` or `string`", t)),
+          self.source.file,
+          self.source.resolve(position)
+        )
+      )
+    }
+  }
 
-                _ => Type::from(TypeNode::Str)
-              }
-            } else {
-              return Err(
-                response!(
-                  Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
-                  self.source.file,
-                  expression.pos
-                )
-              )
-            },
+  // walks an explicit type annotation for things the parser can't reject on its own
+  fn visit_type(&self, t: &Type, position: Span) -> Result<(), ()> {
+    match t.node {
+      TypeNode::Map(ref key, ref value) => {
+        self.ensure_hashable(key, position)?;
+        self.visit_type(key, position)?;
+        self.visit_type(value, position)
+      },
 
-            Eq | Lt | Gt | NEq | LtEq | GtEq => if a == b {
-              Type::from(TypeNode::Bool)
-            } else {
-              return Err(
-                response!(
-                  Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
-                  self.source.file,
-                  expression.pos
-                )
-              )
-            },
+      TypeNode::Array(ref t) => self.visit_type(t, position),
 
-            _ => return Err(
-              response!(
-                Wrong(format!("can't perform operation `{} {} {}`", a, op, b)),
-                self.source.file,
-                expression.pos
-              )
-            )
-          },
+      TypeNode::Set(ref content) => {
+        for t in content {
+          self.visit_type(t, position)?
+        }
+
+        Ok(())
+      },
 
-          _ => Type::from(TypeNode::Nil),
+      TypeNode::Func(ref params, ref return_type) => {
+        for t in params {
+          self.visit_type(t, position)?
         }
+
+        self.visit_type(return_type, position)
       },
 
-      Function(ref params, ref return_type, _) => {
-        use self::StatementNode::*;
+      TypeNode::Generic(_, ref t) => self.visit_type(t, position),
 
-        let mut param_types = Vec::new();
+      _ => Ok(()),
+    }
+  }
 
-        for param in params {
-          match param.node {
-            Variable(ref t, ..) | Constant(ref t, ..) => param_types.push(t.clone()),
-            _ => unreachable!(),
-          }
-        }
+  // built-in operations, unless shadowed by a binding
+  pub fn is_builtin(&mut self, called: &Expression) -> bool {
+    if let ExpressionNode::Identifier(ref name) = called.node {
+      match name.as_str() {
+        "len" | "push" | "pop" | "has" | "remove" |
+        "print" | "println" | "format" | "raise"  |
+        "wrapping_add" | "wrapping_sub" | "wrapping_mul" |
+        "saturating_add" | "saturating_sub" | "saturating_mul" if self.current_tab().0.get_name(name).is_none() => {
+          self.builtins.insert(called.pos);
+
+          true
+        },
 
-        Type::function(param_types, return_type.clone())
-      },
+        _ => false,
+      }
+    } else {
+      false
+    }
+  }
 
-      Set(ref content) => {
-        let mut type_content = Vec::new();
+  fn type_builtin(&mut self, called: &'v Expression, args: &'v [Expression]) -> Result<Type, ()> {
+    let name = if let ExpressionNode::Identifier(ref name) = called.node {
+      name.as_str()
+    } else {
+      unreachable!()
+    };
 
-        for expression in content {
-          type_content.push(self.type_expression(expression)?)
+    match name {
+      // anything can be printed
+      "print" | "println" => {
+        for arg in args {
+          self.type_expression(arg)?;
         }
 
-        Type::set(type_content)
+        return Ok(Type::from(TypeNode::Nil))
       },
+      "format"            => return self.type_format(called, args),
+      "raise"             => return self.type_raise(called, args),
 
-      Block(ref statements) => {
-        let flag_backup = self.flag.clone();
+      "wrapping_add" | "wrapping_sub" | "wrapping_mul" |
+      "saturating_add" | "saturating_sub" | "saturating_mul" => return self.type_overflowing(name, called, args),
 
-        if self.flag.is_none() {
-          self.flag = Some(FlagContext::Block(None))
-        }
+      _                   => (),
+    }
 
-        let block_type = if statements.len() > 0 {
-          for element in statements {
-
-            match element.node {
-              StatementNode::Expression(ref expression) => match expression.node {
-                Block(_) | If(..) => { self.type_expression(expression)?; },
-
-                _ => (),
-              },
-
-              StatementNode::Return(ref return_type) => {
-                let flag = self.flag.clone();
-
-                if let Some(ref flag) = flag {
-                  if let &FlagContext::Block(ref consistent) = flag {
-
-                    let return_type = if let Some(ref return_type) = *return_type {
-                      self.type_expression(&return_type)?
-                    } else {
-                      Type::from(TypeNode::Nil)
-                    };
-
-                    if let Some(ref consistent) = *consistent {
-                      if return_type != *consistent {
-                        return Err(
-                          response!(
-                            Wrong(format!("mismatched types, expected `{}` found `{}`", consistent, return_type)),
-                            self.source.file,
-                            expression.pos
-                          )
-                        )
-                      }
-                    } else {
-                      self.flag = Some(FlagContext::Block(Some(return_type.clone())))
-                    }
-                  }
-                }
-              },
+    let arity = if name == "len" || name == "pop" { 1 } else { 2 };
 
-              _ => (),
-            }
-          }
+    if args.len() != arity {
+      return Err(
+        response!(
+          Wrong(format!("`{}` takes {} argument(s), got {}", name, arity, args.len())),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    }
 
-          self.visit_expression(&expression)?;
+    let collection_type = self.type_expression(&args[0])?;
 
-          self.tabs.push(self.tab_frames.last().unwrap().clone());
+    let t = match (name, &collection_type.node) {
+      ("len", &TypeNode::Array(_)) |
+      ("len", &TypeNode::Map(..))  => Type::from(TypeNode::Int),
 
-          let last          = statements.last().unwrap();
-          let implicit_type = self.type_statement(last)?;
+      ("pop", &TypeNode::Array(ref element)) => (**element).clone(),
 
-          self.tabs.pop();
+      ("push", &TypeNode::Array(ref element)) => {
+        self.check_argument(element, &args[1])?;
 
-          if let Some(flag) = self.flag.clone() {
-            if let FlagContext::Block(ref consistent) = flag {
-              if let Some(ref consistent) = *consistent {
-                if implicit_type != *consistent {
-                  return Err(
-                    response!(
-                      Wrong(format!("mismatched types, expected `{}` found `{}`", consistent, implicit_type)),
-                      self.source.file,
-                      last.pos
-                    )
-                  )
-                }
-              } else {
-                self.flag = Some(FlagContext::Block(Some(implicit_type.clone())))
-              }
-            }
-          }
+        Type::from(TypeNode::Nil)
+      },
 
-          implicit_type
+      ("has", &TypeNode::Map(ref key, _)) => {
+        self.check_argument(key, &args[1])?;
 
-        } else {
-          Type::from(TypeNode::Nil)
-        };
+        Type::from(TypeNode::Bool)
+      },
 
-        self.flag = flag_backup;
+      ("remove", &TypeNode::Map(ref key, ref value)) => {
+        self.check_argument(key, &args[1])?;
 
-        block_type
+        (**value).clone()
       },
 
-      _ => Type::from(TypeNode::Nil)
+      _ => return Err(
+        response!(
+          Wrong(format!("can't `{}` on `{}`", name, collection_type)),
+          self.source.file,
+          self.source.resolve(args[0].pos)
+        )
+      )
     };
 
     Ok(t)
   }
 
+  fn type_format(&mut self, called: &'v Expression, args: &'v [Expression]) -> Result<Type, ()> {
+    let template = match args.first() {
+      Some(template) => template,
+      None           => return Err(
+        response!(
+          Wrong("`format` takes a format string"),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    };
+
+    self.check_argument(&Type::from(TypeNode::Str), template)?;
+
+    for arg in &args[1 ..] {
+      self.type_expression(arg)?;
+    }
+
+    // literal format strings are checked against their arguments up front
+    if let ExpressionNode::Str(ref content) = template.node {
+      let expected = match format::placeholders(content) {
+        Ok(expected) => expected,
+        Err(message) => return Err(
+          response!(
+            Wrong(message),
+            self.source.file,
+            self.source.resolve(template.pos)
+          )
+        )
+      };
+
+      if expected != args.len() - 1 {
+        return Err(
+          response!(
+            Wrong(format!("format string has {} placeholder(s) but {} argument(s) were given", expected, args.len() - 1)),
+            self.source.file,
+            self.source.resolve(called.pos)
+          )
+        )
+      }
+    }
+
+    Ok(Type::from(TypeNode::Str))
+  }
+
+  fn type_raise(&mut self, called: &'v Expression, args: &'v [Expression]) -> Result<Type, ()> {
+    if args.len() != 1 {
+      return Err(
+        response!(
+          Wrong(format!("`raise` takes 1 argument(s), got {}", args.len())),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    }
+
+    self.check_argument(&Type::from(TypeNode::Str), &args[0])?;
+
+    Ok(Type::from(TypeNode::Never))
+  }
+
+  // an int literal next to a sized operand takes its size
+  fn type_operands(&mut self, left: &'v Expression, right: &'v Expression) -> Result<(TypeNode, TypeNode), ()> {
+    let left_type  = self.type_expression(left)?.node;
+    let right_type = self.type_expression(right)?.node;
+
+    if left_type.is_sized() && right_type == TypeNode::Int && self.check_literal(&left_type, right)? {
+      Ok((left_type.clone(), left_type))
+    } else if right_type.is_sized() && left_type == TypeNode::Int && self.check_literal(&right_type, left)? {
+      Ok((right_type.clone(), right_type))
+    } else {
+      Ok((left_type, right_type))
+    }
+  }
+
+  // the wrapping and saturating alternatives to the arithmetic operators
+  fn type_overflowing(&mut self, name: &str, called: &'v Expression, args: &'v [Expression]) -> Result<Type, ()> {
+    if args.len() != 2 {
+      return Err(
+        response!(
+          Wrong(format!("`{}` takes 2 argument(s), got {}", name, args.len())),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    }
+
+    let (left_type, right_type) = self.type_operands(&args[0], &args[1])?;
+
+    if left_type != right_type || !(left_type == TypeNode::Int || left_type.is_sized()) {
+      return Err(
+        response!(
+          Wrong(format!("`{}` takes two integers of the same type, got `{}` and `{}`", name, left_type, right_type)),
+          self.source.file,
+          self.source.resolve(called.pos)
+        )
+      )
+    }
+
+    Ok(Type::from(left_type))
+  }
+
+  // the handler of a `try` sees the error message as `name`
+  fn type_catch(&mut self, name: &str, handler: &'v Expression) -> Result<Type, ()> {
+    let parent = self.current_tab().clone();
+
+    self.tabs.push(
+      (
+        SymTab::new(Rc::new(parent.0), &[name.to_string()]),
+        TypeTab::new(Rc::new(parent.1), &[Type::from(TypeNode::Str)])
+      )
+    );
+
+    let handler_type = self.type_expression(handler);
+
+    self.pop_scope();
+
+    handler_type
+  }
+
 
 
   pub fn current_tab(&mut self) -> &mut (SymTab, TypeTab) {
@@ -1111,6 +2202,50 @@ impl<'v> Visitor<'v> {
   }
 
   pub fn pop_scope(&mut self) {
+    self.trace_scope();
     self.tab_frames.push(self.tabs.pop().unwrap());
   }
+
+
+
+  // the statements of the innermost scope are done with, their lines are kept with the frame it leaves behind
+  fn trace_scope(&mut self) {
+    let depth = self.tabs.len();
+    let frame = self.tab_frames.len();
+
+    if let Some(trace) = self.trace.as_mut() {
+      let lines = trace.statements.iter().filter(|&&(_, d)| d == depth).map(|&(line, _)| line).collect::<Vec<usize>>();
+
+      trace.statements.retain(|&(_, d)| d != depth);
+
+      if let (Some(&first), Some(&last)) = (lines.iter().min(), lines.iter().max()) {
+        trace.scopes.push(((first, last), frame))
+      }
+    }
+  }
+
+  // types by where names were declared, in every scope checked so far
+  pub fn declared_types(&self) -> HashMap<Location, Type> {
+    let mut types = HashMap::new();
+
+    for &(ref symtab, ref typetab) in self.tab_frames.iter().chain(self.tabs.iter()) {
+      for (name, location) in symtab.locations.borrow().iter() {
+        let t = symtab.names.borrow().get(name).and_then(|&index| typetab.types.borrow().get(index).cloned());
+
+        if let Some(t) = t {
+          types.insert(*location, t);
+        }
+      }
+    }
+
+    types
+  }
+
+  fn trace_name(&mut self, name: &str, expression: &Expression) {
+    let declared = self.current_tab().0.get_location(name);
+
+    if let (Some(trace), Some(used), Some(declared)) = (self.trace.as_mut(), self.source.locate(expression.pos), declared) {
+      trace.definitions.insert(used, declared);
+    }
+  }
 }